@@ -0,0 +1,70 @@
+//! Compares reading a tokio stream through `Compat`, which zeroes every chunk before reading
+//! into it, with the native reads of a `MessageTokio` handler.
+//!
+//! Run with `cargo bench --features tokio --bench native_read`.
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use msg_io::{
+    r#async::AsyncMessageIo,
+    codec::{Endian, LengthDelimited, PrefixWidth},
+    tokio::MessageTokio,
+};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+const FRAME_LEN: usize = 64 * 1024;
+const FRAMES: usize = 256;
+const ROUNDS: u32 = 20;
+
+fn wire() -> Vec<u8> {
+    let mut wire = Vec::with_capacity(FRAMES * (FRAME_LEN + 4));
+    for i in 0..FRAMES {
+        wire.extend_from_slice(&(FRAME_LEN as u32).to_be_bytes());
+        wire.extend(std::iter::repeat_n(i as u8, FRAME_LEN));
+    }
+    wire
+}
+
+async fn read_all(wire: &[u8], native: bool) -> Duration {
+    let codec = LengthDelimited::new(PrefixWidth::U32, Endian::Big);
+    let mut reader = if native {
+        MessageTokio::new_reader::<_, _, Vec<u8>>(wire, codec)
+    } else {
+        AsyncMessageIo::new_reader::<Vec<u8>>(wire.compat(), codec)
+    }
+    .with_read_chunk_size(FRAME_LEN);
+    let start = Instant::now();
+    loop {
+        let message: Option<Vec<u8>> = reader.read_message().await.expect("Failed to read message");
+        match message {
+            Some(message) => {
+                black_box(message);
+            }
+            None => break,
+        }
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to build runtime");
+    let wire = wire();
+    for (name, native) in [("compat", false), ("native", true)] {
+        // One warm-up round, then the average of the rest.
+        runtime.block_on(read_all(&wire, native));
+        let mut total = Duration::ZERO;
+        for _ in 0..ROUNDS {
+            total += runtime.block_on(read_all(&wire, native));
+        }
+        let per_round = total / ROUNDS;
+        let mib_per_s = wire.len() as f64 / per_round.as_secs_f64() / (1024.0 * 1024.0);
+        println!(
+            "{name:>6}: {per_round:?} per {} MiB, {mib_per_s:.0} MiB/s",
+            wire.len() >> 20
+        );
+    }
+}
@@ -0,0 +1,9 @@
+//! Feeds arbitrary bytes to every built-in decoder, run with `cargo +nightly fuzz run decode`
+//! from the repository root.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    msg_io::fuzz::decode_all(data);
+});
@@ -1,23 +1,63 @@
 //! Asynchronous Message I/O handler using `futures` traits.
-use std::io;
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    io::{self, IoSlice},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
 
-use bytes::{Buf, BytesMut};
-use futures_util::{AsyncReadExt, AsyncWriteExt};
+use bytes::{Buf, Bytes, BytesMut};
+use futures_util::{
+    AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Sink, Stream,
+    io::{ReadHalf, WriteHalf},
+};
 
 use crate::{
     constants::{INITIAL_BUFFER_SIZE, TEMP_BUFFER_SIZE},
-    decoder::Decoder,
+    decoder::{Decoder, DecoderResult, MessageGuard, RefDecoder, decode_range, reset_and_resync},
     encoder::Encoder,
+    error::MsgIoError,
+    framer::reader::{FrameReader, ReadError, reader_settings},
+    observer::Observer,
 };
 
+/// The read half of an `AsyncMessageIo`, as returned by `AsyncMessageIo::split`.
+pub type AsyncMessageReader<R, D> = AsyncMessageIo<R, (), D>;
+
+/// The write half of an `AsyncMessageIo`, as returned by `AsyncMessageIo::split`.
+pub type AsyncMessageWriter<W, E> = AsyncMessageIo<W, E, ()>;
+
+/// Reads from a stream into the read buffer's spare capacity, see `poll_read_chunk`.
+pub(crate) type PollRead<S> =
+    fn(Pin<&mut S>, &mut Context<'_>, &mut [MaybeUninit<u8>]) -> Poll<io::Result<usize>>;
+
 /// Asynchronous Message I/O handler using `futures` traits.
 pub struct AsyncMessageIo<S, E, D> {
     stream: S,
     encoder: E,
-    decoder: D,
-    buffer: BytesMut,
+    reader: FrameReader<D>,
+    /// How the stream is read, `poll_read_zeroed` when unset.
+    poll_read: Option<PollRead<S>>,
+    observer: Option<Arc<dyn Observer>>,
+    write_buffer: BytesMut,
+    write_slices: VecDeque<Bytes>,
+    /// Lengths of the frames queued for writing, kept for the observer only.
+    queued_lens: VecDeque<usize>,
+    /// Bytes of the first frame in `queued_lens` already written.
+    queued_written: usize,
+    write_high_water_mark: usize,
+    interrupted_write: bool,
+    bytes_written: u64,
+    messages_written: u64,
 }
 
+// The encoder and decoder are never pinned, so only the stream decides whether we can move.
+impl<S: Unpin, E, D> Unpin for AsyncMessageIo<S, E, D> {}
+
 impl<S, E, D> AsyncMessageIo<S, E, D> {
     /// Creates a new MessageIo instance (Read & Write) with the given stream.
     ///
@@ -31,18 +71,364 @@ impl<S, E, D> AsyncMessageIo<S, E, D> {
     ///
     /// A new instance of `MessageIo`.
     fn new(stream: S, encoder: E, decoder: D) -> Self {
+        AsyncMessageIoBuilder::new().build(stream, encoder, decoder)
+    }
+
+    reader_settings!();
+
+    /// Sets an observer called as messages are read and written and on decode errors.
+    ///
+    /// By default there is none and nothing is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `observer`: The observer, see `Observer`.
+    ///   `split` shares it between both halves.
+    ///
+    /// # Returns
+    ///
+    /// The instance with the observer set.
+    pub fn with_observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Sets how many encoded bytes may pile up before they are written to the stream.
+    ///
+    /// Messages are encoded into the write buffer, which is only written out once it holds at
+    /// least this many bytes, or on `flush`. Coalescing many small messages this way saves a
+    /// write call per message. Bytes still buffered when the handler is dropped are lost, so
+    /// flush before dropping it. Defaults to 0, every message is written straight away.
+    ///
+    /// # Arguments
+    ///
+    /// * `high_water_mark`: The buffered byte count that triggers a write.
+    ///
+    /// # Returns
+    ///
+    /// The instance with the threshold applied.
+    pub fn with_write_high_water_mark(mut self, high_water_mark: usize) -> Self {
+        self.write_high_water_mark = high_water_mark;
+        self
+    }
+
+    /// Number of bytes written to the stream so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Number of messages encoded for writing so far.
+    pub fn messages_written(&self) -> u64 {
+        self.messages_written
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    ///
+    /// Reading or writing the stream directly bypasses the handler's buffers. Bytes already
+    /// read ahead stay in the read buffer and encoded bytes may still be queued for writing, so
+    /// raw I/O in the middle of a frame, or before a flush, corrupts the framing.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns a mutable reference to the encoder, e.g. to change its settings after a
+    /// handshake.
+    pub fn encoder_mut(&mut self) -> &mut E {
+        &mut self.encoder
+    }
+
+    /// Returns a mutable reference to the decoder, e.g. to change its settings after a
+    /// handshake.
+    ///
+    /// Bytes read ahead stay buffered and are decoded by the reconfigured decoder, so only
+    /// change it between frames. A decoder holding a partial frame through `ContinueFrom`
+    /// must not be changed at all.
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.reader.decoder
+    }
+
+    /// Replaces the encoder, messages encoded afterwards use the new one.
+    ///
+    /// Bytes already encoded and still buffered for writing are sent unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: The new encoder.
+    pub fn set_encoder(&mut self, encoder: E) {
+        self.encoder = encoder;
+    }
+
+    /// Replaces the decoder, keeping the bytes read ahead for it to decode.
+    ///
+    /// Only swap decoders between frames, see `decoder_mut`.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder`: The new decoder.
+    pub fn set_decoder(&mut self, decoder: D) {
+        self.reader.set_decoder(decoder);
+    }
+
+    /// Consumes the handler, returning the stream and the bytes read ahead but not yet decoded.
+    ///
+    /// Use this to hand the stream to another protocol without losing prefetched data,
+    /// `from_parts` builds the next handler from them. Bytes the decoder already took through
+    /// `ContinueFrom` are part of its state and not returned.
+    ///
+    /// # Returns
+    ///
+    /// The stream and the unconsumed read buffer.
+    pub fn into_parts(self) -> (S, BytesMut) {
+        (self.stream, self.reader.buffer)
+    }
+
+    /// Creates a handler over a stream some bytes were already read from, e.g. the parts
+    /// returned by `into_parts` after a protocol upgrade.
+    ///
+    /// `buffer` is decoded before anything more is read from the stream, so handing a stream
+    /// from one handler to the next loses no data. Other settings start at their defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: The stream to keep reading from and writing to.
+    /// * `buffer`: The bytes read from the stream but not yet decoded.
+    /// * `encoder`: An encoder that implements the `Encoder` trait, `()` for a reader.
+    /// * `decoder`: A decoder that implements the `Decoder` trait, `()` for a writer.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `AsyncMessageIo` seeded with `buffer`.
+    pub fn from_parts(stream: S, buffer: BytesMut, encoder: E, decoder: D) -> Self {
+        let mut io = Self::new(stream, encoder, decoder);
+        io.reader.buffer = buffer;
+        io
+    }
+
+    /// Consumes the handler, returning the stream.
+    ///
+    /// Buffered bytes are discarded, use `into_parts` to keep data read ahead, and flush
+    /// before calling this to send bytes still queued for writing.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Reads the stream with `poll_read` instead of zeroing the spare capacity for `AsyncRead`.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn set_poll_read(&mut self, poll_read: PollRead<S>) {
+        self.poll_read = Some(poll_read);
+    }
+
+    /// Splits the handler into a reader and a writer that can be used from separate tasks.
+    ///
+    /// The reader keeps the decoder, any data buffered but not yet decoded, and the frame size
+    /// limit. The writer keeps the encoder and any bytes queued through the `Sink`
+    /// implementation. The read half reads through its `AsyncRead`.
+    ///
+    /// # Returns
+    ///
+    /// The read half and the write half.
+    pub fn split(
+        self,
+    ) -> (
+        AsyncMessageReader<ReadHalf<S>, D>,
+        AsyncMessageWriter<WriteHalf<S>, E>,
+    )
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (read_half, write_half) = self.stream.split();
+        let reader = AsyncMessageIo {
+            stream: read_half,
+            encoder: (),
+            reader: self.reader,
+            poll_read: None,
+            observer: self.observer.clone(),
+            write_buffer: BytesMut::new(),
+            write_slices: VecDeque::new(),
+            queued_lens: VecDeque::new(),
+            queued_written: 0,
+            write_high_water_mark: 0,
+            interrupted_write: false,
+            bytes_written: 0,
+            messages_written: 0,
+        };
+        let writer = AsyncMessageIo {
+            stream: write_half,
+            encoder: self.encoder,
+            reader: FrameReader::new((), 0),
+            poll_read: None,
+            observer: self.observer,
+            write_buffer: self.write_buffer,
+            write_slices: self.write_slices,
+            queued_lens: self.queued_lens,
+            queued_written: self.queued_written,
+            write_high_water_mark: self.write_high_water_mark,
+            interrupted_write: self.interrupted_write,
+            bytes_written: self.bytes_written,
+            messages_written: self.messages_written,
+        };
+        (reader, writer)
+    }
+}
+
+/// Builder configuring an `AsyncMessageIo` before it is created.
+///
+/// The `new_reader`, `new_writer` and `new_rw` constructors build with the defaults.
+#[derive(Debug, Clone, Copy)]
+pub struct AsyncMessageIoBuilder {
+    initial_capacity: usize,
+    read_chunk_size: usize,
+    max_frame_size: Option<usize>,
+}
+
+impl AsyncMessageIoBuilder {
+    /// Creates a builder with the default settings of the `AsyncMessageIo` constructors.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `AsyncMessageIoBuilder`.
+    pub fn new() -> Self {
         Self {
+            initial_capacity: INITIAL_BUFFER_SIZE,
+            read_chunk_size: TEMP_BUFFER_SIZE,
+            max_frame_size: None,
+        }
+    }
+
+    /// Sets the maximum number of bytes requested from the stream per read.
+    ///
+    /// See `AsyncMessageIo::with_read_chunk_size`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `read_chunk_size` is zero.
+    pub fn read_chunk_size(mut self, read_chunk_size: usize) -> Self {
+        assert!(read_chunk_size > 0, "Read chunk size must be non-zero");
+        self.read_chunk_size = read_chunk_size;
+        self
+    }
+
+    /// Sets the initial capacity of the read buffer.
+    ///
+    /// See `AsyncMessageIo::with_buffer_capacity`.
+    pub fn initial_capacity(mut self, initial_capacity: usize) -> Self {
+        self.initial_capacity = initial_capacity;
+        self
+    }
+
+    /// Sets the maximum number of bytes a single frame may occupy.
+    ///
+    /// See `AsyncMessageIo::with_max_frame_size`.
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = Some(max_frame_size);
+        self
+    }
+
+    /// Creates a MessageIo instance for reading with the given stream.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `DT`: The type of the output data to be decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: An asynchronous stream that implements `AsyncReadExt`.
+    /// * `decoder`: A decoder that implements the `Decoder` trait.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MessageIo` for reading.
+    pub fn build_reader<S, D, DT>(self, stream: S, decoder: D) -> AsyncMessageIo<S, (), D>
+    where
+        S: AsyncReadExt + Unpin,
+        D: Decoder<DT>,
+    {
+        self.build(stream, (), decoder)
+    }
+
+    /// Creates a MessageIo instance for writing with the given stream.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `ET`: The type of the input data to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: An asynchronous stream that implements `AsyncWriteExt`.
+    /// * `encoder`: An encoder that implements the `Encoder` trait.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MessageIo` for writing.
+    pub fn build_writer<S, E, ET>(self, stream: S, encoder: E) -> AsyncMessageIo<S, E, ()>
+    where
+        S: AsyncWriteExt + Unpin,
+        E: Encoder<ET>,
+    {
+        self.build(stream, encoder, ())
+    }
+
+    /// Creates a MessageIo instance for reading and writing with the given stream.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `EDT`: The type of the input/out data to be encoded/decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: An asynchronous stream that implements both `AsyncReadExt` and `AsyncWriteExt`.
+    /// * `enc_dec`: An encoder/decoder that implements both `Encoder` and `Decoder` traits. Needs to be clone as well.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MessageIo` for reading and writing.
+    pub fn build_rw<S, ED, EDT>(self, stream: S, enc_dec: ED) -> AsyncMessageIo<S, ED, ED>
+    where
+        S: AsyncReadExt + AsyncWriteExt + Unpin,
+        ED: Encoder<EDT> + Decoder<EDT> + Clone,
+    {
+        self.build(stream, enc_dec.clone(), enc_dec)
+    }
+
+    fn build<S, E, D>(self, stream: S, encoder: E, decoder: D) -> AsyncMessageIo<S, E, D> {
+        let mut reader = FrameReader::new(decoder, self.initial_capacity);
+        reader.read_chunk_size = self.read_chunk_size;
+        reader.max_frame_size = self.max_frame_size;
+        AsyncMessageIo {
             stream,
             encoder,
-            decoder,
-            buffer: BytesMut::with_capacity(INITIAL_BUFFER_SIZE),
+            reader,
+            poll_read: None,
+            observer: None,
+            write_buffer: BytesMut::new(),
+            write_slices: VecDeque::new(),
+            queued_lens: VecDeque::new(),
+            queued_written: 0,
+            write_high_water_mark: 0,
+            interrupted_write: false,
+            bytes_written: 0,
+            messages_written: 0,
         }
     }
 }
 
+impl Default for AsyncMessageIoBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<S, ED> AsyncMessageIo<S, ED, ED> {
     /// Creates a new MessageIo instance for reading and writing with the given stream.
     ///
+    /// The reader and the writer each get their own clone of `enc_dec`, wrap it in
+    /// `codec::Shared` for both directions to share one codec state.
+    ///
     /// # Type Parameters
     ///
     /// * `EDT`: The type of the input/out data to be encoded/decoded.
@@ -62,6 +448,26 @@ impl<S, ED> AsyncMessageIo<S, ED, ED> {
     {
         Self::new(stream, enc_dec.clone(), enc_dec)
     }
+
+    /// Shuts down the write direction while keeping the read direction open, so the
+    /// connection ends gracefully on both sides.
+    ///
+    /// The steps happen in this order: frames still queued in the write buffer are written,
+    /// the stream is flushed, then its write side is shut down and the peer reads the end of
+    /// the stream once it has read every frame sent before. Reads are not affected, keep
+    /// calling `read_message` to drain what the peer still sends until it returns `Ok(None)`.
+    /// Nothing may be written afterwards. This is `close_write`, named for the full-duplex
+    /// handler.
+    ///
+    /// # Returns
+    ///
+    /// The result of the shutdown operation.
+    pub async fn shutdown(&mut self) -> io::Result<()>
+    where
+        S: AsyncWriteExt + Unpin,
+    {
+        self.close_write().await
+    }
 }
 
 impl<S, D> AsyncMessageIo<S, (), D> {
@@ -87,41 +493,23 @@ impl<S, D> AsyncMessageIo<S, (), D> {
         Self::new(stream, (), decoder)
     }
 
-    /// Reads a message from the stream using the specified decoder.
+    /// Converts the reader into a `Stream` of decoded messages.
     ///
     /// # Type Parameters
     ///
-    /// * `M`: The type of the message to be decoded.
+    /// * `M`: The type of the messages to be decoded.
     ///
     /// # Returns
     ///
-    /// The result of the read operation, which is either:
-    /// - `Ok(Some(M))`: A successfully decoded message.
-    /// - `Ok(None)`: Indicates the end of the stream.
-    /// - `Err(io::Error)`: An error occurred during reading or decoding.
-    pub async fn read_message<M>(&mut self) -> io::Result<Option<M>>
+    /// An `AsyncMessageStream` yielding `io::Result<M>` items, ending at the end of the stream.
+    pub fn into_stream<M>(self) -> AsyncMessageStream<S, D, M>
     where
         D: Decoder<M>,
-        S: AsyncReadExt + Unpin,
+        S: AsyncRead + Unpin,
     {
-        loop {
-            let mut temp = [0u8; TEMP_BUFFER_SIZE];
-            match self.stream.read(&mut temp).await? {
-                0 => return Ok(None),
-                n => {
-                    self.buffer.extend_from_slice(&temp[..n]);
-                    match self.decoder.decode(&self.buffer) {
-                        crate::decoder::DecoderResult::Continue => continue,
-                        crate::decoder::DecoderResult::Done(msg, used) => {
-                            self.buffer.advance(used);
-                            return Ok(Some(msg));
-                        }
-                        crate::decoder::DecoderResult::Error(e) => {
-                            return Err(io::Error::new(io::ErrorKind::InvalidData, e));
-                        }
-                    }
-                }
-            }
+        AsyncMessageStream {
+            io: self,
+            _message: PhantomData,
         }
     }
 }
@@ -148,9 +536,398 @@ impl<S, E> AsyncMessageIo<S, E, ()> {
     {
         Self::new(stream, encoder, ())
     }
+}
+
+impl<S, E, D> AsyncMessageIo<S, E, D> {
+    /// Reads a message from the stream using the specified decoder.
+    ///
+    /// Data already buffered from a previous read is decoded first, the stream is only read
+    /// when the decoder needs more data.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// The result of the read operation, which is either:
+    /// - `Ok(Some(M))`: A successfully decoded message.
+    /// - `Ok(None)`: Indicates the end of the stream.
+    /// - `Err(MsgIoError)`: An error occurred during reading or decoding. If the stream ends
+    ///   while a partial frame is buffered, the error is `MsgIoError::UnexpectedEof`.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This method is cancel safe. The future keeps no state of its own: every byte read from
+    /// the stream goes straight into the handler's buffer within the poll that reads it, and a
+    /// decoded message is returned from that same poll. Dropping the future, e.g. when another
+    /// branch of a `select!` or a timeout completes first, loses nothing, the next call picks up
+    /// the partial frame where this one left off. The same holds for the other read methods,
+    /// which all read through the same buffer.
+    pub async fn read_message<M>(&mut self) -> Result<Option<M>, MsgIoError>
+    where
+        D: Decoder<M>,
+        S: AsyncReadExt + Unpin,
+    {
+        Ok(poll_fn(|cx| Pin::new(&mut *self).poll_read_message(cx)).await?)
+    }
 
+    /// Polls for the next message, the poll-based counterpart of `read_message` for use
+    /// inside other `poll_*` functions or on custom executors.
+    ///
+    /// Bytes read before `Poll::Pending` is returned stay buffered, so nothing is lost between
+    /// polls and the reader is woken through `cx` once the stream has more data.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `cx`: The context of the current task.
+    ///
+    /// # Returns
+    ///
+    /// `Poll::Pending` while no complete message is available, otherwise the same result as
+    /// `read_message`.
+    pub fn poll_read_message<M>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<M>>>
+    where
+        D: Decoder<M>,
+        S: AsyncRead + Unpin,
+    {
+        self.get_mut().poll_next_message(cx, true)
+    }
+
+    /// Reads every message that is ready, appending them to `out`.
+    ///
+    /// Waits for the first message exactly like `read_message`, then keeps decoding frames that
+    /// are already buffered without reading from the stream again, so a burst of small frames
+    /// arriving in one read is handed over in one call. Messages decoded before an error are
+    /// left in `out`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the messages to be decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `out`: The vector to append the messages to.
+    ///
+    /// # Returns
+    ///
+    /// The number of messages appended, `0` indicating a clean end of the stream.
+    pub async fn read_messages<M>(&mut self, out: &mut Vec<M>) -> io::Result<usize>
+    where
+        D: Decoder<M>,
+        S: AsyncReadExt + Unpin,
+    {
+        let Some(first) = self.read_message().await? else {
+            return Ok(0);
+        };
+        out.push(first);
+        let mut count = 1;
+        while let Some(msg) = self.reader.decode(true, self.observer.as_deref())? {
+            out.push(msg);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Reads a frame and hands its payload to `f` straight from the read buffer, without
+    /// copying it into an owned message.
+    ///
+    /// The frame's bytes are dropped from the buffer once `f` returns, so `f` can parse the
+    /// payload in place and return whatever it extracted.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `R`: The type returned by `f`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: Called with the payload of the next frame.
+    ///
+    /// # Returns
+    ///
+    /// The value returned by `f`, or `None` at a clean end of the stream like `read_message`.
+    pub async fn read_message_with<R>(
+        &mut self,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> io::Result<Option<R>>
+    where
+        D: RefDecoder,
+        S: AsyncReadExt + Unpin,
+    {
+        let mut f = Some(f);
+        poll_fn(|cx| {
+            self.poll_read_message_by(cx, |reader, observer| {
+                reader.decode_by(
+                    true,
+                    observer,
+                    |_| 0,
+                    |decoder, data| {
+                        decoder.decode_ref(data).map(|frame| {
+                            let f = f.take().expect("Frame handed over more than once");
+                            f(&frame)
+                        })
+                    },
+                    D::resync,
+                )
+            })
+        })
+        .await
+    }
+
+    /// Reads a frame and returns its payload still in the read buffer, behind a guard that
+    /// drops the frame's bytes from the buffer once it is dropped.
+    ///
+    /// Like `read_message_with` without the closure: the payload can be parsed in place and
+    /// kept borrowed across other work, e.g. by a parser keeping its arena between frames. The
+    /// guard borrows the reader, so the next read waits until the payload is let go.
+    ///
+    /// # Returns
+    ///
+    /// The guard of the frame's payload, or `None` at a clean end of the stream like
+    /// `read_message`.
+    pub async fn read_message_arena(&mut self) -> io::Result<Option<MessageGuard<'_>>>
+    where
+        D: RefDecoder,
+        S: AsyncReadExt + Unpin,
+    {
+        let frame = poll_fn(|cx| {
+            self.poll_read_message_by(cx, |reader, observer| {
+                reader.decode_by(
+                    false,
+                    observer,
+                    |_| 0,
+                    |d, buf| decode_range(d, buf),
+                    D::resync,
+                )
+            })
+        })
+        .await?;
+        match frame {
+            Some((payload, used)) => Ok(Some(self.reader.message_guard(
+                payload,
+                used,
+                self.observer.as_deref(),
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a message's bytes into `out`, replacing its contents, so one buffer can be reused
+    /// for every message instead of allocating a `Vec` per frame.
+    ///
+    /// Frames are decoded with `Decoder::decode_into`, which copies the payload into `out` for
+    /// the byte framings of `codec`. Once `out` has grown to the largest payload, reading
+    /// further messages doesn't allocate for them.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type of the decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `out`: The buffer receiving the message's bytes.
+    ///
+    /// # Returns
+    ///
+    /// `true` once a message was read into `out`, `false` at a clean end of the stream like
+    /// `read_message` returning `None`.
+    pub async fn read_message_into<M>(&mut self, out: &mut Vec<u8>) -> io::Result<bool>
+    where
+        D: Decoder<M>,
+        M: Into<Vec<u8>>,
+        S: AsyncReadExt + Unpin,
+    {
+        let message = poll_fn(|cx| {
+            self.poll_read_message_by(cx, |reader, observer| {
+                reader.decode_by(
+                    true,
+                    observer,
+                    D::min_frame_hint,
+                    |decoder, buf| decoder.decode_into(buf, out),
+                    reset_and_resync::<M, D>,
+                )
+            })
+        })
+        .await?;
+        Ok(message.is_some())
+    }
+
+    /// Decodes the next message without consuming it, the following `read_message` or
+    /// `peek_message` returns the same message again.
+    ///
+    /// If no complete frame is buffered yet the stream is read until one is, exactly like
+    /// `read_message`. The decoder runs again on the same bytes when the message is read, so
+    /// decoders that take bytes with `ContinueFrom` or keep state past `Done` can't be peeked.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// The same as `read_message`.
+    pub async fn peek_message<M>(&mut self) -> io::Result<Option<M>>
+    where
+        D: Decoder<M>,
+        S: AsyncReadExt + Unpin,
+    {
+        poll_fn(|cx| self.poll_next_message(cx, false)).await
+    }
+
+    /// Whether a complete message is already buffered, so the next `read_message` returns
+    /// without reading from the stream.
+    ///
+    /// The decoder runs on the buffered bytes without consuming them, like `peek_message`
+    /// without the read. Decode errors are returned like from `read_message`, and with
+    /// resync enabled the bytes before the next frame boundary are dropped. As with
+    /// `peek_message`, decoders that take bytes with `ContinueFrom` or keep state past `Done`
+    /// can't be checked.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a message is buffered, `false` if the decoder needs more data.
+    pub fn has_buffered_message<M>(&mut self) -> io::Result<bool>
+    where
+        D: Decoder<M>,
+    {
+        Ok(self
+            .reader
+            .decode::<M>(false, self.observer.as_deref())?
+            .is_some())
+    }
+
+    /// Drops the next frame without keeping its message, e.g. one of a type `peek_message`
+    /// showed isn't wanted.
+    ///
+    /// The frame is decoded to find where it ends and the message is dropped straight away. If
+    /// no complete frame is buffered yet the stream is read until one is, exactly like
+    /// `read_message`, and the frame counts toward `messages_read` like a read one.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type of the decoder.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes the frame took up on the wire, or `None` at a clean end of the
+    /// stream like `read_message`.
+    pub async fn skip_message<M>(&mut self) -> io::Result<Option<usize>>
+    where
+        D: Decoder<M>,
+        S: AsyncReadExt + Unpin,
+    {
+        poll_fn(|cx| {
+            self.poll_read_message_by(cx, |reader, observer| {
+                // Bytes taken with `ContinueFrom` before the frame is done count toward it.
+                let consumed = reader.consumed();
+                let used = reader.decode_by(
+                    true,
+                    observer,
+                    D::min_frame_hint,
+                    |decoder, buf| match decoder.decode(buf) {
+                        DecoderResult::Done(_, used) => DecoderResult::Done(used, used),
+                        result => result.map(|_| 0),
+                    },
+                    reset_and_resync::<M, D>,
+                )?;
+                Ok(used.map(|used| consumed + used))
+            })
+        })
+        .await
+    }
+
+    /// Polls for the next message, reading from the stream only when the decoder needs more data.
+    ///
+    /// Bytes are appended to the internal buffer as soon as a read completes, so returning
+    /// `Poll::Pending` never loses data. The message's bytes are only dropped from the buffer
+    /// if `consume` is set.
+    fn poll_next_message<M>(
+        &mut self,
+        cx: &mut Context<'_>,
+        consume: bool,
+    ) -> Poll<io::Result<Option<M>>>
+    where
+        D: Decoder<M>,
+        S: AsyncRead + Unpin,
+    {
+        self.poll_read_message_by(cx, |reader, observer| reader.decode(consume, observer))
+    }
+
+    /// Polls the stream until `decode` returns a message from the buffered data.
+    fn poll_read_message_by<M>(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut decode: impl FnMut(
+            &mut FrameReader<D>,
+            Option<&dyn Observer>,
+        ) -> Result<Option<M>, ReadError>,
+    ) -> Poll<io::Result<Option<M>>>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let poll_read = self.poll_read.unwrap_or(poll_read_zeroed);
+        loop {
+            if self.reader.message_limit_reached() {
+                return Poll::Ready(Ok(None));
+            }
+            if let Some(msg) = decode(&mut self.reader, self.observer.as_deref())? {
+                return Poll::Ready(Ok(Some(msg)));
+            }
+            match ready!(self.poll_read_chunk(cx, poll_read))? {
+                0 if self.reader.at_frame_boundary() => return Poll::Ready(Ok(None)),
+                0 => {
+                    return Poll::Ready(Err(MsgIoError::UnexpectedEof.into()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads up to `read_chunk_size` bytes from the stream straight into the buffer's spare
+    /// capacity.
+    ///
+    /// `poll_read` reads from the stream into the spare capacity it is given and returns how
+    /// many bytes it filled at its start, every one of them initialized.
+    fn poll_read_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll_read: PollRead<S>,
+    ) -> Poll<io::Result<usize>>
+    where
+        S: Unpin,
+    {
+        let spare = self.reader.read_spare();
+        let len = spare.len();
+        let n = ready!(poll_read(Pin::new(&mut self.stream), cx, spare))?;
+        if n > len {
+            return Poll::Ready(Err(io::Error::other(
+                "Stream reported reading more bytes than requested",
+            )));
+        }
+        // SAFETY: `poll_read` initialized and filled the first `n <= len` bytes past the end.
+        unsafe { self.reader.advance_read(n) };
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<S, E, D> AsyncMessageIo<S, E, D> {
     /// Writes a message to the stream using the specified encoder.
     ///
+    /// The stream is not flushed, use `flush` or `write_message_flush` when writing to a
+    /// buffered stream. With a write high-water mark set, the message may also wait in the
+    /// write buffer until `flush`.
+    ///
     /// # Type Parameters
     ///
     /// * `M`: The type of the message to be encoded.
@@ -158,17 +935,415 @@ impl<S, E> AsyncMessageIo<S, E, ()> {
     /// # Returns
     ///
     /// The result of the write operation, which is either:
-    /// - `Ok(())`: The message was successfully written.
+    /// - `Ok(usize)`: The message was successfully written, taking up this many bytes on the
+    ///   wire including its framing.
+    /// - `Err(MsgIoError)`: An error occurred during encoding or writing, `MsgIoError::Encode`
+    ///   if the encoder rejected the message.
+    ///
+    /// # Cancel Safety
+    ///
+    /// The whole frame is encoded into the write buffer before any of it is written, and the
+    /// buffer keeps track of how much of it reached the stream. If the future is dropped partway
+    /// through, e.g. by a timeout, the next call to `write_message` first writes the rest of the
+    /// interrupted frame, then encodes and writes `message` behind it, so the peer sees the
+    /// interrupted frame intact. A `flush` in between writes the rest too.
+    ///
+    /// The same resume happens after a write fails with an I/O error: the unwritten rest of the
+    /// frame stays queued and goes out ahead of the next message. The sync handler instead drops
+    /// the frame when its write fails. An observer hears of the interrupted frame once that rest
+    /// is written, see `Observer::on_message_written`.
+    pub async fn write_message<M>(&mut self, message: M) -> Result<usize, MsgIoError>
+    where
+        E: Encoder<M>,
+        S: AsyncWriteExt + Unpin,
+    {
+        if self.interrupted_write {
+            poll_fn(|cx| self.poll_write_buffer(cx)).await?;
+        }
+        let len = self.encode_into_buffer(message)?;
+        self.messages_written += 1;
+        if self.write_buffer.len() >= self.write_high_water_mark {
+            // Cleared once the write completes or fails, so only a dropped future leaves it set.
+            self.interrupted_write = true;
+            poll_fn(|cx| self.poll_write_buffer(cx)).await?;
+        }
+        Ok(len)
+    }
+
+    /// Encodes a message with the writer's encoder and returns the frame, without writing it.
+    ///
+    /// For tracing, tests, or sending through a transport other than the stream. The encoder is
+    /// the one `write_message` uses, so a stateful encoder, e.g. one numbering its frames,
+    /// advances as if the message had been written. The stream, the write buffer and the
+    /// written counters are left untouched.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `message`: The message to encode.
+    ///
+    /// # Returns
+    ///
+    /// The encoded frame, including its framing.
+    pub fn encode_message<M>(&mut self, message: M) -> io::Result<Vec<u8>>
+    where
+        E: Encoder<M>,
+    {
+        self.encoder
+            .encode(message)
+            .map_err(|e| MsgIoError::Encode(e.into()).into())
+    }
+
+    /// Writes a message to the stream from the slices returned by `Encoder::encode_vectored`.
+    ///
+    /// The slices are handed to the stream with vectored writes instead of being copied into
+    /// the write buffer, so an encoder returning e.g. a header and a body as separate slices
+    /// never joins them. The stream is not flushed.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be encoded.
+    ///
+    /// # Returns
+    ///
+    /// The result of the write operation, which is either:
+    /// - `Ok(usize)`: The message was successfully written, taking up this many bytes on the
+    ///   wire including its framing.
     /// - `Err(io::Error)`: An error occurred during encoding or writing.
-    pub async fn write_message<M>(&mut self, message: M) -> io::Result<()>
+    pub async fn write_message_vectored<M>(&mut self, message: M) -> io::Result<usize>
     where
         E: Encoder<M>,
         S: AsyncWriteExt + Unpin,
     {
-        let encoded = self
+        // Earlier bytes must reach the stream first, the slices are queued behind them.
+        poll_fn(|cx| self.poll_write_buffer(cx)).await?;
+        let slices = self
             .encoder
-            .encode(message)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        self.stream.write_all(&encoded).await
+            .encode_vectored(message)
+            .map_err(|e| MsgIoError::Encode(e.into()))?;
+        let len = slices.iter().map(Bytes::len).sum();
+        self.write_slices
+            .extend(slices.into_iter().filter(|slice| !slice.is_empty()));
+        self.messages_written += 1;
+        if self.observer.is_some() {
+            self.queued_lens.push_back(len);
+        }
+        poll_fn(|cx| self.poll_write_buffer(cx)).await?;
+        Ok(len)
     }
+
+    /// Writes several messages to the stream with a single `write_all`.
+    ///
+    /// Every message is encoded before anything is written, so if any message fails to
+    /// encode nothing reaches the stream.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the messages to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages`: The messages to write, in order.
+    ///
+    /// # Returns
+    ///
+    /// The result of the write operation, which is either:
+    /// - `Ok(())`: All messages were successfully written.
+    /// - `Err(io::Error)`: An error occurred during encoding or writing.
+    pub async fn write_messages<'m, M>(&mut self, messages: &'m [M]) -> io::Result<()>
+    where
+        E: Encoder<&'m M>,
+        S: AsyncWriteExt + Unpin,
+    {
+        let start = self.write_buffer.len();
+        let queued = self.queued_lens.len();
+        for message in messages {
+            if let Err(e) = self.encode_into_buffer(message) {
+                self.write_buffer.truncate(start);
+                self.queued_lens.truncate(queued);
+                return Err(e);
+            }
+        }
+        self.messages_written += messages.len() as u64;
+        if self.write_buffer.len() >= self.write_high_water_mark {
+            poll_fn(|cx| self.poll_write_buffer(cx)).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes a message to the stream and then flushes it.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be encoded.
+    ///
+    /// # Returns
+    ///
+    /// The result of the write operation, which is either:
+    /// - `Ok(usize)`: The message was successfully written and flushed, taking up this many
+    ///   bytes on the wire including its framing.
+    /// - `Err(io::Error)`: An error occurred during encoding, writing, or flushing.
+    pub async fn write_message_flush<M>(&mut self, message: M) -> io::Result<usize>
+    where
+        E: Encoder<M>,
+        S: AsyncWriteExt + Unpin,
+    {
+        let len = self.write_message(message).await?;
+        self.flush().await?;
+        Ok(len)
+    }
+
+    /// Flushes any queued bytes and the underlying stream.
+    ///
+    /// # Returns
+    ///
+    /// The result of the flush operation.
+    pub async fn flush(&mut self) -> io::Result<()>
+    where
+        S: AsyncWriteExt + Unpin,
+    {
+        poll_fn(|cx| self.poll_write_buffer(cx)).await?;
+        self.stream.flush().await
+    }
+
+    /// Flushes any queued bytes and shuts down the write side of the stream.
+    ///
+    /// For sockets this is a half-close, the peer sees the end of the stream while messages
+    /// can still be read from it. Nothing may be written afterwards.
+    ///
+    /// # Returns
+    ///
+    /// The result of the close operation.
+    pub async fn close_write(&mut self) -> io::Result<()>
+    where
+        S: AsyncWriteExt + Unpin,
+    {
+        poll_fn(|cx| self.poll_write_buffer(cx)).await?;
+        self.stream.close().await
+    }
+
+    /// Encodes a message onto the end of the write buffer, leaving the buffer untouched if the
+    /// encoder fails. Returns the encoded length, which is queued for the observer, if any.
+    fn encode_into_buffer<M>(&mut self, message: M) -> io::Result<usize>
+    where
+        E: Encoder<M>,
+    {
+        if let Some(len) = self.encoder.size_hint(&message) {
+            self.write_buffer.reserve(len);
+        }
+        let start = self.write_buffer.len();
+        self.encoder
+            .encode_into(message, &mut self.write_buffer)
+            .map_err(|e| {
+                self.write_buffer.truncate(start);
+                MsgIoError::Encode(e.into())
+            })?;
+        let len = self.write_buffer.len() - start;
+        if self.observer.is_some() {
+            self.queued_lens.push_back(len);
+        }
+        Ok(len)
+    }
+
+    /// Counts `n` more queued bytes as written, reporting the frames they complete to the
+    /// observer, if any.
+    fn report_written(&mut self, n: usize) {
+        let Some(observer) = &self.observer else {
+            return;
+        };
+        self.queued_written += n;
+        while let Some(&len) = self.queued_lens.front()
+            && len <= self.queued_written
+        {
+            self.queued_written -= len;
+            self.queued_lens.pop_front();
+            observer.on_message_written(len);
+        }
+    }
+
+    /// Writes out everything queued, ending an interrupted write once it completes or fails.
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        let result = ready!(self.poll_write_queued(cx));
+        self.interrupted_write = false;
+        Poll::Ready(result)
+    }
+
+    /// Writes out the slices queued by `write_message_vectored`, then the bytes queued in the
+    /// write buffer.
+    fn poll_write_queued(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        while !self.write_slices.is_empty() {
+            let slices: Vec<IoSlice<'_>> = self
+                .write_slices
+                .iter()
+                .map(|slice| IoSlice::new(slice))
+                .collect();
+            let mut n = match ready!(Pin::new(&mut self.stream).poll_write_vectored(cx, &slices))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                n => n,
+            };
+            self.bytes_written += n as u64;
+            self.report_written(n);
+            while n > 0 {
+                let Some(front) = self.write_slices.front_mut() else {
+                    return Poll::Ready(Err(io::Error::other(
+                        "Stream reported writing more bytes than requested",
+                    )));
+                };
+                if n < front.len() {
+                    front.advance(n);
+                    break;
+                }
+                n -= front.len();
+                self.write_slices.pop_front();
+            }
+        }
+        while !self.write_buffer.is_empty() {
+            match ready!(Pin::new(&mut self.stream).poll_write(cx, &self.write_buffer))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                n => {
+                    self.write_buffer.advance(n);
+                    self.bytes_written += n as u64;
+                    self.report_written(n);
+                }
+            }
+        }
+        // Frames of no bytes are only written once everything before them is.
+        self.report_written(0);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads from an `AsyncRead` stream into `spare`, which is zeroed first since `poll_read` takes
+/// an initialized buffer.
+fn poll_read_zeroed<S: AsyncRead>(
+    stream: Pin<&mut S>,
+    cx: &mut Context<'_>,
+    spare: &mut [MaybeUninit<u8>],
+) -> Poll<io::Result<usize>> {
+    spare.fill(MaybeUninit::new(0));
+    // SAFETY: every byte of `spare` was initialized just above.
+    let dst = unsafe { &mut *(spare as *mut [MaybeUninit<u8>] as *mut [u8]) };
+    stream.poll_read(cx, dst)
+}
+
+/// A `Stream` of decoded messages, created by `AsyncMessageIo::into_stream`.
+pub struct AsyncMessageStream<S, D, M> {
+    io: AsyncMessageIo<S, (), D>,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<S, D, M> AsyncMessageStream<S, D, M> {
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &AsyncMessageIo<S, (), D> {
+        &self.io
+    }
+
+    /// Returns a mutable reference to the underlying reader, see `AsyncMessageIo::get_mut` for
+    /// the caveats of reaching the stream through it.
+    pub fn get_mut(&mut self) -> &mut AsyncMessageIo<S, (), D> {
+        &mut self.io
+    }
+
+    /// Consumes the stream, returning the underlying reader.
+    ///
+    /// # Returns
+    ///
+    /// The `AsyncMessageIo` reader, including any data it has buffered.
+    pub fn into_inner(self) -> AsyncMessageIo<S, (), D> {
+        self.io
+    }
+}
+
+impl<S, D, M> Stream for AsyncMessageStream<S, D, M>
+where
+    S: AsyncRead + Unpin,
+    D: Decoder<M>,
+{
+    type Item = io::Result<M>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .io
+            .poll_next_message(cx, true)
+            .map(Result::transpose)
+    }
+}
+
+/// Encodes each item into an internal buffer on `start_send`, the bytes reach the stream on
+/// `poll_flush`, `poll_close`, or the next `poll_ready`.
+impl<S, E, D, M> Sink<M> for AsyncMessageIo<S, E, D>
+where
+    S: AsyncWrite + Unpin,
+    E: Encoder<M>,
+{
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.write_slices.is_empty() && this.write_buffer.len() < this.write_high_water_mark {
+            return Poll::Ready(Ok(()));
+        }
+        this.poll_write_buffer(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: M) -> io::Result<()> {
+        let this = self.get_mut();
+        this.encode_into_buffer(item)?;
+        this.messages_written += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.stream).poll_close(cx)
+    }
+}
+
+/// Reads a single message from `stream`, then hands the stream back, e.g. for a handshake frame
+/// sent before the protocol switches modes.
+///
+/// The temporary reader may read past the end of the message, so the bytes read ahead are
+/// returned too. Pass them to `AsyncMessageIo::from_parts` along with the stream to carry on with
+/// the next stage without losing data.
+///
+/// # Type Parameters
+///
+/// * `M`: The type of the message to be decoded.
+///
+/// # Arguments
+///
+/// * `stream`: An asynchronous stream that implements `AsyncReadExt`.
+/// * `decoder`: A decoder that implements the `Decoder` trait.
+///
+/// # Returns
+///
+/// The result of the read operation, which is either:
+/// - `Ok((Option<M>, S, BytesMut))`: The message, or `None` at the end of the stream, along
+///   with the stream and the bytes read past the message.
+/// - `Err(io::Error)`: An error occurred during reading or decoding.
+pub async fn read_one<S, D, M>(stream: S, decoder: D) -> io::Result<(Option<M>, S, BytesMut)>
+where
+    S: AsyncReadExt + Unpin,
+    D: Decoder<M>,
+{
+    let mut reader = AsyncMessageIo::new_reader(stream, decoder);
+    let message = reader.read_message().await?;
+    let (stream, buffer) = reader.into_parts();
+    Ok((message, stream, buffer))
 }
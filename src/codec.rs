@@ -0,0 +1,355 @@
+//! Built-in codecs for common wire framings.
+//!
+//! Each codec implements both `Encoder` and `Decoder`, so it can be handed to any of the
+//! MessageIo constructors.
+mod any;
+#[cfg(feature = "bincode")]
+mod bincode;
+mod boxed;
+mod checksummed;
+mod cobs;
+#[cfg(feature = "flate2")]
+mod compressed;
+mod delimiter;
+#[cfg(feature = "crypto")]
+mod encrypted;
+mod fixed_length;
+mod hdlc;
+#[cfg(feature = "serde-json")]
+mod json;
+mod length_delimited;
+mod lines;
+#[cfg(feature = "rmp-serde")]
+mod msgpack;
+#[cfg(feature = "prost")]
+mod prost;
+mod raw;
+#[cfg(feature = "std")]
+mod shared;
+mod slip;
+mod suffix_checksum;
+mod suffix_length;
+mod tagged;
+mod varint_delimited;
+mod websocket;
+
+pub use any::AnyCodec;
+#[cfg(feature = "bincode")]
+pub use bincode::BincodeCodec;
+pub use boxed::{BoxedDecoder, BoxedEncoder};
+pub use checksummed::{Checksum, Checksummed};
+pub use cobs::Cobs;
+#[cfg(feature = "flate2")]
+pub use compressed::Compressed;
+pub use delimiter::DelimiterCodec;
+#[cfg(feature = "crypto")]
+pub use encrypted::{Encrypted, Role};
+pub use fixed_length::FixedLength;
+pub use hdlc::Hdlc;
+#[cfg(feature = "serde-json")]
+pub use json::JsonCodec;
+pub use length_delimited::{LengthDelimited, PrefixWidth};
+pub use lines::LinesCodec;
+#[cfg(feature = "rmp-serde")]
+pub use msgpack::MessagePackCodec;
+#[cfg(feature = "prost")]
+pub use prost::ProstCodec;
+pub use raw::{RawDecoder, RawEncoder};
+#[cfg(feature = "std")]
+pub use shared::Shared;
+pub use slip::Slip;
+pub use suffix_checksum::SuffixChecksum;
+pub use suffix_length::SuffixLength;
+pub use tagged::{TagDecodeFn, TagEncodeFn, Tagged};
+pub use varint_delimited::VarintDelimited;
+pub use websocket::{WebSocketFrame, WsFrame};
+
+use bytes::BufMut;
+
+use crate::error::CodecError;
+
+/// Adds the body length of a frame, as read off the wire, to the length of its header.
+///
+/// Lengths on the wire can be anything, so the sum is checked rather than left to overflow,
+/// e.g. a `u32` body length on a 32-bit target. Decoders call this before slicing the frame
+/// out of their input with `get`.
+///
+/// # Arguments
+///
+/// * `header_len`: The number of bytes in front of the body.
+/// * `body_len`: The body length announced by the header.
+///
+/// # Returns
+///
+/// The length of the whole frame, or an error if it doesn't fit in a `usize`.
+pub(crate) fn frame_len(header_len: usize, body_len: u64) -> Result<usize, CodecError> {
+    usize::try_from(body_len)
+        .ok()
+        .and_then(|len| len.checked_add(header_len))
+        .ok_or_else(|| "Frame length exceeds addressable memory".into())
+}
+
+/// Byte order used for numeric fields on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    /// Most significant byte first (network byte order).
+    Big,
+    /// Least significant byte first.
+    Little,
+}
+
+impl Endian {
+    /// Reads a `u16` in this byte order.
+    pub fn read_u16(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Endian::Big => u16::from_be_bytes(bytes),
+            Endian::Little => u16::from_le_bytes(bytes),
+        }
+    }
+
+    /// Reads a `u32` in this byte order.
+    pub fn read_u32(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endian::Big => u32::from_be_bytes(bytes),
+            Endian::Little => u32::from_le_bytes(bytes),
+        }
+    }
+
+    /// Reads a `u64` in this byte order.
+    pub fn read_u64(self, bytes: [u8; 8]) -> u64 {
+        match self {
+            Endian::Big => u64::from_be_bytes(bytes),
+            Endian::Little => u64::from_le_bytes(bytes),
+        }
+    }
+
+    /// Reads an unsigned integer of any width up to 8 bytes, e.g. a 3 byte length field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is longer than 8 bytes.
+    pub fn read_uint(self, bytes: &[u8]) -> u64 {
+        assert!(bytes.len() <= 8, "Integers are at most 8 bytes wide");
+        let fold = |value: u64, byte: &u8| (value << 8) | u64::from(*byte);
+        match self {
+            Endian::Big => bytes.iter().fold(0, fold),
+            Endian::Little => bytes.iter().rev().fold(0, fold),
+        }
+    }
+
+    /// Writes a `u16` in this byte order.
+    pub fn write_u16(self, value: u16, dst: &mut impl BufMut) {
+        match self {
+            Endian::Big => dst.put_u16(value),
+            Endian::Little => dst.put_u16_le(value),
+        }
+    }
+
+    /// Writes a `u32` in this byte order.
+    pub fn write_u32(self, value: u32, dst: &mut impl BufMut) {
+        match self {
+            Endian::Big => dst.put_u32(value),
+            Endian::Little => dst.put_u32_le(value),
+        }
+    }
+
+    /// Writes a `u64` in this byte order.
+    pub fn write_u64(self, value: u64, dst: &mut impl BufMut) {
+        match self {
+            Endian::Big => dst.put_u64(value),
+            Endian::Little => dst.put_u64_le(value),
+        }
+    }
+
+    /// Writes the low `size` bytes of `value`, the counterpart of `read_uint`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is larger than 8.
+    pub fn write_uint(self, value: u64, size: usize, dst: &mut impl BufMut) {
+        assert!(size <= 8, "Integers are at most 8 bytes wide");
+        match self {
+            Endian::Big => dst.put_slice(&value.to_be_bytes()[8 - size..]),
+            Endian::Little => dst.put_slice(&value.to_le_bytes()[..size]),
+        }
+    }
+}
+
+/// Checks that `codec` decodes what it encodes, for the tests of codecs.
+///
+/// `sample` is encoded, then the frame is decoded twice: handed over whole, and one byte at a
+/// time the way a reader sees a frame trickle in. Both must decode a message equal to `sample`
+/// using exactly the encoded length, which catches an off-by-one `used` as well as decoders
+/// misreading a partial frame. The decoder's results are followed like a reader does, bytes
+/// taken with `ContinueFrom` are dropped from the data given to the next call.
+///
+/// # Arguments
+///
+/// * `codec`: The codec to check.
+/// * `sample`: The message to encode and decode.
+///
+/// # Panics
+///
+/// Panics if encoding or decoding fails, or if either decoded message or its length differs
+/// from the encoded one.
+#[cfg(feature = "testing")]
+pub fn assert_roundtrip<T, C>(codec: &mut C, sample: &T)
+where
+    T: Clone + PartialEq + core::fmt::Debug,
+    C: crate::encoder::Encoder<T> + crate::decoder::Decoder<T>,
+{
+    let frame = codec
+        .encode(sample.clone())
+        .expect("Failed to encode the sample");
+    for chunk in [frame.len().max(1), 1] {
+        let (decoded, used) = decode_in_chunks(codec, &frame, chunk);
+        assert_eq!(
+            &decoded, sample,
+            "Decoded message differs from the sample, fed {chunk} bytes at a time"
+        );
+        assert_eq!(
+            used,
+            frame.len(),
+            "Decoder used a different length than was encoded, fed {chunk} bytes at a time"
+        );
+    }
+}
+
+/// Decodes `frame` handing it to `decoder` `chunk` bytes at a time, returning the message and
+/// the number of bytes it took up.
+#[cfg(feature = "testing")]
+fn decode_in_chunks<T, D: crate::decoder::Decoder<T>>(
+    decoder: &mut D,
+    frame: &[u8],
+    chunk: usize,
+) -> (T, usize) {
+    use crate::decoder::DecoderResult;
+
+    // Bytes taken with `ContinueFrom`, and the end of the bytes handed over so far.
+    let (mut consumed, mut fed) = (0, 0);
+    loop {
+        let data = &frame[consumed..fed];
+        let result = match data {
+            [] => DecoderResult::Continue,
+            data => decoder.decode(data),
+        };
+        match result {
+            DecoderResult::Done(message, used) => return (message, consumed + used),
+            DecoderResult::ContinueFrom(skip) if consumed + skip <= fed => {
+                consumed += skip;
+                if fed < frame.len() {
+                    fed = (fed + chunk).min(frame.len());
+                }
+            }
+            DecoderResult::ContinueFrom(_) => {
+                panic!("Decoder skipped past the data it was given, fed {chunk} bytes at a time")
+            }
+            DecoderResult::Continue | DecoderResult::NeedMore(_) => {
+                assert!(
+                    fed < frame.len(),
+                    "Decoder needs more than the encoded frame, fed {chunk} bytes at a time"
+                );
+                fed = (fed + chunk).min(frame.len());
+            }
+            DecoderResult::Error(e) | DecoderResult::ErrorAt(e, _) => {
+                panic!("Failed to decode the encoded frame, fed {chunk} bytes at a time: {e}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_assert_roundtrip_length_delimited() {
+        for width in [PrefixWidth::U8, PrefixWidth::U16, PrefixWidth::U32] {
+            let mut codec = LengthDelimited::new(width, Endian::Little);
+            assert_roundtrip::<Vec<u8>, _>(&mut codec, &b"round trip".to_vec());
+            assert_roundtrip::<Vec<u8>, _>(&mut codec, &Vec::new());
+        }
+    }
+
+    /// Prefixes payloads with their length, but decodes whatever part of a payload arrived
+    /// instead of waiting for the rest.
+    #[cfg(feature = "testing")]
+    struct Impatient;
+
+    #[cfg(feature = "testing")]
+    impl crate::encoder::Encoder<Vec<u8>> for Impatient {
+        type Error = CodecError;
+
+        fn encode(&mut self, data: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+            let mut frame = alloc::vec![data.len() as u8];
+            frame.extend(data);
+            Ok(frame)
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    impl crate::decoder::Decoder<Vec<u8>> for Impatient {
+        type Error = CodecError;
+
+        fn decode(&mut self, data: &[u8]) -> crate::decoder::DecoderResult<Vec<u8>> {
+            match data.split_first() {
+                Some((&len, payload)) => {
+                    let payload = &payload[..payload.len().min(len as usize)];
+                    crate::decoder::DecoderResult::Done(payload.to_vec(), 1 + payload.len())
+                }
+                None => crate::decoder::DecoderResult::Continue,
+            }
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    #[should_panic(expected = "fed 1 bytes at a time")]
+    fn test_assert_roundtrip_catches_partial_reads() {
+        assert_roundtrip::<Vec<u8>, _>(&mut Impatient, &b"abc".to_vec());
+    }
+
+    #[test]
+    fn test_endian_round_trips() {
+        for endian in [Endian::Big, Endian::Little] {
+            let mut dst = Vec::new();
+            endian.write_u16(0x0102, &mut dst);
+            endian.write_u32(0x0304_0506, &mut dst);
+            endian.write_u64(0x0708_090A_0B0C_0D0E, &mut dst);
+            assert_eq!(endian.read_u16(dst[..2].try_into().unwrap()), 0x0102);
+            assert_eq!(endian.read_u32(dst[2..6].try_into().unwrap()), 0x0304_0506);
+            assert_eq!(
+                endian.read_u64(dst[6..14].try_into().unwrap()),
+                0x0708_090A_0B0C_0D0E
+            );
+            for size in 1..=8 {
+                let value = 0x1122_3344_5566_7788u64 >> (64 - 8 * size);
+                let mut dst = Vec::new();
+                endian.write_uint(value, size, &mut dst);
+                assert_eq!(dst.len(), size);
+                assert_eq!(endian.read_uint(&dst), value, "{endian:?} {size} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_len_overflow() {
+        assert_eq!(frame_len(4, 16).unwrap(), 20);
+        assert!(frame_len(usize::MAX, 1).is_err());
+        assert!(frame_len(10, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_endian_byte_order() {
+        let mut big = Vec::new();
+        Endian::Big.write_u32(0x0102_0304, &mut big);
+        assert_eq!(big, [1, 2, 3, 4]);
+        let mut little = Vec::new();
+        Endian::Little.write_u32(0x0102_0304, &mut little);
+        assert_eq!(little, [4, 3, 2, 1]);
+        assert_eq!(Endian::Big.read_uint(&[1, 2, 3]), 0x01_0203);
+        assert_eq!(Endian::Little.read_uint(&[1, 2, 3]), 0x03_0201);
+    }
+}
@@ -0,0 +1,201 @@
+//! A codec chosen at runtime from the built-in byte framings.
+use alloc::{format, string::String, vec::Vec};
+
+use bytes::BytesMut;
+
+use crate::{
+    codec::{
+        Cobs, DelimiterCodec, FixedLength, Hdlc, LengthDelimited, LinesCodec, Slip, SuffixLength,
+        VarintDelimited,
+    },
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec dispatching to one of the built-in framings of byte payloads.
+///
+/// Useful when the framing is only known at runtime, e.g. from a config value, without boxing
+/// the codec behind a `dyn` trait object. Every variant encodes and decodes `Vec<u8>` payloads.
+/// `Lines` encodes payloads that are valid UTF-8 only, and decodes lines to their bytes.
+#[derive(Debug, Clone)]
+pub enum AnyCodec {
+    /// Payloads prefixed with their length, see `LengthDelimited`.
+    LengthDelimited(LengthDelimited),
+    /// Payloads prefixed with their length as a varint, see `VarintDelimited`.
+    VarintDelimited(VarintDelimited),
+    /// Payloads followed by their length, see `SuffixLength`.
+    SuffixLength(SuffixLength),
+    /// Payloads of a fixed size, see `FixedLength`.
+    FixedLength(FixedLength),
+    /// Payloads terminated by a delimiter, see `DelimiterCodec`.
+    Delimiter(DelimiterCodec),
+    /// Newline terminated UTF-8 lines, see `LinesCodec`.
+    Lines(LinesCodec),
+    /// COBS stuffed payloads, see `Cobs`.
+    Cobs(Cobs),
+    /// SLIP framed payloads, see `Slip`.
+    Slip(Slip),
+    /// HDLC framed payloads, see `Hdlc`.
+    Hdlc(Hdlc),
+}
+
+/// Checks that a payload for `AnyCodec::Lines` is a valid line of text.
+fn as_line(data: &[u8]) -> Result<&str, CodecError> {
+    core::str::from_utf8(data).map_err(|e| format!("Line is not valid UTF-8: {e}").into())
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for AnyCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        match self {
+            AnyCodec::LengthDelimited(codec) => codec.encode(data),
+            AnyCodec::VarintDelimited(codec) => codec.encode(data),
+            AnyCodec::SuffixLength(codec) => codec.encode(data),
+            AnyCodec::FixedLength(codec) => codec.encode(data),
+            AnyCodec::Delimiter(codec) => codec.encode(data),
+            AnyCodec::Lines(codec) => codec.encode(as_line(data.as_ref())?),
+            AnyCodec::Cobs(codec) => codec.encode(data),
+            AnyCodec::Slip(codec) => codec.encode(data),
+            AnyCodec::Hdlc(codec) => codec.encode(data),
+        }
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match self {
+            AnyCodec::LengthDelimited(codec) => codec.encode_into(data, dst),
+            AnyCodec::VarintDelimited(codec) => codec.encode_into(data, dst),
+            AnyCodec::SuffixLength(codec) => codec.encode_into(data, dst),
+            AnyCodec::FixedLength(codec) => codec.encode_into(data, dst),
+            AnyCodec::Delimiter(codec) => codec.encode_into(data, dst),
+            AnyCodec::Lines(codec) => codec.encode_into(as_line(data.as_ref())?, dst),
+            AnyCodec::Cobs(codec) => codec.encode_into(data, dst),
+            AnyCodec::Slip(codec) => codec.encode_into(data, dst),
+            AnyCodec::Hdlc(codec) => codec.encode_into(data, dst),
+        }
+    }
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        match self {
+            AnyCodec::LengthDelimited(codec) => codec.size_hint(data),
+            AnyCodec::VarintDelimited(codec) => codec.size_hint(data),
+            AnyCodec::SuffixLength(codec) => codec.size_hint(data),
+            AnyCodec::FixedLength(codec) => codec.size_hint(data),
+            AnyCodec::Delimiter(codec) => codec.size_hint(data),
+            AnyCodec::Cobs(codec) => codec.size_hint(data),
+            AnyCodec::Slip(codec) => codec.size_hint(data),
+            AnyCodec::Hdlc(codec) => codec.size_hint(data),
+            AnyCodec::Lines(_) => None,
+        }
+    }
+}
+
+impl Decoder<Vec<u8>> for AnyCodec {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        match self {
+            AnyCodec::LengthDelimited(codec) => codec.decode(data),
+            AnyCodec::VarintDelimited(codec) => codec.decode(data),
+            AnyCodec::SuffixLength(codec) => codec.decode(data),
+            AnyCodec::FixedLength(codec) => codec.decode(data),
+            AnyCodec::Delimiter(codec) => codec.decode(data),
+            AnyCodec::Lines(codec) => {
+                Decoder::<String>::decode(codec, data).map(String::into_bytes)
+            }
+            AnyCodec::Cobs(codec) => codec.decode(data),
+            AnyCodec::Slip(codec) => codec.decode(data),
+            AnyCodec::Hdlc(codec) => codec.decode(data),
+        }
+    }
+
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<()> {
+        match self {
+            AnyCodec::LengthDelimited(codec) => codec.decode_into(data, out),
+            AnyCodec::VarintDelimited(codec) => codec.decode_into(data, out),
+            AnyCodec::SuffixLength(codec) => codec.decode_into(data, out),
+            AnyCodec::FixedLength(codec) => codec.decode_into(data, out),
+            AnyCodec::Delimiter(codec) => codec.decode_into(data, out),
+            AnyCodec::Lines(codec) => Decoder::<String>::decode_into(codec, data, out),
+            AnyCodec::Cobs(codec) => codec.decode_into(data, out),
+            AnyCodec::Slip(codec) => codec.decode_into(data, out),
+            AnyCodec::Hdlc(codec) => codec.decode_into(data, out),
+        }
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        match self {
+            AnyCodec::LengthDelimited(codec) => codec.min_frame_hint(),
+            AnyCodec::VarintDelimited(codec) => codec.min_frame_hint(),
+            AnyCodec::SuffixLength(codec) => codec.min_frame_hint(),
+            AnyCodec::FixedLength(codec) => codec.min_frame_hint(),
+            AnyCodec::Delimiter(codec) => codec.min_frame_hint(),
+            AnyCodec::Lines(codec) => Decoder::<String>::min_frame_hint(codec),
+            AnyCodec::Cobs(codec) => codec.min_frame_hint(),
+            AnyCodec::Slip(codec) => codec.min_frame_hint(),
+            AnyCodec::Hdlc(codec) => codec.min_frame_hint(),
+        }
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        match self {
+            AnyCodec::LengthDelimited(codec) => Decoder::<Vec<u8>>::resync(codec, data),
+            AnyCodec::VarintDelimited(codec) => codec.resync(data),
+            AnyCodec::SuffixLength(codec) => codec.resync(data),
+            AnyCodec::FixedLength(codec) => Decoder::<Vec<u8>>::resync(codec, data),
+            AnyCodec::Delimiter(codec) => Decoder::<Vec<u8>>::resync(codec, data),
+            AnyCodec::Lines(codec) => Decoder::<String>::resync(codec, data),
+            AnyCodec::Cobs(codec) => codec.resync(data),
+            AnyCodec::Slip(codec) => codec.resync(data),
+            AnyCodec::Hdlc(codec) => codec.resync(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Picks a codec by name, as a proxy would from its config.
+    fn from_config(framing: &str) -> AnyCodec {
+        match framing {
+            "lines" => AnyCodec::Lines(LinesCodec::new().with_strip_carriage_return(true)),
+            "length" => AnyCodec::LengthDelimited(LengthDelimited::u16_be()),
+            _ => AnyCodec::Cobs(Cobs::new()),
+        }
+    }
+
+    #[test]
+    fn test_lines_round_trip() {
+        let mut codec = from_config("lines");
+        assert!(matches!(codec, AnyCodec::Lines(_)));
+        let encoded = codec.encode(b"hello".to_vec()).expect("Failed to encode");
+        assert_eq!(encoded, b"hello\n");
+        match codec.decode(b"hello\r\nworld") {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, b"hello");
+                assert_eq!(used, 7);
+            }
+            _ => panic!("Expected a decoded line"),
+        }
+        assert!(codec.encode(vec![0xFF]).is_err());
+        assert!(codec.encode(b"two\nlines".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_dispatches_to_variant() {
+        let mut codec = from_config("length");
+        let encoded = codec.encode(b"framed".to_vec()).expect("Failed to encode");
+        assert_eq!(encoded, b"\x00\x06framed");
+        assert_eq!(codec.size_hint(&b"framed".to_vec()), Some(encoded.len()));
+        assert_eq!(Decoder::<Vec<u8>>::min_frame_hint(&codec), 2);
+        let mut out = Vec::new();
+        assert!(matches!(
+            codec.decode_into(&encoded, &mut out),
+            DecoderResult::Done((), 8)
+        ));
+        assert_eq!(out, b"framed");
+    }
+}
@@ -0,0 +1,164 @@
+//! Binary serialization via `bincode`.
+use std::{fmt, marker::PhantomData};
+
+use bincode::Options;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    codec::{Endian, LengthDelimited, PrefixWidth},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec serializing messages of type `T` with `bincode`, framed by a big-endian `u32` length
+/// prefix.
+///
+/// Deserialization is limited to the frame length, so a corrupted body can't make `bincode`
+/// allocate more memory than the frame itself occupies.
+pub struct BincodeCodec<T> {
+    framing: LengthDelimited,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T> BincodeCodec<T> {
+    /// Creates a new bincode codec.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `BincodeCodec`.
+    pub fn new() -> Self {
+        Self {
+            framing: LengthDelimited::new(PrefixWidth::U32, Endian::Big),
+            _message: PhantomData,
+        }
+    }
+
+    fn encode_value<V: Serialize + ?Sized>(&mut self, value: &V) -> Result<Vec<u8>, CodecError> {
+        let payload = bincode::DefaultOptions::new()
+            .serialize(value)
+            .map_err(CodecError::new)?;
+        self.framing.encode(payload)
+    }
+}
+
+impl<T> Default for BincodeCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for BincodeCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            framing: self.framing.clone(),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for BincodeCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BincodeCodec")
+            .field("framing", &self.framing)
+            .finish()
+    }
+}
+
+impl<T: Serialize> Encoder<T> for BincodeCodec<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        self.encode_value(&data)
+    }
+}
+
+impl<'a, T: Serialize> Encoder<&'a T> for BincodeCodec<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: &'a T) -> Result<Vec<u8>, Self::Error> {
+        self.encode_value(data)
+    }
+}
+
+impl<T: DeserializeOwned> Decoder<T> for BincodeCodec<T> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T> {
+        let (payload, used) = match self.framing.decode(data) {
+            DecoderResult::Done(payload, used) => (payload, used),
+            DecoderResult::Continue => return DecoderResult::Continue,
+            DecoderResult::ContinueFrom(skip) => return DecoderResult::ContinueFrom(skip),
+            DecoderResult::NeedMore(len) => return DecoderResult::NeedMore(len),
+            DecoderResult::Error(e) => return DecoderResult::Error(e),
+            DecoderResult::ErrorAt(e, offset) => return DecoderResult::ErrorAt(e, offset),
+        };
+        let options = bincode::DefaultOptions::new().with_limit(payload.len() as u64);
+        match options.deserialize(&payload) {
+            Ok(message) => DecoderResult::Done(message, used),
+            Err(e) => DecoderResult::Error(CodecError::new(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Header {
+        version: u8,
+        flags: Vec<bool>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Packet {
+        header: Header,
+        body: Vec<u8>,
+        label: Option<String>,
+    }
+
+    fn packet() -> Packet {
+        Packet {
+            header: Header {
+                version: 2,
+                flags: vec![true, false, true],
+            },
+            body: vec![0xDE, 0xAD, 0xBE, 0xEF],
+            label: Some("nested".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_nested_struct() {
+        let mut codec = BincodeCodec::<Packet>::new();
+        let encoded = codec.encode(&packet()).expect("Failed to encode");
+        let len = u32::from_be_bytes(encoded[..4].try_into().unwrap()) as usize;
+        assert_eq!(len + 4, encoded.len());
+        match codec.decode(&encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, packet());
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded message"),
+        }
+        assert!(matches!(
+            codec.decode(&encoded[..encoded.len() - 1]),
+            DecoderResult::Continue
+        ));
+    }
+
+    #[test]
+    fn test_corrupted_body() {
+        let mut codec = BincodeCodec::<Packet>::new();
+        let mut encoded = codec.encode(&packet()).expect("Failed to encode");
+        // Claim an enormous number of flags; decoding must fail rather than allocate or panic.
+        encoded[5..].fill(0xFF);
+        assert!(matches!(codec.decode(&encoded), DecoderResult::Error(_)));
+        // A body cut short inside its own frame is also an error.
+        let frame = [0, 0, 0, 2, 2, 3];
+        assert!(matches!(codec.decode(&frame), DecoderResult::Error(_)));
+    }
+}
@@ -0,0 +1,218 @@
+//! Boxed encoders and decoders, for picking codecs at runtime behind a trait object.
+use alloc::{boxed::Box, vec::Vec};
+use core::{error::Error, fmt};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Encoder of `T` messages boxed behind a trait object.
+///
+/// Any `Encoder<T>` can be boxed, its error converted into a `CodecError`, so encoders of
+/// different types fit in one field or collection, e.g. codecs loaded as plugins. Each call
+/// goes through a virtual call, see `AnyCodec` for the built-in framings without one.
+pub struct BoxedEncoder<T>(Box<dyn Encoder<T, Error = CodecError> + Send>);
+
+impl<T> BoxedEncoder<T> {
+    /// Boxes an encoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: The encoder to box.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `BoxedEncoder`.
+    pub fn new<E: Encoder<T> + Send + 'static>(encoder: E) -> Self {
+        Self(Box::new(Erased(encoder)))
+    }
+}
+
+impl<T> fmt::Debug for BoxedEncoder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedEncoder").finish_non_exhaustive()
+    }
+}
+
+impl<T> Encoder<T> for BoxedEncoder<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        self.0.encode(data)
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode_into(data, dst)
+    }
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        self.0.size_hint(data)
+    }
+
+    fn encode_vectored(&mut self, data: T) -> Result<Vec<Bytes>, Self::Error> {
+        self.0.encode_vectored(data)
+    }
+}
+
+/// Decoder of `T` messages boxed behind a trait object.
+///
+/// Any `Decoder<T>` can be boxed, its error converted into a `CodecError`, so decoders of
+/// different types fit in one field or collection, e.g. codecs loaded as plugins. Each call
+/// goes through a virtual call, see `AnyCodec` for the built-in framings without one.
+pub struct BoxedDecoder<T>(Box<dyn Decoder<T, Error = CodecError> + Send>);
+
+impl<T> BoxedDecoder<T> {
+    /// Boxes a decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder`: The decoder to box.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `BoxedDecoder`.
+    pub fn new<D: Decoder<T> + Send + 'static>(decoder: D) -> Self {
+        Self(Box::new(Erased(decoder)))
+    }
+}
+
+impl<T> fmt::Debug for BoxedDecoder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedDecoder").finish_non_exhaustive()
+    }
+}
+
+impl<T> Decoder<T> for BoxedDecoder<T> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T, Self::Error> {
+        self.0.decode(data)
+    }
+
+    fn decode_bytes(&mut self, buf: &mut BytesMut) -> DecoderResult<T, Self::Error> {
+        self.0.decode_bytes(buf)
+    }
+
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<(), Self::Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.0.decode_into(data, out)
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        self.0.min_frame_hint()
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        self.0.resync(data)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+/// Wraps a codec to report its errors as `CodecError`, giving every boxed codec the same
+/// error type.
+struct Erased<C>(C);
+
+/// Converts a codec's error into a `CodecError`, without wrapping one that already is.
+fn erase(error: impl Error + Send + Sync + 'static) -> CodecError {
+    let error: Box<dyn Error + Send + Sync> = Box::new(error);
+    match error.downcast::<CodecError>() {
+        Ok(error) => *error,
+        Err(error) => CodecError::new(error),
+    }
+}
+
+impl<C: Encoder<T>, T> Encoder<T> for Erased<C> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        self.0.encode(data).map_err(erase)
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode_into(data, dst).map_err(erase)
+    }
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        self.0.size_hint(data)
+    }
+
+    fn encode_vectored(&mut self, data: T) -> Result<Vec<Bytes>, Self::Error> {
+        self.0.encode_vectored(data).map_err(erase)
+    }
+}
+
+impl<C: Decoder<T>, T> Decoder<T> for Erased<C> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T, Self::Error> {
+        self.0.decode(data).map_err(erase)
+    }
+
+    fn decode_bytes(&mut self, buf: &mut BytesMut) -> DecoderResult<T, Self::Error> {
+        self.0.decode_bytes(buf).map_err(erase)
+    }
+
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<(), Self::Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.0.decode_into(data, out).map_err(erase)
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        self.0.min_frame_hint()
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        self.0.resync(data)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{Cobs, LengthDelimited, LinesCodec};
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn test_decoders_of_different_types() {
+        let mut decoders: Vec<BoxedDecoder<Vec<u8>>> = vec![
+            BoxedDecoder::new(LengthDelimited::u16_be()),
+            BoxedDecoder::new(Cobs::new()),
+        ];
+        let frames: [&[u8]; 2] = [b"\x00\x03abc", b"\x04abc\x00"];
+        for (decoder, frame) in decoders.iter_mut().zip(frames) {
+            match decoder.decode(frame) {
+                DecoderResult::Done(decoded, used) => {
+                    assert_eq!(decoded, b"abc");
+                    assert_eq!(used, frame.len());
+                }
+                _ => panic!("Expected a decoded frame"),
+            }
+        }
+        assert_eq!(decoders[0].min_frame_hint(), 2);
+    }
+
+    #[test]
+    fn test_errors_are_not_rewrapped() {
+        let mut encoder = BoxedEncoder::new(LinesCodec::new());
+        let error = encoder.encode("two\nlines").unwrap_err();
+        assert_eq!(error.to_string(), "Line contains a newline");
+        assert_eq!(error.get_ref().to_string(), "Line contains a newline");
+        assert!(error.source().is_none());
+        assert!(error.into_inner().downcast::<CodecError>().is_err());
+    }
+}
@@ -0,0 +1,336 @@
+//! Frames of an inner codec followed by a CRC checksum: `<inner frame><crc>`.
+use alloc::{boxed::Box, format, vec::Vec};
+use core::any::Any;
+
+use bytes::BytesMut;
+
+use crate::{
+    codec::Endian,
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// CRC algorithm appended to every frame by `Checksummed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// CRC-32 (IEEE 802.3), as used by Ethernet, zlib and PNG.
+    Crc32,
+    /// CRC-32C (Castagnoli), as used by iSCSI and SCTP.
+    Crc32c,
+}
+
+const CRC32_TABLE: [u32; 256] = crc_table(0xEDB8_8320);
+const CRC32C_TABLE: [u32; 256] = crc_table(0x82F6_3B78);
+
+/// Builds the lookup table of a reflected CRC, of any width up to 32 bits, from its reversed
+/// polynomial.
+pub(super) const fn crc_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ polynomial
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+impl Checksum {
+    /// Number of bytes the checksum occupies on the wire.
+    pub const fn size(self) -> usize {
+        4
+    }
+
+    /// Computes the checksum of `data`.
+    pub fn compute(self, data: &[u8]) -> u32 {
+        !self.update(!0, data)
+    }
+
+    /// Feeds `data` into the CRC register `crc`, which starts at `!0` and is inverted to give
+    /// the checksum.
+    pub(super) fn update(self, crc: u32, data: &[u8]) -> u32 {
+        let table = match self {
+            Checksum::Crc32 => &CRC32_TABLE,
+            Checksum::Crc32c => &CRC32C_TABLE,
+        };
+        data.iter().fold(crc, |crc, &byte| {
+            table[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+        })
+    }
+}
+
+/// Codec appending a checksum of each inner frame, written big-endian after the frame.
+///
+/// The inner decoder finds where a frame ends, and its message is only returned once the
+/// checksum over the frame matches. The message is held back while the checksum is still
+/// arriving, so the inner decoder sees every frame once. The checksum covers the whole frame,
+/// so inner decoders must not return `ContinueFrom`.
+///
+/// A clone doesn't take over a message held back for its checksum, so clone the codec before
+/// decoding with it.
+#[derive(Debug)]
+pub struct Checksummed<C> {
+    inner: C,
+    checksum: Checksum,
+    pending: Option<(Box<dyn Any + Send>, usize)>,
+}
+
+impl<C: Clone> Clone for Checksummed<C> {
+    fn clone(&self) -> Self {
+        Self::with_checksum(self.inner.clone(), self.checksum)
+    }
+}
+
+impl<C> Checksummed<C> {
+    /// Wraps a codec, appending a CRC-32 to every frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: The codec framing the messages.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Checksummed`.
+    pub fn new(inner: C) -> Self {
+        Self::with_checksum(inner, Checksum::Crc32)
+    }
+
+    /// Wraps a codec, appending the given checksum to every frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: The codec framing the messages.
+    /// * `checksum`: The CRC algorithm to use.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Checksummed`.
+    pub fn with_checksum(inner: C, checksum: Checksum) -> Self {
+        Self {
+            inner,
+            checksum,
+            pending: None,
+        }
+    }
+
+    /// Consumes the wrapper, returning the inner codec.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C: Encoder<T>, T> Encoder<T> for Checksummed<C> {
+    type Error = C::Error;
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        Some(self.inner.size_hint(data)? + self.checksum.size())
+    }
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let mut encoded = self.inner.encode(data)?;
+        let crc = self.checksum.compute(&encoded);
+        Endian::Big.write_u32(crc, &mut encoded);
+        Ok(encoded)
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        self.inner.encode_into(data, dst)?;
+        let crc = self.checksum.compute(&dst[start..]);
+        Endian::Big.write_u32(crc, dst);
+        Ok(())
+    }
+}
+
+impl<C: Decoder<T>, T: Send + 'static> Decoder<T> for Checksummed<C> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T> {
+        let (message, used) = match self.pending.take() {
+            Some((message, used)) => match message.downcast::<T>() {
+                Ok(message) => (*message, used),
+                Err(_) => {
+                    return DecoderResult::Error(
+                        "Checksummed decoded a frame as another message type".into(),
+                    );
+                }
+            },
+            None => match self.inner.decode(data) {
+                DecoderResult::Done(message, used) => (message, used),
+                DecoderResult::Continue => return DecoderResult::Continue,
+                DecoderResult::NeedMore(len) => return DecoderResult::NeedMore(len),
+                DecoderResult::ContinueFrom(_) => {
+                    return DecoderResult::Error(
+                        "Checksummed inner decoder returned ContinueFrom".into(),
+                    );
+                }
+                DecoderResult::Error(e) => return DecoderResult::Error(CodecError::new(e)),
+                DecoderResult::ErrorAt(e, offset) => {
+                    return DecoderResult::ErrorAt(CodecError::new(e), offset);
+                }
+            },
+        };
+        let Some(frame_len) = used.checked_add(self.checksum.size()) else {
+            return DecoderResult::Error(
+                "Checksummed inner decoder used an impossible length".into(),
+            );
+        };
+        let Some(trailer) = data.get(used..frame_len) else {
+            self.pending = Some((Box::new(message), used));
+            return DecoderResult::Continue;
+        };
+        let expected = Endian::Big.read_u32([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let actual = self.checksum.compute(&data[..used]);
+        if actual != expected {
+            return DecoderResult::Error(
+                format!(
+                    "{:?} mismatch: frame has {expected:#010x}, computed {actual:#010x}",
+                    self.checksum
+                )
+                .into(),
+            );
+        }
+        DecoderResult::Done(message, frame_len)
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        match &self.pending {
+            Some((_, used)) => used.saturating_add(self.checksum.size()),
+            None => self.inner.min_frame_hint() + self.checksum.size(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.pending = None;
+        self.inner.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{Endian, LengthDelimited, PrefixWidth};
+
+    fn codec(checksum: Checksum) -> Checksummed<LengthDelimited> {
+        Checksummed::with_checksum(
+            LengthDelimited::new(PrefixWidth::U16, Endian::Big),
+            checksum,
+        )
+    }
+
+    #[test]
+    fn test_check_values() {
+        assert_eq!(Checksum::Crc32.compute(b"123456789"), 0xCBF4_3926);
+        assert_eq!(Checksum::Crc32c.compute(b"123456789"), 0xE306_9283);
+    }
+
+    #[test]
+    fn test_valid_frame() {
+        for checksum in [Checksum::Crc32, Checksum::Crc32c] {
+            let mut codec = codec(checksum);
+            let mut encoded = codec.encode(b"payload").expect("Failed to encode");
+            assert_eq!(encoded.len(), 2 + 7 + 4);
+            encoded.extend_from_slice(b"next");
+            match codec.decode(&encoded) {
+                DecoderResult::Done(decoded, used) => {
+                    assert_eq!(decoded, b"payload");
+                    assert_eq!(used, 13);
+                }
+                _ => panic!("Expected a decoded frame for {checksum:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_into_matches_encode() {
+        let mut codec = codec(Checksum::Crc32c);
+        let mut dst = BytesMut::from(&b"queued"[..]);
+        codec
+            .encode_into(b"payload", &mut dst)
+            .expect("Failed to encode");
+        assert_eq!(dst[6..], codec.encode(b"payload").unwrap());
+    }
+
+    #[test]
+    fn test_bit_flipped_frame() {
+        let mut codec = codec(Checksum::Crc32);
+        let encoded = codec.encode(b"payload").expect("Failed to encode");
+        for byte in 2..encoded.len() {
+            let mut corrupted = encoded.clone();
+            corrupted[byte] ^= 0x10;
+            assert!(
+                matches!(codec.decode(&corrupted), DecoderResult::Error(_)),
+                "Expected an error with byte {byte} flipped"
+            );
+        }
+    }
+
+    #[test]
+    fn test_frame_split_across_reads() {
+        let mut codec = codec(Checksum::Crc32);
+        let encoded = codec.encode(b"payload").expect("Failed to encode");
+        for end in 0..encoded.len() {
+            assert!(
+                matches!(codec.decode(&encoded[..end]), DecoderResult::Continue),
+                "Expected Continue for {} of {} bytes",
+                end,
+                encoded.len()
+            );
+        }
+        assert!(matches!(
+            codec.decode(&encoded),
+            DecoderResult::Done(decoded, 13) if decoded == b"payload"
+        ));
+    }
+
+    /// Numbers the frames it decodes, so decoding a frame twice shows in the message.
+    struct Counting {
+        inner: LengthDelimited,
+        decoded: usize,
+    }
+
+    impl Decoder<(usize, Vec<u8>)> for Counting {
+        type Error = CodecError;
+
+        fn decode(&mut self, data: &[u8]) -> DecoderResult<(usize, Vec<u8>)> {
+            self.inner.decode(data).map(|message| {
+                self.decoded += 1;
+                (self.decoded, message)
+            })
+        }
+    }
+
+    #[test]
+    fn test_split_in_checksum_with_stateful_inner_decoder() {
+        let framing = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let mut encoder = Checksummed::new(framing.clone());
+        let mut decoder = Checksummed::new(Counting {
+            inner: framing,
+            decoded: 0,
+        });
+        for expected in 1..=2 {
+            let encoded = encoder.encode(b"payload").expect("Failed to encode");
+            for end in encoded.len() - 3..encoded.len() {
+                assert!(matches!(
+                    decoder.decode(&encoded[..end]),
+                    DecoderResult::Continue
+                ));
+            }
+            assert!(matches!(
+                decoder.decode(&encoded),
+                DecoderResult::Done((count, decoded), 13)
+                    if count == expected && decoded == b"payload"
+            ));
+        }
+    }
+}
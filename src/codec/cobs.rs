@@ -0,0 +1,213 @@
+//! Consistent Overhead Byte Stuffing (COBS) framing.
+use alloc::vec::Vec;
+
+use crate::{
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec framing payloads with COBS, each frame terminated by a `0x00` byte.
+///
+/// Stuffing removes every zero from the payload, so the terminator is the only zero on the
+/// wire and a receiver can always resynchronize at the next frame.
+#[derive(Debug, Clone, Default)]
+pub struct Cobs;
+
+impl Cobs {
+    /// Creates a new COBS codec.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Cobs`.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// COBS-encodes `payload`, without the trailing frame delimiter.
+fn stuff(payload: &[u8], dst: &mut Vec<u8>) {
+    let mut code_index = dst.len();
+    let mut code = 1u8;
+    dst.push(0);
+    for &byte in payload {
+        if byte != 0 {
+            dst.push(byte);
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            dst[code_index] = code;
+            code_index = dst.len();
+            code = 1;
+            dst.push(0);
+        }
+    }
+    dst[code_index] = code;
+}
+
+/// Reverses `stuff`, `data` must not include the frame delimiter. Errors carry the offset of
+/// the offending code byte.
+fn unstuff(data: &[u8]) -> Result<Vec<u8>, (&'static str, usize)> {
+    if data.is_empty() {
+        return Err(("Empty COBS frame", 0));
+    }
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let code = data[i] as usize;
+        if code == 0 {
+            return Err(("Zero byte inside COBS frame", i));
+        }
+        let end = i + code;
+        let Some(block) = data.get(i + 1..end) else {
+            return Err(("COBS code overruns the frame", i));
+        };
+        decoded.extend_from_slice(block);
+        i = end;
+        if code != 0xFF && i < data.len() {
+            decoded.push(0);
+        }
+    }
+    Ok(decoded)
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for Cobs {
+    type Error = CodecError;
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        // An upper bound, an overhead byte per 254 payload bytes and one more, plus the delimiter.
+        let len = data.as_ref().len();
+        Some(len + len / 254 + 2)
+    }
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let payload = data.as_ref();
+        let mut encoded = Vec::with_capacity(payload.len() + payload.len() / 254 + 2);
+        stuff(payload, &mut encoded);
+        encoded.push(0);
+        Ok(encoded)
+    }
+}
+
+impl Decoder<Vec<u8>> for Cobs {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        let Some(end) = data.iter().position(|&byte| byte == 0) else {
+            return DecoderResult::Continue;
+        };
+        match unstuff(&data[..end]) {
+            Ok(payload) => DecoderResult::Done(payload, end + 1),
+            Err((message, offset)) => DecoderResult::ErrorAt(CodecError::from(message), offset),
+        }
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        data.iter().position(|&byte| byte == 0).map(|end| end + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn round_trip(payload: &[u8]) {
+        let mut codec = Cobs::new();
+        let encoded = codec.encode(payload).expect("Failed to encode");
+        assert_eq!(
+            encoded.iter().position(|&byte| byte == 0),
+            Some(encoded.len() - 1),
+            "Only the delimiter may be zero"
+        );
+        match codec.decode(&encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, payload);
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded frame for {payload:?}"),
+        }
+    }
+
+    #[test]
+    fn test_known_vectors() {
+        let mut codec = Cobs::new();
+        assert_eq!(codec.encode([]).unwrap(), [0x01, 0x00]);
+        assert_eq!(codec.encode([0x00]).unwrap(), [0x01, 0x01, 0x00]);
+        assert_eq!(
+            codec.encode([0x00, 0x00]).unwrap(),
+            [0x01, 0x01, 0x01, 0x00]
+        );
+        assert_eq!(
+            codec.encode([0x11, 0x22, 0x00, 0x33]).unwrap(),
+            [0x03, 0x11, 0x22, 0x02, 0x33, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_many_zero_bytes() {
+        round_trip(&[0u8; 1000]);
+        let mut payload = vec![0u8; 600];
+        payload.extend(vec![0xAA; 600]);
+        payload.extend(vec![0u8; 3]);
+        round_trip(&payload);
+    }
+
+    #[test]
+    fn test_long_runs_without_zero() {
+        for len in [253, 254, 255, 508, 509, 1000] {
+            round_trip(&vec![0x42; len]);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_property() {
+        // Deterministic xorshift, biased towards zeros to exercise the stuffing.
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..500 {
+            let len = (next() % 700) as usize;
+            let payload: Vec<u8> = (0..len)
+                .map(|_| match next() % 4 {
+                    0 => 0,
+                    _ => next() as u8,
+                })
+                .collect();
+            round_trip(&payload);
+        }
+    }
+
+    #[test]
+    fn test_waits_for_delimiter() {
+        let mut codec = Cobs::new();
+        let encoded = codec.encode(b"abc").unwrap();
+        assert!(matches!(
+            codec.decode(&encoded[..encoded.len() - 1]),
+            DecoderResult::Continue
+        ));
+    }
+
+    #[test]
+    fn test_malformed_stuffing() {
+        let mut codec = Cobs::new();
+        // The code byte claims five more bytes but the frame ends after two.
+        assert!(matches!(
+            codec.decode(&[0x06, 0x11, 0x22, 0x00]),
+            DecoderResult::ErrorAt(_, 0)
+        ));
+        assert!(matches!(
+            codec.decode(&[0x02, 0x11, 0x05, 0x22, 0x00]),
+            DecoderResult::ErrorAt(_, 2)
+        ));
+        // A bare delimiter is not a valid frame.
+        assert!(matches!(
+            codec.decode(&[0x00]),
+            DecoderResult::ErrorAt(_, 0)
+        ));
+    }
+}
@@ -0,0 +1,213 @@
+//! Per-frame deflate compression around another codec, via `flate2`.
+use std::io::{Read, Write};
+
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+
+use crate::{
+    codec::{Endian, LengthDelimited, PrefixWidth},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec deflating the output of an inner codec `C`, framed by a big-endian `u32` length
+/// prefix around the compressed bytes.
+///
+/// Every frame is compressed on its own, so frames can be decoded independently of each other.
+#[derive(Debug, Clone)]
+pub struct Compressed<C> {
+    inner: C,
+    framing: LengthDelimited,
+    level: Compression,
+    max_decompressed_len: Option<usize>,
+}
+
+impl<C> Compressed<C> {
+    /// Wraps a codec, compressing at the default level.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: The codec encoding and decoding the uncompressed bytes.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Compressed`.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            framing: LengthDelimited::new(PrefixWidth::U32, Endian::Big),
+            level: Compression::default(),
+            max_decompressed_len: None,
+        }
+    }
+
+    /// Sets the compression level.
+    ///
+    /// # Arguments
+    ///
+    /// * `level`: From 0 (no compression) to 9 (best compression).
+    ///
+    /// # Returns
+    ///
+    /// The codec with the level applied.
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = Compression::new(level.min(9));
+        self
+    }
+
+    /// Sets the maximum size a frame may inflate to.
+    ///
+    /// Frames inflating past the limit fail to decode, which guards against small frames
+    /// expanding into huge allocations. By default there is no limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_decompressed_len`: The maximum inflated size in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the limit applied.
+    pub fn with_max_decompressed_len(mut self, max_decompressed_len: usize) -> Self {
+        self.max_decompressed_len = Some(max_decompressed_len);
+        self
+    }
+
+    /// Consumes the wrapper, returning the inner codec.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn inflate(&self, compressed: &[u8]) -> Result<Vec<u8>, CodecError> {
+        let mut inflated = Vec::new();
+        let decoder = DeflateDecoder::new(compressed);
+        match self.max_decompressed_len {
+            Some(max) => {
+                decoder
+                    .take(max as u64 + 1)
+                    .read_to_end(&mut inflated)
+                    .map_err(CodecError::new)?;
+                if inflated.len() > max {
+                    return Err(format!("Frame inflates past the maximum of {max} bytes").into());
+                }
+            }
+            None => {
+                let mut decoder = decoder;
+                decoder
+                    .read_to_end(&mut inflated)
+                    .map_err(CodecError::new)?;
+            }
+        }
+        Ok(inflated)
+    }
+}
+
+impl<C: Encoder<T>, T> Encoder<T> for Compressed<C> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let raw = self.inner.encode(data).map_err(CodecError::new)?;
+        let mut deflater = DeflateEncoder::new(Vec::new(), self.level);
+        deflater.write_all(&raw).map_err(CodecError::new)?;
+        let compressed = deflater.finish().map_err(CodecError::new)?;
+        self.framing.encode(compressed)
+    }
+}
+
+impl<C: Decoder<T>, T> Decoder<T> for Compressed<C> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T> {
+        let (compressed, used) = match self.framing.decode(data) {
+            DecoderResult::Done(compressed, used) => (compressed, used),
+            DecoderResult::Continue => return DecoderResult::Continue,
+            DecoderResult::ContinueFrom(skip) => return DecoderResult::ContinueFrom(skip),
+            DecoderResult::NeedMore(len) => return DecoderResult::NeedMore(len),
+            DecoderResult::Error(e) => return DecoderResult::Error(e),
+            DecoderResult::ErrorAt(e, offset) => return DecoderResult::ErrorAt(e, offset),
+        };
+        let inflated = match self.inflate(&compressed) {
+            Ok(inflated) => inflated,
+            Err(e) => return DecoderResult::Error(e),
+        };
+        match self.inner.decode(&inflated) {
+            DecoderResult::Done(message, inner_used) if inner_used == inflated.len() => {
+                DecoderResult::Done(message, used)
+            }
+            DecoderResult::Done(_, inner_used) => DecoderResult::Error(
+                format!(
+                    "Compressed frame holds {} bytes after the inner frame",
+                    inflated.len() - inner_used
+                )
+                .into(),
+            ),
+            DecoderResult::Continue
+            | DecoderResult::ContinueFrom(_)
+            | DecoderResult::NeedMore(_) => {
+                DecoderResult::Error("Compressed frame holds an incomplete inner frame".into())
+            }
+            // Offsets into the inner payload don't locate anything on the wire.
+            DecoderResult::Error(e) | DecoderResult::ErrorAt(e, _) => {
+                DecoderResult::Error(CodecError::new(e))
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::LinesCodec;
+
+    fn log_line() -> String {
+        "GET /index.html 200 ".repeat(200)
+    }
+
+    #[test]
+    fn test_round_trip_compressible_payload() {
+        let mut raw = LinesCodec::new();
+        let mut codec = Compressed::new(LinesCodec::new()).with_level(9);
+        let plain = raw.encode(log_line()).expect("Failed to encode");
+        let encoded = codec.encode(log_line()).expect("Failed to encode");
+        assert!(
+            encoded.len() < plain.len() / 10,
+            "Expected {} compressed bytes to be far fewer than {}",
+            encoded.len(),
+            plain.len()
+        );
+        match Decoder::<String>::decode(&mut codec, &encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, log_line());
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded message"),
+        }
+        assert!(matches!(
+            Decoder::<String>::decode(&mut codec, &encoded[..encoded.len() - 1]),
+            DecoderResult::Continue
+        ));
+    }
+
+    #[test]
+    fn test_decompression_limit() {
+        let mut codec = Compressed::new(LinesCodec::new()).with_max_decompressed_len(1024);
+        let encoded = codec.encode(log_line()).expect("Failed to encode");
+        assert!(matches!(
+            Decoder::<String>::decode(&mut codec, &encoded),
+            DecoderResult::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_corrupted_frame() {
+        let mut codec = Compressed::new(LinesCodec::new());
+        let frame = [0, 0, 0, 3, 0xFF, 0xFF, 0xFF];
+        assert!(matches!(
+            Decoder::<String>::decode(&mut codec, &frame),
+            DecoderResult::Error(_)
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+//! Framing on an arbitrary delimiter byte sequence.
+use alloc::vec::Vec;
+
+use crate::{
+    decoder::{BorrowedFrame, Decoder, DecoderResult, RefDecoder},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec framing payloads with a trailing delimiter, e.g. `b"\0"` or `b"\r\n"`.
+#[derive(Debug, Clone)]
+pub struct DelimiterCodec {
+    delimiter: Vec<u8>,
+    reject_embedded: bool,
+}
+
+impl DelimiterCodec {
+    /// Creates a new delimiter codec.
+    ///
+    /// # Arguments
+    ///
+    /// * `delimiter`: The byte sequence terminating every frame.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `DelimiterCodec`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `delimiter` is empty.
+    pub fn new(delimiter: impl Into<Vec<u8>>) -> Self {
+        let delimiter = delimiter.into();
+        assert!(!delimiter.is_empty(), "Delimiter must not be empty");
+        Self {
+            delimiter,
+            reject_embedded: false,
+        }
+    }
+
+    /// Sets whether encoding fails for payloads that contain the delimiter.
+    ///
+    /// Such payloads would otherwise be split into several frames by the receiver.
+    ///
+    /// # Arguments
+    ///
+    /// * `reject`: `true` to reject payloads containing the delimiter.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the option applied.
+    pub fn with_reject_embedded(mut self, reject: bool) -> Self {
+        self.reject_embedded = reject;
+        self
+    }
+
+    fn find(&self, data: &[u8]) -> Option<usize> {
+        data.windows(self.delimiter.len())
+            .position(|window| window == self.delimiter.as_slice())
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for DelimiterCodec {
+    type Error = CodecError;
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        Some(data.as_ref().len() + self.delimiter.len())
+    }
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let payload = data.as_ref();
+        if self.reject_embedded && self.find(payload).is_some() {
+            return Err("Payload contains the frame delimiter".into());
+        }
+        let mut encoded = Vec::with_capacity(payload.len() + self.delimiter.len());
+        encoded.extend_from_slice(payload);
+        encoded.extend_from_slice(&self.delimiter);
+        Ok(encoded)
+    }
+}
+
+impl Decoder<Vec<u8>> for DelimiterCodec {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        self.decode_ref(data).map(|frame| frame.to_vec())
+    }
+
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<()> {
+        self.decode_ref(data).map(|frame| {
+            out.clear();
+            out.extend_from_slice(&frame);
+        })
+    }
+}
+
+impl RefDecoder for DelimiterCodec {
+    type Error = CodecError;
+
+    fn decode_ref<'a>(&mut self, data: &'a [u8]) -> DecoderResult<BorrowedFrame<'a>> {
+        match self.find(data) {
+            Some(end) => {
+                DecoderResult::Done(BorrowedFrame::new(&data[..end]), end + self.delimiter.len())
+            }
+            None => DecoderResult::Continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut codec = DelimiterCodec::new(b"\0".as_slice());
+        let encoded = codec.encode(b"payload").unwrap();
+        assert_eq!(encoded, b"payload\0");
+        assert!(matches!(
+            codec.decode(&encoded),
+            DecoderResult::Done(payload, 8) if payload == b"payload"
+        ));
+    }
+
+    #[test]
+    fn test_multi_byte_delimiter_split_across_reads() {
+        let mut codec = DelimiterCodec::new(b"\r\n".as_slice());
+        // The first read ends between the two delimiter bytes.
+        assert!(matches!(codec.decode(b"hello\r"), DecoderResult::Continue));
+        match codec.decode(b"hello\r\nworld") {
+            DecoderResult::Done(payload, used) => {
+                assert_eq!(payload, b"hello");
+                assert_eq!(used, 7);
+            }
+            _ => panic!("Expected a decoded frame"),
+        }
+    }
+
+    #[test]
+    fn test_embedded_delimiter() {
+        let mut codec = DelimiterCodec::new(b"\r\n".as_slice());
+        assert!(codec.encode(b"a\r\nb").is_ok());
+        let mut codec = codec.with_reject_embedded(true);
+        assert!(codec.encode(b"a\r\nb").is_err());
+        assert!(codec.encode(b"a\rb\n").is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_empty_delimiter_panics() {
+        DelimiterCodec::new(Vec::new());
+    }
+}
@@ -0,0 +1,294 @@
+//! Per-frame authenticated encryption around another codec, via ChaCha20-Poly1305.
+use std::fmt;
+
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce, aead::Aead};
+
+use crate::{
+    codec::{Endian, LengthDelimited, PrefixWidth},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+const NONCE_SIZE: usize = 12;
+const COUNTER_SIZE: usize = 8;
+const TAG_SIZE: usize = 16;
+
+/// The side of a connection an `Encrypted` codec is on.
+///
+/// Both peers share one key, and the role keeps the nonces of the two directions apart: frames
+/// are sealed with the sender's role in their nonce, and frames carrying the receiver's own
+/// role are rejected. The peers must take different roles, e.g. the side that connected is the
+/// `Initiator` and the side that accepted the `Responder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// The side that opened the connection.
+    Initiator,
+    /// The side that accepted the connection.
+    Responder,
+}
+
+impl Role {
+    /// The value stored in the leading bytes of the nonces this role seals frames with.
+    fn tag(self) -> [u8; NONCE_SIZE - COUNTER_SIZE] {
+        match self {
+            Role::Initiator => 1u32.to_be_bytes(),
+            Role::Responder => 2u32.to_be_bytes(),
+        }
+    }
+
+    fn peer(self) -> Role {
+        match self {
+            Role::Initiator => Role::Responder,
+            Role::Responder => Role::Initiator,
+        }
+    }
+}
+
+/// Codec encrypting the output of an inner codec `C` with ChaCha20-Poly1305.
+///
+/// Every frame is `<u32 big-endian length><nonce><ciphertext><tag>`, the nonce being the
+/// sender's `Role` followed by a counter that increases with every encoded frame. Decoding
+/// rejects frames sealed by its own role, and any frame whose counter isn't greater than the
+/// last one accepted, so reflected, replayed or reordered frames fail to decode.
+///
+/// A key must only ever be shared by the two codecs of one connection, each encrypting one
+/// direction under its own role. For the same reason the codec isn't `Clone`.
+pub struct Encrypted<C> {
+    inner: C,
+    cipher: ChaCha20Poly1305,
+    role: Role,
+    framing: LengthDelimited,
+    next_nonce: Option<u64>,
+    last_received: Option<u64>,
+}
+
+// The cipher is left out so the key can't end up in logs.
+impl<C: fmt::Debug> fmt::Debug for Encrypted<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encrypted")
+            .field("inner", &self.inner)
+            .field("role", &self.role)
+            .field("next_nonce", &self.next_nonce)
+            .field("last_received", &self.last_received)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C> Encrypted<C> {
+    /// Wraps a codec, encrypting its frames with the given key.
+    ///
+    /// The encoder and decoder of one side must share its role, which the peer's codecs must
+    /// not use.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: The codec encoding and decoding the plaintext.
+    /// * `key`: The 256-bit ChaCha20-Poly1305 key.
+    /// * `role`: The side of the connection this codec is on.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Encrypted`.
+    pub fn new(inner: C, key: [u8; 32], role: Role) -> Self {
+        Self {
+            inner,
+            cipher: ChaCha20Poly1305::new(&Key::from(key)),
+            role,
+            framing: LengthDelimited::new(PrefixWidth::U32, Endian::Big),
+            next_nonce: Some(0),
+            last_received: None,
+        }
+    }
+
+    /// Consumes the wrapper, returning the inner codec.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn nonce(role: Role, counter: u64) -> Nonce {
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce[..NONCE_SIZE - COUNTER_SIZE].copy_from_slice(&role.tag());
+        nonce[NONCE_SIZE - COUNTER_SIZE..].copy_from_slice(&counter.to_be_bytes());
+        Nonce::from(nonce)
+    }
+
+    fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, CodecError> {
+        if sealed.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(format!("Encrypted frame of {} bytes is too short", sealed.len()).into());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        let (tag, counter_bytes) = nonce.split_at(NONCE_SIZE - COUNTER_SIZE);
+        if tag == self.role.tag() {
+            return Err("Encrypted frame was sealed by this side of the connection".into());
+        }
+        if tag != self.role.peer().tag() {
+            return Err("Encrypted frame has an unknown nonce layout".into());
+        }
+        let mut counter = [0u8; COUNTER_SIZE];
+        counter.copy_from_slice(counter_bytes);
+        let counter = u64::from_be_bytes(counter);
+        if self.last_received.is_some_and(|last| counter <= last) {
+            return Err(format!("Encrypted frame reuses nonce {counter}").into());
+        }
+        let plaintext = self
+            .cipher
+            .decrypt(&Self::nonce(self.role.peer(), counter), ciphertext)
+            .map_err(|_| CodecError::from("Encrypted frame failed authentication"))?;
+        self.last_received = Some(counter);
+        Ok(plaintext)
+    }
+}
+
+impl<C: Encoder<T>, T> Encoder<T> for Encrypted<C> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let Some(counter) = self.next_nonce else {
+            return Err("Nonce counter exhausted, the key must be replaced".into());
+        };
+        let plaintext = self.inner.encode(data).map_err(CodecError::new)?;
+        let nonce = Self::nonce(self.role, counter);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| CodecError::from("Failed to encrypt frame"))?;
+        self.next_nonce = counter.checked_add(1);
+        let mut sealed = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        self.framing.encode(sealed)
+    }
+}
+
+impl<C: Decoder<T>, T> Decoder<T> for Encrypted<C> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T> {
+        let (sealed, used) = match self.framing.decode(data) {
+            DecoderResult::Done(sealed, used) => (sealed, used),
+            DecoderResult::Continue => return DecoderResult::Continue,
+            DecoderResult::ContinueFrom(skip) => return DecoderResult::ContinueFrom(skip),
+            DecoderResult::NeedMore(len) => return DecoderResult::NeedMore(len),
+            DecoderResult::Error(e) => return DecoderResult::Error(e),
+            DecoderResult::ErrorAt(e, offset) => return DecoderResult::ErrorAt(e, offset),
+        };
+        let plaintext = match self.open(&sealed) {
+            Ok(plaintext) => plaintext,
+            Err(e) => return DecoderResult::Error(e),
+        };
+        match self.inner.decode(&plaintext) {
+            DecoderResult::Done(message, inner_used) if inner_used == plaintext.len() => {
+                DecoderResult::Done(message, used)
+            }
+            DecoderResult::Done(_, inner_used) => DecoderResult::Error(
+                format!(
+                    "Encrypted frame holds {} bytes after the inner frame",
+                    plaintext.len() - inner_used
+                )
+                .into(),
+            ),
+            DecoderResult::Continue
+            | DecoderResult::ContinueFrom(_)
+            | DecoderResult::NeedMore(_) => {
+                DecoderResult::Error("Encrypted frame holds an incomplete inner frame".into())
+            }
+            // Offsets into the inner payload don't locate anything on the wire.
+            DecoderResult::Error(e) | DecoderResult::ErrorAt(e, _) => {
+                DecoderResult::Error(CodecError::new(e))
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::LinesCodec;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn test_round_trip() {
+        let mut sender = Encrypted::new(LinesCodec::new(), KEY, Role::Initiator);
+        let mut receiver = Encrypted::new(LinesCodec::new(), KEY, Role::Responder);
+        for line in ["first", "second"] {
+            let encoded = sender.encode(line).expect("Failed to encode");
+            assert_eq!(encoded.len(), 4 + NONCE_SIZE + line.len() + 1 + TAG_SIZE);
+            assert!(!encoded.windows(line.len()).any(|w| w == line.as_bytes()));
+            match Decoder::<String>::decode(&mut receiver, &encoded) {
+                DecoderResult::Done(decoded, used) => {
+                    assert_eq!(decoded, line);
+                    assert_eq!(used, encoded.len());
+                }
+                _ => panic!("Expected a decoded message"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_tampered_frame() {
+        let mut sender = Encrypted::new(LinesCodec::new(), KEY, Role::Initiator);
+        let encoded = sender.encode("secret").expect("Failed to encode");
+        let mut tampered = encoded.clone();
+        tampered[4 + NONCE_SIZE] ^= 0x01;
+        let mut receiver = Encrypted::new(LinesCodec::new(), KEY, Role::Responder);
+        assert!(matches!(
+            Decoder::<String>::decode(&mut receiver, &tampered),
+            DecoderResult::Error(_)
+        ));
+        // The failed frame doesn't count as received.
+        assert!(matches!(
+            Decoder::<String>::decode(&mut receiver, &encoded),
+            DecoderResult::Done(decoded, _) if decoded == "secret"
+        ));
+    }
+
+    #[test]
+    fn test_wrong_key_and_replay() {
+        let mut sender = Encrypted::new(LinesCodec::new(), KEY, Role::Initiator);
+        let encoded = sender.encode("secret").expect("Failed to encode");
+        let mut stranger = Encrypted::new(LinesCodec::new(), [8; 32], Role::Responder);
+        assert!(matches!(
+            Decoder::<String>::decode(&mut stranger, &encoded),
+            DecoderResult::Error(_)
+        ));
+        let mut receiver = Encrypted::new(LinesCodec::new(), KEY, Role::Responder);
+        assert!(matches!(
+            Decoder::<String>::decode(&mut receiver, &encoded),
+            DecoderResult::Done(..)
+        ));
+        assert!(matches!(
+            Decoder::<String>::decode(&mut receiver, &encoded),
+            DecoderResult::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_directions_sharing_a_key() {
+        let mut initiator = Encrypted::new(LinesCodec::new(), KEY, Role::Initiator);
+        let mut responder = Encrypted::new(LinesCodec::new(), KEY, Role::Responder);
+        let request = initiator.encode("same").expect("Failed to encode");
+        let response = responder.encode("same").expect("Failed to encode");
+        // Both first frames use counter 0, the roles keep their nonces apart.
+        assert_ne!(request[4..4 + NONCE_SIZE], response[4..4 + NONCE_SIZE]);
+        assert_ne!(request, response);
+        // A frame reflected back to its sender is rejected.
+        assert!(matches!(
+            Decoder::<String>::decode(&mut initiator, &request),
+            DecoderResult::Error(_)
+        ));
+        assert!(matches!(
+            Decoder::<String>::decode(&mut initiator, &response),
+            DecoderResult::Done(decoded, _) if decoded == "same"
+        ));
+        assert!(matches!(
+            Decoder::<String>::decode(&mut responder, &request),
+            DecoderResult::Done(decoded, _) if decoded == "same"
+        ));
+    }
+}
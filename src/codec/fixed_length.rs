@@ -0,0 +1,142 @@
+//! Fixed-size framing: every record is exactly the same number of bytes.
+use alloc::{format, vec::Vec};
+
+use bytes::BytesMut;
+
+use crate::{
+    decoder::{BorrowedFrame, Decoder, DecoderResult, RefDecoder},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec for constant-size records with no length prefix or delimiter.
+#[derive(Debug, Clone)]
+pub struct FixedLength {
+    len: usize,
+}
+
+impl FixedLength {
+    /// Creates a new fixed-length codec.
+    ///
+    /// # Arguments
+    ///
+    /// * `len`: The size of every record in bytes.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `FixedLength`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is zero.
+    pub fn new(len: usize) -> Self {
+        assert!(len > 0, "Record length must be non-zero");
+        Self { len }
+    }
+
+    fn check_len(&self, len: usize) -> Result<(), CodecError> {
+        if len != self.len {
+            return Err(format!(
+                "Payload of {} bytes doesn't match the record length of {} bytes",
+                len, self.len
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for FixedLength {
+    type Error = CodecError;
+
+    fn size_hint(&self, _data: &T) -> Option<usize> {
+        Some(self.len)
+    }
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let payload = data.as_ref();
+        self.check_len(payload.len())?;
+        Ok(payload.to_vec())
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let payload = data.as_ref();
+        self.check_len(payload.len())?;
+        dst.extend_from_slice(payload);
+        Ok(())
+    }
+}
+
+impl Decoder<Vec<u8>> for FixedLength {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        self.decode_ref(data).map(|record| record.to_vec())
+    }
+
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<()> {
+        self.decode_ref(data).map(|record| {
+            out.clear();
+            out.extend_from_slice(&record);
+        })
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        self.len
+    }
+}
+
+impl RefDecoder for FixedLength {
+    type Error = CodecError;
+
+    fn decode_ref<'a>(&mut self, data: &'a [u8]) -> DecoderResult<BorrowedFrame<'a>> {
+        match data.get(..self.len) {
+            Some(record) => DecoderResult::Done(BorrowedFrame::new(record), self.len),
+            None => DecoderResult::Continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_size_round_trip() {
+        let mut codec = FixedLength::new(4);
+        let encoded = codec.encode(b"abcd").expect("Failed to encode");
+        assert_eq!(encoded, b"abcd");
+        assert!(matches!(
+            codec.decode(&encoded[..3]),
+            DecoderResult::Continue
+        ));
+        assert!(matches!(
+            codec.decode(&encoded),
+            DecoderResult::Done(record, 4) if record == b"abcd"
+        ));
+    }
+
+    #[test]
+    fn test_encode_rejects_wrong_size() {
+        let mut codec = FixedLength::new(4);
+        assert!(codec.encode(b"abc").is_err());
+        assert!(codec.encode(b"abcde").is_err());
+
+        let mut dst = BytesMut::new();
+        assert!(codec.encode_into(b"abcde", &mut dst).is_err());
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn test_multiple_records_in_one_buffer() {
+        let mut codec = FixedLength::new(3);
+        let mut data: &[u8] = b"onetwosix!";
+        let mut records = Vec::new();
+        while let DecoderResult::Done(record, used) = codec.decode(data) {
+            records.push(record);
+            data = &data[used..];
+        }
+        assert_eq!(records, [b"one", b"two", b"six"]);
+        assert_eq!(data, b"!");
+    }
+}
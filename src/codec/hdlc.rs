@@ -0,0 +1,246 @@
+//! HDLC-style framing with flag bytes and byte stuffing (RFC 1662).
+use alloc::{format, vec::Vec};
+
+use crate::{
+    codec::{Endian, checksummed::crc_table},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+const FLAG: u8 = 0x7E;
+const ESCAPE: u8 = 0x7D;
+const ESCAPE_XOR: u8 = 0x20;
+const FCS_SIZE: usize = 2;
+
+/// CRC-16/X-25, the 16-bit frame check sequence of RFC 1662.
+const FCS16_TABLE: [u32; 256] = crc_table(0x8408);
+
+fn fcs16(data: &[u8]) -> u16 {
+    let fcs = data.iter().fold(0xFFFFu32, |fcs, &byte| {
+        FCS16_TABLE[((fcs ^ u32::from(byte)) & 0xFF) as usize] ^ (fcs >> 8)
+    });
+    !(fcs as u16)
+}
+
+/// Codec framing payloads between `0x7E` flag bytes, escaping flag and `0x7D` escape bytes in
+/// the payload.
+///
+/// Every frame is written with an opening and a closing flag. When decoding, consecutive flags
+/// are treated as one, so frames sharing a single flag between them decode as well. An
+/// optional 16-bit frame check sequence (CRC-16/X-25, least significant byte first) can be
+/// appended to each payload.
+#[derive(Debug, Clone)]
+pub struct Hdlc {
+    fcs: bool,
+}
+
+impl Hdlc {
+    /// Creates a new HDLC codec without a frame check sequence.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Hdlc`.
+    pub fn new() -> Self {
+        Self { fcs: false }
+    }
+
+    /// Sets whether a 16-bit frame check sequence follows every payload.
+    ///
+    /// When enabled, frames whose check sequence doesn't match fail to decode.
+    ///
+    /// # Arguments
+    ///
+    /// * `fcs`: `true` to append and verify the frame check sequence.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the option applied.
+    pub fn with_fcs(mut self, fcs: bool) -> Self {
+        self.fcs = fcs;
+        self
+    }
+
+    fn check_fcs(&self, mut frame: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        if !self.fcs {
+            return Ok(frame);
+        }
+        let Some(payload_len) = frame.len().checked_sub(FCS_SIZE) else {
+            return Err("HDLC frame is too short to hold a frame check sequence".into());
+        };
+        let received = Endian::Little.read_u16([frame[payload_len], frame[payload_len + 1]]);
+        frame.truncate(payload_len);
+        let computed = fcs16(&frame);
+        if received != computed {
+            return Err(format!(
+                "HDLC frame check sequence mismatch: frame has {received:#06x}, computed {computed:#06x}"
+            )
+            .into());
+        }
+        Ok(frame)
+    }
+}
+
+impl Default for Hdlc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Appends `data` to `dst`, escaping flag and escape bytes.
+fn stuff(data: &[u8], dst: &mut Vec<u8>) {
+    for &byte in data {
+        match byte {
+            FLAG | ESCAPE => dst.extend_from_slice(&[ESCAPE, byte ^ ESCAPE_XOR]),
+            _ => dst.push(byte),
+        }
+    }
+}
+
+/// Reverses `stuff`, `data` must not include the flags.
+fn unstuff(data: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != ESCAPE {
+            decoded.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(&escaped) => decoded.push(escaped ^ ESCAPE_XOR),
+            None => return Err("HDLC frame ends with an escape byte".into()),
+        }
+    }
+    Ok(decoded)
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for Hdlc {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let payload = data.as_ref();
+        if payload.is_empty() && !self.fcs {
+            return Err("Empty HDLC frames can't be told apart from back-to-back flags".into());
+        }
+        let mut encoded = Vec::with_capacity(payload.len() + FCS_SIZE + 2);
+        encoded.push(FLAG);
+        stuff(payload, &mut encoded);
+        if self.fcs {
+            stuff(&fcs16(payload).to_le_bytes(), &mut encoded);
+        }
+        encoded.push(FLAG);
+        Ok(encoded)
+    }
+}
+
+impl Decoder<Vec<u8>> for Hdlc {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        // An escape split across reads has no flag after it yet, so it waits here too.
+        let start = data.iter().take_while(|&&byte| byte == FLAG).count();
+        let Some(end) = data[start..].iter().position(|&byte| byte == FLAG) else {
+            return DecoderResult::Continue;
+        };
+        match unstuff(&data[start..start + end]).and_then(|frame| self.check_fcs(frame)) {
+            Ok(payload) => DecoderResult::Done(payload, start + end + 1),
+            Err(e) => DecoderResult::Error(e),
+        }
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        let start = data.iter().take_while(|&&byte| byte == FLAG).count();
+        data[start..]
+            .iter()
+            .position(|&byte| byte == FLAG)
+            .map(|end| start + end + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_fcs_check_value() {
+        assert_eq!(fcs16(b"123456789"), 0x906E);
+    }
+
+    #[test]
+    fn test_stuffed_round_trip() {
+        for fcs in [false, true] {
+            let mut codec = Hdlc::new().with_fcs(fcs);
+            let payload = [0x01, FLAG, 0x02, ESCAPE, FLAG, ESCAPE, 0x5E, 0x5D];
+            let encoded = codec.encode(payload).expect("Failed to encode");
+            assert_eq!(encoded[0], FLAG);
+            assert_eq!(
+                encoded[1..].iter().position(|&byte| byte == FLAG),
+                Some(encoded.len() - 2),
+                "Only the delimiters may be flags"
+            );
+            match codec.decode(&encoded) {
+                DecoderResult::Done(decoded, used) => {
+                    assert_eq!(decoded, payload);
+                    assert_eq!(used, encoded.len());
+                }
+                _ => panic!("Expected a decoded frame with fcs {fcs}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_vector() {
+        let mut codec = Hdlc::new();
+        assert_eq!(
+            codec.encode([0x11, FLAG, ESCAPE]).unwrap(),
+            [FLAG, 0x11, ESCAPE, 0x5E, ESCAPE, 0x5D, FLAG]
+        );
+        assert!(codec.encode([]).is_err());
+        assert!(Hdlc::new().with_fcs(true).encode([]).is_ok());
+    }
+
+    #[test]
+    fn test_shared_flags() {
+        let mut codec = Hdlc::new();
+        let mut data: &[u8] = &[FLAG, FLAG, 0x01, FLAG, 0x02, 0x03, FLAG, FLAG, 0x04];
+        let mut frames = Vec::new();
+        while let DecoderResult::Done(frame, used) = codec.decode(data) {
+            frames.push(frame);
+            data = &data[used..];
+        }
+        assert_eq!(frames, [vec![0x01], vec![0x02, 0x03]]);
+        assert_eq!(data, [FLAG, 0x04]);
+    }
+
+    #[test]
+    fn test_split_escape_sequence() {
+        let mut codec = Hdlc::new();
+        let encoded = codec.encode([FLAG]).unwrap();
+        assert_eq!(encoded, [FLAG, ESCAPE, 0x5E, FLAG]);
+        for end in 0..encoded.len() {
+            assert!(matches!(
+                codec.decode(&encoded[..end]),
+                DecoderResult::Continue
+            ));
+        }
+        assert!(matches!(
+            codec.decode(&encoded),
+            DecoderResult::Done(decoded, 4) if decoded == [FLAG]
+        ));
+    }
+
+    #[test]
+    fn test_fcs_failure() {
+        let mut codec = Hdlc::new().with_fcs(true);
+        let mut encoded = codec.encode(b"payload").unwrap();
+        encoded[3] ^= 0x01;
+        assert!(matches!(codec.decode(&encoded), DecoderResult::Error(_)));
+        // Too short to hold the check sequence.
+        assert!(matches!(
+            codec.decode(&[FLAG, 0x01, FLAG]),
+            DecoderResult::Error(_)
+        ));
+        assert_eq!(codec.resync(&encoded), Some(encoded.len()));
+    }
+}
@@ -0,0 +1,203 @@
+//! JSON serialization via `serde_json`.
+use std::{fmt, marker::PhantomData};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    codec::{Endian, LengthDelimited, PrefixWidth},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec serializing messages of type `T` as JSON.
+///
+/// JSON is not self-delimiting on a stream, so by default every message is written as a
+/// single line of compact JSON terminated by `\n` (newline-delimited JSON). Compact JSON never
+/// contains a raw newline, strings escape it. Use `with_length_prefix` to frame messages with
+/// a length prefix instead.
+pub struct JsonCodec<T> {
+    length_prefix: Option<LengthDelimited>,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T> JsonCodec<T> {
+    /// Creates a new newline-delimited JSON codec.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `JsonCodec`.
+    pub fn new() -> Self {
+        Self {
+            length_prefix: None,
+            _message: PhantomData,
+        }
+    }
+
+    /// Frames messages with a length prefix instead of a trailing newline.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The width of the length prefix.
+    /// * `endian`: The byte order of the length prefix.
+    ///
+    /// # Returns
+    ///
+    /// The codec using length-prefixed framing.
+    pub fn with_length_prefix(mut self, width: PrefixWidth, endian: Endian) -> Self {
+        self.length_prefix = Some(LengthDelimited::new(width, endian));
+        self
+    }
+
+    fn encode_value<V: Serialize + ?Sized>(&mut self, value: &V) -> Result<Vec<u8>, CodecError> {
+        let json = serde_json::to_vec(value).map_err(CodecError::new)?;
+        match &mut self.length_prefix {
+            Some(framing) => framing.encode(json),
+            None => {
+                let mut encoded = json;
+                encoded.push(b'\n');
+                Ok(encoded)
+            }
+        }
+    }
+}
+
+impl<T> Default for JsonCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for JsonCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            length_prefix: self.length_prefix.clone(),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for JsonCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonCodec")
+            .field("length_prefix", &self.length_prefix)
+            .finish()
+    }
+}
+
+impl<T: Serialize> Encoder<T> for JsonCodec<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        self.encode_value(&data)
+    }
+}
+
+impl<'a, T: Serialize> Encoder<&'a T> for JsonCodec<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: &'a T) -> Result<Vec<u8>, Self::Error> {
+        self.encode_value(data)
+    }
+}
+
+impl<T: DeserializeOwned> Decoder<T> for JsonCodec<T> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T> {
+        let (json, used) = match &mut self.length_prefix {
+            Some(framing) => match framing.decode(data) {
+                DecoderResult::Done(json, used) => (json, used),
+                DecoderResult::Continue => return DecoderResult::Continue,
+                DecoderResult::ContinueFrom(skip) => return DecoderResult::ContinueFrom(skip),
+                DecoderResult::NeedMore(len) => return DecoderResult::NeedMore(len),
+                DecoderResult::Error(e) => return DecoderResult::Error(e),
+                DecoderResult::ErrorAt(e, offset) => return DecoderResult::ErrorAt(e, offset),
+            },
+            None => match data.iter().position(|&byte| byte == b'\n') {
+                Some(end) => (data[..end].to_vec(), end + 1),
+                None => return DecoderResult::Continue,
+            },
+        };
+        match serde_json::from_slice(&json) {
+            Ok(message) => DecoderResult::Done(message, used),
+            Err(e) => DecoderResult::Error(CodecError::new(e)),
+        }
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        // A length prefix can't be trusted after an error, a newline can.
+        match self.length_prefix {
+            Some(_) => None,
+            None => data
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .map(|end| end + 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Greeting {
+        id: u32,
+        text: String,
+        tags: Vec<String>,
+    }
+
+    fn greeting() -> Greeting {
+        Greeting {
+            id: 7,
+            text: "hello\nworld".to_string(),
+            tags: vec!["a".to_string(), "b".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_round_trip_newline_delimited() {
+        let mut codec = JsonCodec::<Greeting>::new();
+        let encoded = codec.encode(&greeting()).expect("Failed to encode");
+        assert_eq!(encoded.iter().filter(|&&byte| byte == b'\n').count(), 1);
+        assert_eq!(encoded.last(), Some(&b'\n'));
+        match codec.decode(&encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, greeting());
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded message"),
+        }
+        assert!(matches!(
+            codec.decode(&encoded[..encoded.len() - 1]),
+            DecoderResult::Continue
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_length_prefixed() {
+        let mut codec =
+            JsonCodec::<Greeting>::new().with_length_prefix(PrefixWidth::U32, Endian::Big);
+        let encoded = codec.encode(greeting()).expect("Failed to encode");
+        assert!(matches!(
+            codec.decode(&encoded),
+            DecoderResult::Done(decoded, used) if decoded == greeting() && used == encoded.len()
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_error() {
+        let mut codec = JsonCodec::<Greeting>::new();
+        assert!(matches!(
+            codec.decode(b"{\"id\": \"not a number\"}\n"),
+            DecoderResult::Error(_)
+        ));
+        assert!(matches!(
+            codec.decode(b"not json\n"),
+            DecoderResult::Error(_)
+        ));
+    }
+}
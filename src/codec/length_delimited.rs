@@ -0,0 +1,424 @@
+//! Length-prefixed framing: `<length><payload>`.
+use alloc::{format, vec::Vec};
+
+use bytes::{BufMut, BytesMut};
+
+use crate::{
+    codec::{Endian, frame_len},
+    decoder::{BorrowedFrame, Decoder, DecoderResult, RefDecoder},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Width of the length prefix written in front of every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixWidth {
+    U8,
+    U16,
+    U24,
+    U32,
+    U64,
+}
+
+impl PrefixWidth {
+    /// Number of bytes the prefix occupies on the wire.
+    pub const fn size(self) -> usize {
+        match self {
+            PrefixWidth::U8 => 1,
+            PrefixWidth::U16 => 2,
+            PrefixWidth::U24 => 3,
+            PrefixWidth::U32 => 4,
+            PrefixWidth::U64 => 8,
+        }
+    }
+
+    /// Largest payload length the prefix can represent.
+    pub const fn max_len(self) -> u64 {
+        match self {
+            PrefixWidth::U8 => u8::MAX as u64,
+            PrefixWidth::U16 => u16::MAX as u64,
+            PrefixWidth::U24 => (1 << 24) - 1,
+            PrefixWidth::U32 => u32::MAX as u64,
+            PrefixWidth::U64 => u64::MAX,
+        }
+    }
+}
+
+/// Codec framing each payload with an unsigned length prefix.
+///
+/// The prefix holds the payload length only, it does not count its own bytes.
+#[derive(Debug, Clone)]
+pub struct LengthDelimited {
+    width: PrefixWidth,
+    endian: Endian,
+    max_len: Option<usize>,
+}
+
+impl LengthDelimited {
+    /// Creates a new length-delimited codec.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The width of the length prefix.
+    /// * `endian`: The byte order of the length prefix.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `LengthDelimited`.
+    pub fn new(width: PrefixWidth, endian: Endian) -> Self {
+        Self {
+            width,
+            endian,
+            max_len: None,
+        }
+    }
+
+    /// Creates a codec with a `u8` length prefix.
+    pub fn u8() -> Self {
+        Self::new(PrefixWidth::U8, Endian::Big)
+    }
+
+    /// Creates a codec with a big-endian `u16` length prefix.
+    pub fn u16_be() -> Self {
+        Self::new(PrefixWidth::U16, Endian::Big)
+    }
+
+    /// Creates a codec with a little-endian `u16` length prefix.
+    pub fn u16_le() -> Self {
+        Self::new(PrefixWidth::U16, Endian::Little)
+    }
+
+    /// Creates a codec with a big-endian 3 byte length prefix.
+    pub fn u24_be() -> Self {
+        Self::new(PrefixWidth::U24, Endian::Big)
+    }
+
+    /// Creates a codec with a little-endian 3 byte length prefix, as in MySQL packet headers.
+    pub fn u24_le() -> Self {
+        Self::new(PrefixWidth::U24, Endian::Little)
+    }
+
+    /// Creates a codec with a big-endian `u32` length prefix, the most common layout and the `Default`.
+    pub fn u32_be() -> Self {
+        Self::new(PrefixWidth::U32, Endian::Big)
+    }
+
+    /// Creates a codec with a little-endian `u32` length prefix.
+    pub fn u32_le() -> Self {
+        Self::new(PrefixWidth::U32, Endian::Little)
+    }
+
+    /// Creates a codec with a big-endian `u64` length prefix.
+    pub fn u64_be() -> Self {
+        Self::new(PrefixWidth::U64, Endian::Big)
+    }
+
+    /// Creates a codec with a little-endian `u64` length prefix.
+    pub fn u64_le() -> Self {
+        Self::new(PrefixWidth::U64, Endian::Little)
+    }
+
+    /// Sets the maximum payload length.
+    ///
+    /// Longer payloads fail to encode, and a frame whose prefix announces one fails to decode
+    /// as soon as the prefix is buffered, without waiting for the payload. By default only the
+    /// width of the prefix bounds it.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_len`: The maximum payload length in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the limit applied.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    pub(super) fn read_prefix(&self, header: &[u8]) -> u64 {
+        self.endian.read_uint(header)
+    }
+
+    pub(super) fn check_len(&self, len: usize) -> Result<u64, CodecError> {
+        let len = len as u64;
+        if len > self.width.max_len() {
+            return Err(format!(
+                "Payload of {} bytes exceeds the {} byte limit of a {:?} length prefix",
+                len,
+                self.width.max_len(),
+                self.width
+            )
+            .into());
+        }
+        self.check_max_len(len)?;
+        Ok(len)
+    }
+
+    fn check_max_len(&self, len: u64) -> Result<(), CodecError> {
+        match self.max_len {
+            Some(max) if len > max as u64 => {
+                Err(format!("Payload of {len} bytes exceeds the maximum of {max} bytes").into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn write_prefix(&self, len: u64, dst: &mut impl BufMut) {
+        self.endian.write_uint(len, self.width.size(), dst);
+    }
+}
+
+impl Default for LengthDelimited {
+    fn default() -> Self {
+        Self::u32_be()
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for LengthDelimited {
+    type Error = CodecError;
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        Some(self.width.size() + data.as_ref().len())
+    }
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let payload = data.as_ref();
+        let len = self.check_len(payload.len())?;
+        let mut encoded = Vec::with_capacity(self.width.size() + payload.len());
+        self.write_prefix(len, &mut encoded);
+        encoded.extend_from_slice(payload);
+        Ok(encoded)
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let payload = data.as_ref();
+        let len = self.check_len(payload.len())?;
+        dst.reserve(self.width.size() + payload.len());
+        self.write_prefix(len, dst);
+        dst.extend_from_slice(payload);
+        Ok(())
+    }
+}
+
+impl Decoder<Vec<u8>> for LengthDelimited {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        self.decode_ref(data).map(|frame| frame.to_vec())
+    }
+
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<()> {
+        self.decode_ref(data).map(|frame| {
+            out.clear();
+            out.extend_from_slice(&frame);
+        })
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        self.width.size()
+    }
+}
+
+impl RefDecoder for LengthDelimited {
+    type Error = CodecError;
+
+    fn decode_ref<'a>(&mut self, data: &'a [u8]) -> DecoderResult<BorrowedFrame<'a>> {
+        let header = self.width.size();
+        let Some(prefix) = data.get(..header) else {
+            return DecoderResult::Continue;
+        };
+        let len = self.read_prefix(prefix);
+        if let Err(e) = self.check_max_len(len) {
+            return DecoderResult::Error(e);
+        }
+        let frame_len = match frame_len(header, len) {
+            Ok(frame_len) => frame_len,
+            Err(e) => return DecoderResult::Error(e),
+        };
+        match data.get(header..frame_len) {
+            Some(payload) => DecoderResult::Done(BorrowedFrame::new(payload), frame_len),
+            None => DecoderResult::Continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const WIDTHS: [PrefixWidth; 5] = [
+        PrefixWidth::U8,
+        PrefixWidth::U16,
+        PrefixWidth::U24,
+        PrefixWidth::U32,
+        PrefixWidth::U64,
+    ];
+
+    #[test]
+    fn test_round_trip_all_widths() {
+        for width in WIDTHS {
+            for endian in [Endian::Big, Endian::Little] {
+                let mut codec = LengthDelimited::new(width, endian);
+                let payload = b"hello world!".to_vec();
+                let encoded = codec.encode(&payload).expect("Failed to encode");
+                assert_eq!(encoded.len(), width.size() + payload.len());
+                match codec.decode(&encoded) {
+                    DecoderResult::Done(decoded, used) => {
+                        assert_eq!(decoded, payload);
+                        assert_eq!(used, encoded.len());
+                    }
+                    _ => panic!("Expected a decoded frame for {:?} {:?}", width, endian),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_ref_borrows_payload() {
+        let mut codec = LengthDelimited::new(PrefixWidth::U8, Endian::Big);
+        let encoded = codec.encode(b"in place").unwrap();
+        match codec.decode_ref(&encoded) {
+            DecoderResult::Done(frame, used) => {
+                assert_eq!(frame.payload(), b"in place");
+                assert!(core::ptr::eq(frame.payload(), &encoded[1..]));
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded frame"),
+        }
+        assert!(matches!(
+            codec.decode_ref(&encoded[..4]),
+            DecoderResult::Continue
+        ));
+    }
+
+    #[test]
+    fn test_named_constructors() {
+        let codecs = [
+            (LengthDelimited::u8(), &b"\x05"[..]),
+            (LengthDelimited::u16_be(), b"\x00\x05"),
+            (LengthDelimited::u16_le(), b"\x05\x00"),
+            (LengthDelimited::u24_be(), b"\x00\x00\x05"),
+            (LengthDelimited::u24_le(), b"\x05\x00\x00"),
+            (LengthDelimited::u32_be(), b"\x00\x00\x00\x05"),
+            (LengthDelimited::u32_le(), b"\x05\x00\x00\x00"),
+            (
+                LengthDelimited::u64_be(),
+                b"\x00\x00\x00\x00\x00\x00\x00\x05",
+            ),
+            (
+                LengthDelimited::u64_le(),
+                b"\x05\x00\x00\x00\x00\x00\x00\x00",
+            ),
+            (LengthDelimited::default(), b"\x00\x00\x00\x05"),
+        ];
+        for (mut codec, prefix) in codecs {
+            let encoded = codec.encode(b"hello").unwrap();
+            assert_eq!(&encoded[..prefix.len()], prefix, "{codec:?}");
+            match codec.decode(&encoded) {
+                DecoderResult::Done(decoded, used) => {
+                    assert_eq!(decoded, b"hello");
+                    assert_eq!(used, encoded.len());
+                }
+                _ => panic!("Expected a decoded frame for {codec:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_prefix_byte_order() {
+        let mut big = LengthDelimited::new(PrefixWidth::U32, Endian::Big);
+        let mut little = LengthDelimited::new(PrefixWidth::U32, Endian::Little);
+        assert_eq!(big.encode(b"ab").unwrap(), b"\x00\x00\x00\x02ab");
+        assert_eq!(little.encode(b"ab").unwrap(), b"\x02\x00\x00\x00ab");
+    }
+
+    #[test]
+    fn test_encode_rejects_oversized_payload() {
+        let mut codec = LengthDelimited::new(PrefixWidth::U8, Endian::Big);
+        assert!(codec.encode(vec![0u8; 255]).is_ok());
+        assert!(codec.encode(vec![0u8; 256]).is_err());
+
+        let mut codec = LengthDelimited::new(PrefixWidth::U16, Endian::Little);
+        assert!(codec.encode(vec![0u8; 70000]).is_err());
+    }
+
+    #[test]
+    fn test_u24_boundary() {
+        let mut codec = LengthDelimited::u24_le();
+        let largest = codec
+            .encode(vec![0x5A; (1 << 24) - 1])
+            .expect("Failed to encode");
+        assert_eq!(largest[..3], [0xFF, 0xFF, 0xFF]);
+        match codec.decode(&largest) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded.len(), (1 << 24) - 1);
+                assert_eq!(used, largest.len());
+            }
+            _ => panic!("Expected a decoded frame"),
+        }
+        assert!(codec.encode(vec![0u8; 1 << 24]).is_err());
+    }
+
+    #[test]
+    fn test_max_len_rejects_header() {
+        let mut codec = LengthDelimited::new(PrefixWidth::U32, Endian::Big).with_max_len(16);
+        assert!(codec.encode(vec![0u8; 16]).is_ok());
+        assert!(codec.encode(vec![0u8; 17]).is_err());
+        // The prefix alone is enough to reject the frame.
+        assert!(matches!(
+            codec.decode(&[0xFF, 0xFF, 0xFF, 0xFF]),
+            DecoderResult::Error(_)
+        ));
+        assert!(matches!(
+            codec.decode(&[0, 0, 0, 16]),
+            DecoderResult::Continue
+        ));
+    }
+
+    #[test]
+    fn test_encode_into_matches_encode() {
+        for width in WIDTHS {
+            for endian in [Endian::Big, Endian::Little] {
+                let mut codec = LengthDelimited::new(width, endian);
+                let mut dst = BytesMut::from(&b"queued"[..]);
+                codec
+                    .encode_into(b"payload", &mut dst)
+                    .expect("Failed to encode");
+                assert_eq!(dst[..6], *b"queued");
+                assert_eq!(dst[6..], codec.encode(b"payload").unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_partial_frame_continues() {
+        for width in WIDTHS {
+            let mut codec = LengthDelimited::new(width, Endian::Big);
+            let encoded = codec.encode(b"partial").unwrap();
+            for end in 0..encoded.len() {
+                assert!(
+                    matches!(codec.decode(&encoded[..end]), DecoderResult::Continue),
+                    "Expected Continue for {} of {} bytes",
+                    end,
+                    encoded.len()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_leaves_trailing_frame() {
+        let mut codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let mut encoded = codec.encode(b"one").unwrap();
+        encoded.extend(codec.encode(b"two").unwrap());
+        match codec.decode(&encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, b"one");
+                assert_eq!(used, 5);
+            }
+            _ => panic!("Expected a decoded frame"),
+        }
+    }
+}
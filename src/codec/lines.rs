@@ -0,0 +1,209 @@
+//! Newline-delimited text framing.
+use alloc::{format, string::String, string::ToString, vec::Vec};
+
+use crate::{
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec framing UTF-8 text as one message per `\n` terminated line.
+#[derive(Debug, Clone)]
+pub struct LinesCodec {
+    max_length: Option<usize>,
+    strip_carriage_return: bool,
+}
+
+impl LinesCodec {
+    /// Creates a new lines codec with no line length limit, keeping any `\r` before the `\n`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `LinesCodec`.
+    pub fn new() -> Self {
+        Self {
+            max_length: None,
+            strip_carriage_return: false,
+        }
+    }
+
+    /// Sets the maximum line length, excluding the line terminator.
+    ///
+    /// Longer lines are rejected with an error when encoding or decoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_length`: The maximum line length in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the limit applied.
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Sets whether a `\r` directly before the `\n` is stripped from decoded lines.
+    ///
+    /// # Arguments
+    ///
+    /// * `strip`: `true` to decode `\r\n` terminated lines.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the option applied.
+    pub fn with_strip_carriage_return(mut self, strip: bool) -> Self {
+        self.strip_carriage_return = strip;
+        self
+    }
+
+    fn check_length(&self, len: usize) -> Result<(), CodecError> {
+        match self.max_length {
+            Some(max) if len > max => {
+                Err(format!("Line of {len} bytes exceeds the maximum of {max} bytes").into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: AsRef<str>> Encoder<T> for LinesCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let line = data.as_ref();
+        if line.contains('\n') {
+            return Err("Line contains a newline".into());
+        }
+        self.check_length(line.len())?;
+        let mut encoded = Vec::with_capacity(line.len() + 1);
+        encoded.extend_from_slice(line.as_bytes());
+        encoded.push(b'\n');
+        Ok(encoded)
+    }
+}
+
+impl Decoder<String> for LinesCodec {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<String> {
+        let Some(newline) = data.iter().position(|&byte| byte == b'\n') else {
+            // Without a terminator the line is at least as long as the data buffered so far,
+            // minus a trailing `\r` that may still be stripped.
+            let mut pending = data.len();
+            if self.strip_carriage_return && data.ends_with(b"\r") {
+                pending -= 1;
+            }
+            return match self.check_length(pending) {
+                Ok(()) => DecoderResult::Continue,
+                Err(e) => DecoderResult::Error(e),
+            };
+        };
+        let mut line = &data[..newline];
+        if self.strip_carriage_return
+            && let Some(stripped) = line.strip_suffix(b"\r")
+        {
+            line = stripped;
+        }
+        if let Err(e) = self.check_length(line.len()) {
+            return DecoderResult::Error(e);
+        }
+        match core::str::from_utf8(line) {
+            Ok(line) => DecoderResult::Done(line.to_string(), newline + 1),
+            Err(e) => DecoderResult::ErrorAt(
+                format!("Line is not valid UTF-8: {e}").into(),
+                e.valid_up_to(),
+            ),
+        }
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        data.iter()
+            .position(|&byte| byte == b'\n')
+            .map(|end| end + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_appends_newline() {
+        let mut codec = LinesCodec::new();
+        assert_eq!(codec.encode("hello").unwrap(), b"hello\n");
+        assert!(codec.encode("two\nlines").is_err());
+    }
+
+    #[test]
+    fn test_multiple_lines_in_one_buffer() {
+        let mut codec = LinesCodec::new();
+        let mut data: &[u8] = b"first\nsecond\n\nthird";
+        let mut lines = Vec::new();
+        while let DecoderResult::Done(line, used) = codec.decode(data) {
+            lines.push(line);
+            data = &data[used..];
+        }
+        assert_eq!(lines, ["first", "second", ""]);
+        assert_eq!(data, b"third");
+    }
+
+    #[test]
+    fn test_line_split_across_reads() {
+        let mut codec = LinesCodec::new();
+        assert!(matches!(codec.decode(b"hel"), DecoderResult::Continue));
+        assert!(matches!(
+            codec.decode(b"hello wor"),
+            DecoderResult::Continue
+        ));
+        match codec.decode(b"hello world\nnext") {
+            DecoderResult::Done(line, used) => {
+                assert_eq!(line, "hello world");
+                assert_eq!(used, 12);
+            }
+            _ => panic!("Expected a decoded line"),
+        }
+    }
+
+    #[test]
+    fn test_strip_carriage_return() {
+        let mut codec = LinesCodec::new();
+        assert!(matches!(
+            codec.decode(b"crlf\r\n"),
+            DecoderResult::Done(line, 6) if line == "crlf\r"
+        ));
+        let mut codec = LinesCodec::new().with_strip_carriage_return(true);
+        assert!(matches!(
+            codec.decode(b"crlf\r\n"),
+            DecoderResult::Done(line, 6) if line == "crlf"
+        ));
+    }
+
+    #[test]
+    fn test_max_length_rejection() {
+        let mut codec = LinesCodec::new().with_max_length(4);
+        assert!(matches!(
+            codec.decode(b"four\n"),
+            DecoderResult::Done(line, 5) if line == "four"
+        ));
+        assert!(matches!(codec.decode(b"five!\n"), DecoderResult::Error(_)));
+        // An over-long line is rejected before its terminator arrives.
+        assert!(matches!(codec.decode(b"too long"), DecoderResult::Error(_)));
+        assert!(codec.encode("five!").is_err());
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut codec = LinesCodec::new();
+        assert!(matches!(
+            codec.decode(b"ok\xFF\xFE\n"),
+            DecoderResult::ErrorAt(_, 2)
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Schemaless binary serialization via MessagePack (`rmp-serde`).
+use std::{fmt, marker::PhantomData};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    codec::{Endian, LengthDelimited, PrefixWidth},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec serializing messages of type `T` as MessagePack, framed by a big-endian `u32` length
+/// prefix.
+pub struct MessagePackCodec<T> {
+    framing: LengthDelimited,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T> MessagePackCodec<T> {
+    /// Creates a new MessagePack codec.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MessagePackCodec`.
+    pub fn new() -> Self {
+        Self {
+            framing: LengthDelimited::new(PrefixWidth::U32, Endian::Big),
+            _message: PhantomData,
+        }
+    }
+
+    fn encode_value<V: Serialize + ?Sized>(&mut self, value: &V) -> Result<Vec<u8>, CodecError> {
+        let payload = rmp_serde::to_vec(value).map_err(CodecError::new)?;
+        self.framing.encode(payload)
+    }
+}
+
+impl<T> Default for MessagePackCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for MessagePackCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            framing: self.framing.clone(),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for MessagePackCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessagePackCodec")
+            .field("framing", &self.framing)
+            .finish()
+    }
+}
+
+impl<T: Serialize> Encoder<T> for MessagePackCodec<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        self.encode_value(&data)
+    }
+}
+
+impl<'a, T: Serialize> Encoder<&'a T> for MessagePackCodec<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: &'a T) -> Result<Vec<u8>, Self::Error> {
+        self.encode_value(data)
+    }
+}
+
+impl<T: DeserializeOwned> Decoder<T> for MessagePackCodec<T> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T> {
+        let (payload, used) = match self.framing.decode(data) {
+            DecoderResult::Done(payload, used) => (payload, used),
+            DecoderResult::Continue => return DecoderResult::Continue,
+            DecoderResult::ContinueFrom(skip) => return DecoderResult::ContinueFrom(skip),
+            DecoderResult::NeedMore(len) => return DecoderResult::NeedMore(len),
+            DecoderResult::Error(e) => return DecoderResult::Error(e),
+            DecoderResult::ErrorAt(e, offset) => return DecoderResult::ErrorAt(e, offset),
+        };
+        match rmp_serde::from_slice(&payload) {
+            Ok(message) => DecoderResult::Done(message, used),
+            Err(e) => DecoderResult::Error(CodecError::new(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        id: u64,
+        kind: String,
+        attributes: BTreeMap<String, f64>,
+        payload: Option<Vec<u8>>,
+    }
+
+    fn event() -> Event {
+        Event {
+            id: 9001,
+            kind: "sample".to_string(),
+            attributes: BTreeMap::from([("temp".to_string(), 21.5), ("rh".to_string(), 0.4)]),
+            payload: Some(vec![1, 2, 3]),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut codec = MessagePackCodec::<Event>::new();
+        let encoded = codec.encode(&event()).expect("Failed to encode");
+        let len = u32::from_be_bytes(encoded[..4].try_into().unwrap()) as usize;
+        assert_eq!(len + 4, encoded.len());
+        match codec.decode(&encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, event());
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded message"),
+        }
+    }
+
+    #[test]
+    fn test_truncated_frame_continues() {
+        let mut codec = MessagePackCodec::<Event>::new();
+        let encoded = codec.encode(&event()).expect("Failed to encode");
+        for end in 0..encoded.len() {
+            assert!(
+                matches!(codec.decode(&encoded[..end]), DecoderResult::Continue),
+                "Expected Continue for {} of {} bytes",
+                end,
+                encoded.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_corrupted_body() {
+        let mut codec = MessagePackCodec::<Event>::new();
+        // A complete frame whose body is a MessagePack `nil` instead of a map.
+        let frame = [0, 0, 0, 1, 0xC0];
+        assert!(matches!(codec.decode(&frame), DecoderResult::Error(_)));
+    }
+}
@@ -0,0 +1,182 @@
+//! Adapters framing `Bytes` and `&[u8]` messages with the built-in byte framings.
+use alloc::vec::Vec;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    decoder::{Decoder, DecoderResult, RefDecoder},
+    encoder::Encoder,
+};
+
+/// Encoder of `Bytes` and `&[u8]` messages, framing them with the byte slice encoder `C`.
+///
+/// The byte framings of `codec` already take any `AsRef<[u8]>`, this gives encoders of plain
+/// slices the same reach, so messages are framed as they are passed around without converting
+/// them to a `Vec` first.
+#[derive(Debug, Clone, Default)]
+pub struct RawEncoder<C>(C);
+
+impl<C> RawEncoder<C> {
+    /// Wraps an encoder of byte slices.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: The encoder framing the messages, e.g. `LengthDelimited`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `RawEncoder`.
+    pub fn new(encoder: C) -> Self {
+        Self(encoder)
+    }
+
+    /// Consumes the adapter, returning the wrapped encoder.
+    pub fn into_inner(self) -> C {
+        self.0
+    }
+}
+
+impl<C, E> Encoder<Bytes> for RawEncoder<C>
+where
+    C: for<'a> Encoder<&'a [u8], Error = E>,
+    E: core::error::Error + Send + Sync + 'static,
+{
+    type Error = E;
+
+    fn encode(&mut self, data: Bytes) -> Result<Vec<u8>, Self::Error> {
+        self.0.encode(&data)
+    }
+
+    fn encode_into(&mut self, data: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode_into(&data, dst)
+    }
+
+    fn size_hint(&self, data: &Bytes) -> Option<usize> {
+        self.0.size_hint(&&data[..])
+    }
+}
+
+impl<'b, C, E> Encoder<&'b [u8]> for RawEncoder<C>
+where
+    C: for<'a> Encoder<&'a [u8], Error = E>,
+    E: core::error::Error + Send + Sync + 'static,
+{
+    type Error = E;
+
+    fn encode(&mut self, data: &'b [u8]) -> Result<Vec<u8>, Self::Error> {
+        self.0.encode(data)
+    }
+
+    fn encode_into(&mut self, data: &'b [u8], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode_into(data, dst)
+    }
+
+    fn size_hint(&self, data: &&'b [u8]) -> Option<usize> {
+        self.0.size_hint(data)
+    }
+}
+
+/// Decoder of `Bytes` messages, finding frames with the in place decoder `C`.
+///
+/// Readers consuming a message hand `decode_bytes` their buffer, the frame is split off it and
+/// the payload returned as a slice of the same allocation, without copying it.
+#[derive(Debug, Clone, Default)]
+pub struct RawDecoder<C>(C);
+
+impl<C> RawDecoder<C> {
+    /// Wraps an in place decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder`: The decoder finding the frames, e.g. `LengthDelimited`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `RawDecoder`.
+    pub fn new(decoder: C) -> Self {
+        Self(decoder)
+    }
+
+    /// Consumes the adapter, returning the wrapped decoder.
+    pub fn into_inner(self) -> C {
+        self.0
+    }
+}
+
+impl<C: RefDecoder> Decoder<Bytes> for RawDecoder<C> {
+    type Error = C::Error;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Bytes, Self::Error> {
+        self.0
+            .decode_ref(data)
+            .map(|frame| Bytes::copy_from_slice(&frame))
+    }
+
+    fn decode_bytes(&mut self, buf: &mut BytesMut) -> DecoderResult<Bytes, Self::Error> {
+        let (payload, used) = match self.0.decode_ref(&buf[..]) {
+            DecoderResult::Done(frame, used) => {
+                // The payload is a slice of `buf`, so its offset follows from the pointers.
+                let start = (frame.as_ptr() as usize).wrapping_sub(buf.as_ptr() as usize);
+                (start..start.saturating_add(frame.len()), used)
+            }
+            result => return result.map(|_| Bytes::new()),
+        };
+        if payload.end <= used && used <= buf.len() {
+            DecoderResult::Done(buf.split_to(used).freeze().slice(payload), used)
+        } else {
+            // A decoder breaking the contract of `decode_ref` gets its payload copied, and the
+            // reader rejects a `used` past the end of its buffer.
+            self.decode(buf)
+        }
+    }
+
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<(), Self::Error> {
+        self.0.decode_ref(data).map(|frame| {
+            out.clear();
+            out.extend_from_slice(&frame);
+        })
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        self.0.resync(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::LengthDelimited;
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut encoder = RawEncoder::new(LengthDelimited::u16_be());
+        let mut decoder = RawDecoder::new(LengthDelimited::u16_be());
+        let mut buf = BytesMut::new();
+        encoder
+            .encode_into(Bytes::from_static(b"shared"), &mut buf)
+            .expect("Failed to encode");
+        encoder
+            .encode_into(&b"slice"[..], &mut buf)
+            .expect("Failed to encode");
+        let start = buf.as_ptr() as usize;
+
+        match decoder.decode_bytes(&mut buf) {
+            DecoderResult::Done(payload, used) => {
+                assert_eq!(payload, &b"shared"[..]);
+                assert_eq!(used, 8);
+                // Split off the buffer rather than copied out of it.
+                assert_eq!(payload.as_ptr() as usize, start + 2);
+            }
+            _ => panic!("Expected a decoded frame"),
+        }
+        assert_eq!(&buf[..], b"\x00\x05slice");
+        assert!(matches!(
+            decoder.decode(&buf),
+            DecoderResult::Done(payload, 7) if payload == b"slice"[..]
+        ));
+        assert!(matches!(
+            decoder.decode_bytes(&mut BytesMut::from(&b"\x00\x05sl"[..])),
+            DecoderResult::Continue
+        ));
+    }
+}
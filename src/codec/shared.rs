@@ -0,0 +1,108 @@
+//! A codec handle sharing one codec instance between the read and write paths.
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+};
+
+/// Codec sharing a single `C` between all of its clones.
+///
+/// `new_rw` clones its codec so the reader and the writer each get their own, which leaves a
+/// stateful codec with two independent states. Wrapping it in `Shared` makes the clones handles
+/// to the same instance, so encoding and decoding see each other's state changes.
+///
+/// The codec sits behind an `Arc<Mutex<_>>`, which costs a lock per encode and decode call and
+/// keeps the handles `Send` and `Sync` when `C` is `Send`, so the halves of a split handler can
+/// still move to separate tasks. A reader and a writer used concurrently contend for the lock.
+#[derive(Debug)]
+pub struct Shared<C> {
+    inner: Arc<Mutex<C>>,
+}
+
+impl<C> Shared<C> {
+    /// Wraps a codec so that clones of the wrapper share it.
+    ///
+    /// # Arguments
+    ///
+    /// * `codec`: The codec to share.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Shared`.
+    pub fn new(codec: C) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(codec)),
+        }
+    }
+
+    /// Locks the shared codec, e.g. to inspect its state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a previous user of the codec panicked while holding the lock.
+    pub fn lock(&self) -> MutexGuard<'_, C> {
+        self.inner.lock().expect("Shared codec lock poisoned")
+    }
+}
+
+impl<C> Clone for Shared<C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C: Encoder<T>, T> Encoder<T> for Shared<C> {
+    type Error = C::Error;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        self.lock().encode(data)
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.lock().encode_into(data, dst)
+    }
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        self.lock().size_hint(data)
+    }
+
+    fn encode_vectored(&mut self, data: T) -> Result<Vec<Bytes>, Self::Error> {
+        self.lock().encode_vectored(data)
+    }
+}
+
+impl<C: Decoder<T>, T> Decoder<T> for Shared<C> {
+    type Error = C::Error;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T, Self::Error> {
+        self.lock().decode(data)
+    }
+
+    fn decode_bytes(&mut self, buf: &mut BytesMut) -> DecoderResult<T, Self::Error> {
+        self.lock().decode_bytes(buf)
+    }
+
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<(), Self::Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.lock().decode_into(data, out)
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        self.lock().min_frame_hint()
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        self.lock().resync(data)
+    }
+
+    fn reset(&mut self) {
+        self.lock().reset();
+    }
+}
@@ -0,0 +1,172 @@
+//! Serial Line Internet Protocol (SLIP, RFC 1055) framing.
+use alloc::{format, vec::Vec};
+
+use crate::{
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+const END: u8 = 0xC0;
+const ESC: u8 = 0xDB;
+const ESC_END: u8 = 0xDC;
+const ESC_ESC: u8 = 0xDD;
+
+/// Codec framing payloads with SLIP, each frame terminated by an `END` (`0xC0`) byte.
+///
+/// `END` and `ESC` bytes in the payload are escaped, so the terminator never appears inside a
+/// frame. Empty frames, e.g. from peers that also send an `END` before every frame, are
+/// skipped when decoding.
+#[derive(Debug, Clone, Default)]
+pub struct Slip;
+
+impl Slip {
+    /// Creates a new SLIP codec.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Slip`.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Reverses the escaping of a frame, `data` must not include the `END` byte.
+fn unescape(data: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut bytes = data.iter().enumerate();
+    while let Some((i, &byte)) = bytes.next() {
+        if byte != ESC {
+            decoded.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some((_, &ESC_END)) => decoded.push(END),
+            Some((_, &ESC_ESC)) => decoded.push(ESC),
+            Some((_, &other)) => {
+                return Err(format!("Invalid SLIP escape 0x{other:02X} at offset {i}").into());
+            }
+            None => return Err("SLIP frame ends with an escape byte".into()),
+        }
+    }
+    Ok(decoded)
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for Slip {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let payload = data.as_ref();
+        let mut encoded = Vec::with_capacity(payload.len() + 2);
+        for &byte in payload {
+            match byte {
+                END => encoded.extend_from_slice(&[ESC, ESC_END]),
+                ESC => encoded.extend_from_slice(&[ESC, ESC_ESC]),
+                _ => encoded.push(byte),
+            }
+        }
+        encoded.push(END);
+        Ok(encoded)
+    }
+}
+
+impl Decoder<Vec<u8>> for Slip {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        // An escape split across reads has no `END` after it yet, so it waits here too.
+        let start = data.iter().take_while(|&&byte| byte == END).count();
+        let Some(end) = data[start..].iter().position(|&byte| byte == END) else {
+            return DecoderResult::Continue;
+        };
+        match unescape(&data[start..start + end]) {
+            Ok(payload) => DecoderResult::Done(payload, start + end + 1),
+            Err(e) => DecoderResult::Error(e),
+        }
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        let start = data.iter().take_while(|&&byte| byte == END).count();
+        data[start..]
+            .iter()
+            .position(|&byte| byte == END)
+            .map(|end| start + end + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_with_special_bytes() {
+        let mut codec = Slip::new();
+        let payload = [0x01, END, 0x02, ESC, ESC_END, ESC_ESC, END, END, ESC];
+        let encoded = codec.encode(payload).expect("Failed to encode");
+        assert_eq!(
+            encoded.iter().position(|&byte| byte == END),
+            Some(encoded.len() - 1),
+            "Only the terminator may be END"
+        );
+        match codec.decode(&encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, payload);
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded frame"),
+        }
+    }
+
+    #[test]
+    fn test_known_vector() {
+        let mut codec = Slip::new();
+        assert_eq!(
+            codec.encode([0x11, END, ESC]).unwrap(),
+            [0x11, ESC, ESC_END, ESC, ESC_ESC, END]
+        );
+    }
+
+    #[test]
+    fn test_split_escape_sequence() {
+        let mut codec = Slip::new();
+        let encoded = codec.encode([END]).unwrap();
+        assert_eq!(encoded, [ESC, ESC_END, END]);
+        // The read boundary falls between ESC and ESC_END.
+        assert!(matches!(
+            codec.decode(&encoded[..1]),
+            DecoderResult::Continue
+        ));
+        assert!(matches!(
+            codec.decode(&encoded[..2]),
+            DecoderResult::Continue
+        ));
+        assert!(matches!(
+            codec.decode(&encoded),
+            DecoderResult::Done(decoded, 3) if decoded == [END]
+        ));
+    }
+
+    #[test]
+    fn test_skips_leading_end_bytes() {
+        let mut codec = Slip::new();
+        assert!(matches!(
+            codec.decode(&[END, END, 0x42, END, 0x43]),
+            DecoderResult::Done(decoded, 4) if decoded == [0x42]
+        ));
+        assert!(matches!(codec.decode(&[END, END]), DecoderResult::Continue));
+    }
+
+    #[test]
+    fn test_invalid_escape() {
+        let mut codec = Slip::new();
+        assert!(matches!(
+            codec.decode(&[0x01, ESC, 0x02, END]),
+            DecoderResult::Error(_)
+        ));
+        assert!(matches!(
+            codec.decode(&[0x01, ESC, END]),
+            DecoderResult::Error(_)
+        ));
+        assert_eq!(codec.resync(&[0x01, ESC, END, 0x02]), Some(3));
+    }
+}
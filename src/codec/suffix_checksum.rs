@@ -0,0 +1,237 @@
+//! Trailing-checksum framing: `<type><body><crc>`.
+use alloc::{format, vec::Vec};
+
+use bytes::BytesMut;
+
+use crate::{
+    codec::{Checksum, Endian},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+const TYPE_SIZE: usize = 1;
+
+/// Codec for legacy records of a type byte and a body, ended by a checksum instead of carrying
+/// their length.
+///
+/// Messages are `(type, body)` pairs, and every record is the type byte, the body and a
+/// big-endian checksum of both. Nothing marks where the body ends, so the decoder looks for the
+/// first end of the buffered data whose trailer is the checksum of the bytes in front of it.
+/// Encoding rejects the rare records whose bytes already hold such a trailer before their end,
+/// since they would be decoded as a shorter record.
+#[derive(Debug, Clone)]
+pub struct SuffixChecksum {
+    checksum: Checksum,
+    max_len: Option<usize>,
+}
+
+impl SuffixChecksum {
+    /// Creates a new trailing-checksum codec with no body length limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `checksum`: The CRC algorithm ending every record.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `SuffixChecksum`.
+    pub fn new(checksum: Checksum) -> Self {
+        Self {
+            checksum,
+            max_len: None,
+        }
+    }
+
+    /// Sets the maximum body length.
+    ///
+    /// Without a limit a stream that never forms a valid record is buffered forever. With one,
+    /// decoding fails once more than a whole record's worth of bytes is buffered without a
+    /// matching checksum.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_len`: The maximum body length in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the limit applied.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    fn max_record_len(&self) -> Option<usize> {
+        self.max_len
+            .map(|max| max.saturating_add(TYPE_SIZE + self.checksum.size()))
+    }
+
+    /// Finds the first record end within `data` whose trailer matches the checksum in front of
+    /// it.
+    fn find_end(&self, data: &[u8]) -> Option<usize> {
+        let size = self.checksum.size();
+        let last = match self.max_record_len() {
+            Some(max) => data.len().min(max),
+            None => data.len(),
+        };
+        let mut crc = self.checksum.update(!0, data.get(..TYPE_SIZE)?);
+        for end in TYPE_SIZE + size..=last {
+            let body_end = end - size;
+            let trailer = &data[body_end..end];
+            let expected = Endian::Big.read_u32([trailer[0], trailer[1], trailer[2], trailer[3]]);
+            if expected == !crc {
+                return Some(end);
+            }
+            crc = self.checksum.update(crc, &data[body_end..=body_end]);
+        }
+        None
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<(u8, T)> for SuffixChecksum {
+    type Error = CodecError;
+
+    fn size_hint(&self, (_, body): &(u8, T)) -> Option<usize> {
+        Some(TYPE_SIZE + body.as_ref().len() + self.checksum.size())
+    }
+
+    fn encode(&mut self, data: (u8, T)) -> Result<Vec<u8>, Self::Error> {
+        let mut encoded = BytesMut::new();
+        self.encode_into(data, &mut encoded)?;
+        Ok(encoded.to_vec())
+    }
+
+    fn encode_into(
+        &mut self,
+        (kind, body): (u8, T),
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        let body = body.as_ref();
+        if let Some(max) = self.max_len
+            && body.len() > max
+        {
+            return Err(format!(
+                "Body of {} bytes exceeds the maximum of {max} bytes",
+                body.len()
+            )
+            .into());
+        }
+        let start = dst.len();
+        dst.reserve(TYPE_SIZE + body.len() + self.checksum.size());
+        dst.extend_from_slice(&[kind]);
+        dst.extend_from_slice(body);
+        let crc = self.checksum.compute(&dst[start..]);
+        Endian::Big.write_u32(crc, dst);
+        let record = &dst[start..];
+        match self.find_end(record) {
+            Some(end) if end == record.len() => Ok(()),
+            _ => {
+                dst.truncate(start);
+                Err(format!(
+                    "Body of {} bytes holds a {:?} trailer and would be decoded as a shorter record",
+                    body.len(),
+                    self.checksum
+                )
+                .into())
+            }
+        }
+    }
+}
+
+impl Decoder<(u8, Vec<u8>)> for SuffixChecksum {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<(u8, Vec<u8>)> {
+        if let Some(end) = self.find_end(data) {
+            let body = data[TYPE_SIZE..end - self.checksum.size()].to_vec();
+            return DecoderResult::Done((data[0], body), end);
+        }
+        match (self.max_len, self.max_record_len()) {
+            (Some(max), Some(max_record)) if data.len() >= max_record => DecoderResult::Error(
+                format!("No checksum trailer found within {max} bytes of body").into(),
+            ),
+            _ => DecoderResult::Continue,
+        }
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        TYPE_SIZE + self.checksum.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_round_trip() {
+        for checksum in [Checksum::Crc32, Checksum::Crc32c] {
+            let mut codec = SuffixChecksum::new(checksum);
+            let encoded = codec.encode((3, b"legacy record")).unwrap();
+            assert_eq!(encoded.len(), 1 + 13 + 4);
+            assert_eq!(&encoded[..14], b"\x03legacy record");
+            assert_eq!(
+                encoded[14..],
+                checksum.compute(&encoded[..14]).to_be_bytes()
+            );
+            match codec.decode(&encoded) {
+                DecoderResult::Done((kind, body), used) => {
+                    assert_eq!(kind, 3);
+                    assert_eq!(body, b"legacy record");
+                    assert_eq!(used, encoded.len());
+                }
+                _ => panic!("Expected a decoded record for {checksum:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_prefixed_body() {
+        let mut codec = SuffixChecksum::new(Checksum::Crc32);
+        let mut data = codec.encode((0, [0, 0, 0, 0, 7, 8])).unwrap();
+        data.extend(codec.encode((1, b"")).unwrap());
+
+        let mut records = Vec::new();
+        let mut rest = data.as_slice();
+        while let DecoderResult::Done(record, used) = codec.decode(rest) {
+            records.push(record);
+            rest = &rest[used..];
+        }
+        assert_eq!(records, [(0, vec![0, 0, 0, 0, 7, 8]), (1, Vec::new())]);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_body_holding_a_checksum() {
+        let mut codec = SuffixChecksum::new(Checksum::Crc32);
+        let mut body = Checksum::Crc32.compute(&[5]).to_be_bytes().to_vec();
+        body.push(9);
+        let mut dst = BytesMut::from(&b"queued"[..]);
+        assert!(codec.encode_into((5, &body), &mut dst).is_err());
+        assert_eq!(dst, &b"queued"[..]);
+    }
+
+    #[test]
+    fn test_waits_for_checksum() {
+        let mut codec = SuffixChecksum::new(Checksum::Crc32c);
+        let encoded = codec.encode((7, b"partial record")).unwrap();
+        for end in 0..encoded.len() {
+            assert!(
+                matches!(codec.decode(&encoded[..end]), DecoderResult::Continue),
+                "Decoded a record from {end} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_len() {
+        let mut codec = SuffixChecksum::new(Checksum::Crc32).with_max_len(8);
+        assert!(codec.encode((1, b"too long!")).is_err());
+        assert!(matches!(
+            codec.decode(b"no checksum at all"),
+            DecoderResult::Error(_)
+        ));
+        assert!(matches!(codec.decode(b"short"), DecoderResult::Continue));
+    }
+}
@@ -0,0 +1,229 @@
+//! Trailing-length framing: `<payload><length>`.
+use alloc::{format, vec::Vec};
+
+use bytes::BytesMut;
+
+use crate::{
+    codec::{Endian, LengthDelimited, PrefixWidth},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Codec for legacy records that carry their payload length in a trailer instead of a header.
+///
+/// Nothing marks where a record starts its trailer, so the decoder looks for the first end of
+/// the buffered data whose trailer matches the number of payload bytes in front of it. Payload
+/// bytes that happen to look like such a trailer would end the record early, so encoding
+/// rejects those payloads, e.g. any starting with as many zero bytes as the trailer is wide.
+/// Formats that need to carry them should use `SuffixChecksum`, whose trailers payloads only
+/// match by chance.
+#[derive(Debug, Clone)]
+pub struct SuffixLength {
+    length: LengthDelimited,
+    width: PrefixWidth,
+    max_len: Option<usize>,
+}
+
+impl SuffixLength {
+    /// Creates a new trailing-length codec with no payload length limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The width of the length trailer.
+    /// * `endian`: The byte order of the length trailer.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `SuffixLength`.
+    pub fn new(width: PrefixWidth, endian: Endian) -> Self {
+        Self {
+            length: LengthDelimited::new(width, endian),
+            width,
+            max_len: None,
+        }
+    }
+
+    /// Sets the maximum payload length.
+    ///
+    /// Without a limit a stream that never forms a valid record is buffered forever. With one,
+    /// decoding fails once more than a whole record's worth of bytes is buffered without a
+    /// matching trailer.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_len`: The maximum payload length in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the limit applied.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    fn width(&self) -> usize {
+        self.width.size()
+    }
+
+    /// Finds the first record end within `data` whose trailer matches its payload length.
+    fn find_end(&self, data: &[u8]) -> Option<usize> {
+        let width = self.width();
+        let last = match self.max_len {
+            Some(max) => data.len().min(max.saturating_add(width)),
+            None => data.len(),
+        };
+        (width..=last).find(|&end| {
+            let len = self.length.read_prefix(&data[end - width..end]);
+            len == (end - width) as u64
+        })
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for SuffixLength {
+    type Error = CodecError;
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        Some(data.as_ref().len() + self.width())
+    }
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let mut encoded = BytesMut::new();
+        self.encode_into(data, &mut encoded)?;
+        Ok(encoded.to_vec())
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let payload = data.as_ref();
+        if let Some(max) = self.max_len
+            && payload.len() > max
+        {
+            return Err(format!(
+                "Payload of {} bytes exceeds the maximum of {max} bytes",
+                payload.len()
+            )
+            .into());
+        }
+        let len = self.length.check_len(payload.len())?;
+        let start = dst.len();
+        dst.reserve(payload.len() + self.width());
+        dst.extend_from_slice(payload);
+        self.length.write_prefix(len, dst);
+        let record = &dst[start..];
+        match self.find_end(record) {
+            Some(end) if end == record.len() => Ok(()),
+            _ => {
+                dst.truncate(start);
+                Err(format!(
+                    "Payload of {} bytes holds a length trailer and would be decoded as a shorter record",
+                    payload.len()
+                )
+                .into())
+            }
+        }
+    }
+}
+
+impl Decoder<Vec<u8>> for SuffixLength {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        let width = self.width();
+        if let Some(end) = self.find_end(data) {
+            return DecoderResult::Done(data[..end - width].to_vec(), end);
+        }
+        match self.max_len {
+            Some(max) if data.len() >= max.saturating_add(width) => DecoderResult::Error(
+                format!("No length trailer found within {max} bytes of payload").into(),
+            ),
+            _ => DecoderResult::Continue,
+        }
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        self.width()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for endian in [Endian::Big, Endian::Little] {
+            let mut codec = SuffixLength::new(PrefixWidth::U32, endian);
+            let encoded = codec.encode(b"legacy record").unwrap();
+            assert_eq!(encoded.len(), 13 + 4);
+            assert_eq!(&encoded[..13], b"legacy record");
+            match codec.decode(&encoded) {
+                DecoderResult::Done(decoded, used) => {
+                    assert_eq!(decoded, b"legacy record");
+                    assert_eq!(used, encoded.len());
+                }
+                _ => panic!("Expected a decoded record for {endian:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_trailer_byte_order() {
+        let mut codec = SuffixLength::new(PrefixWidth::U16, Endian::Big);
+        assert_eq!(codec.encode(b"ab").unwrap(), b"ab\x00\x02");
+        let mut codec = SuffixLength::new(PrefixWidth::U16, Endian::Little);
+        assert_eq!(codec.encode(b"ab").unwrap(), b"ab\x02\x00");
+    }
+
+    #[test]
+    fn test_consecutive_records() {
+        let mut codec = SuffixLength::new(PrefixWidth::U32, Endian::Big);
+        let mut data = codec.encode(b"first").unwrap();
+        data.extend(codec.encode(b"").unwrap());
+        data.extend(codec.encode(b"third record").unwrap());
+
+        let mut records = Vec::new();
+        let mut rest = data.as_slice();
+        while let DecoderResult::Done(record, used) = codec.decode(rest) {
+            records.push(record);
+            rest = &rest[used..];
+        }
+        assert_eq!(records, [&b"first"[..], b"", b"third record"]);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_waits_for_trailer() {
+        let mut codec = SuffixLength::new(PrefixWidth::U32, Endian::Big);
+        let encoded = codec.encode(b"partial record").unwrap();
+        for end in 0..encoded.len() {
+            assert!(
+                matches!(codec.decode(&encoded[..end]), DecoderResult::Continue),
+                "Decoded a record from {end} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_payload_read_as_shorter_record() {
+        let mut codec = SuffixLength::new(PrefixWidth::U32, Endian::Big);
+        let mut dst = BytesMut::from(&b"queued"[..]);
+        assert!(codec.encode_into([0, 0, 0, 0, 7, 8], &mut dst).is_err());
+        assert_eq!(dst, &b"queued"[..]);
+        let encoded = codec.encode([1, 0, 0, 0, 7, 8]).unwrap();
+        assert!(matches!(
+            codec.decode(&encoded),
+            DecoderResult::Done(decoded, 10) if decoded == [1, 0, 0, 0, 7, 8]
+        ));
+    }
+
+    #[test]
+    fn test_max_len() {
+        let mut codec = SuffixLength::new(PrefixWidth::U32, Endian::Big).with_max_len(8);
+        assert!(codec.encode(b"too long!").is_err());
+        assert!(matches!(
+            codec.decode(b"no trailer at all"),
+            DecoderResult::Error(_)
+        ));
+        assert!(matches!(codec.decode(b"short"), DecoderResult::Continue));
+    }
+}
@@ -0,0 +1,263 @@
+//! Tagged union framing: `[u8 tag][u32 length][body]`.
+use alloc::{collections::BTreeMap, format, vec::Vec};
+use core::fmt;
+
+use bytes::{BufMut, BytesMut};
+
+use crate::{
+    codec::{Endian, frame_len},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Size of the frame header, the tag byte and the big-endian `u32` body length.
+const HEADER_LEN: usize = 5;
+
+/// Turns a message into its tag and body bytes.
+pub type TagEncodeFn<T> = fn(&T) -> Result<(u8, Vec<u8>), CodecError>;
+
+/// Decodes the body of a frame carrying one particular tag.
+pub type TagDecodeFn<T> = fn(&[u8]) -> Result<T, CodecError>;
+
+/// Codec for heterogeneous messages of an enum `T`, each frame carrying a type tag byte and a
+/// big-endian `u32` body length ahead of the body.
+///
+/// The encoder reports every message's tag along with its body bytes. The decoder looks up the
+/// function registered with `with_tag` for the tag of each frame and hands it the body, frames
+/// with an unregistered tag fail to decode.
+pub struct Tagged<T> {
+    encode: TagEncodeFn<T>,
+    decoders: BTreeMap<u8, TagDecodeFn<T>>,
+    max_len: Option<usize>,
+}
+
+impl<T> Clone for Tagged<T> {
+    fn clone(&self) -> Self {
+        Self {
+            encode: self.encode,
+            decoders: self.decoders.clone(),
+            max_len: self.max_len,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Tagged<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tagged")
+            .field("tags", &self.decoders.keys())
+            .field("max_len", &self.max_len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Tagged<T> {
+    /// Creates a new tagged codec with no tags registered for decoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `encode`: Returns the tag and the body bytes of a message.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Tagged`.
+    pub fn new(encode: TagEncodeFn<T>) -> Self {
+        Self {
+            encode,
+            decoders: BTreeMap::new(),
+            max_len: None,
+        }
+    }
+
+    /// Registers the function decoding the bodies of frames tagged `tag`, replacing any
+    /// function registered for it before.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag`: The type tag.
+    /// * `decode`: Decodes a body into a message.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the tag registered.
+    pub fn with_tag(mut self, tag: u8, decode: TagDecodeFn<T>) -> Self {
+        self.decoders.insert(tag, decode);
+        self
+    }
+
+    /// Sets the maximum body length.
+    ///
+    /// Longer bodies fail to encode, and frames announcing one fail to decode before the body
+    /// is buffered. By default only the `u32` length field bounds it.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_len`: The maximum body length in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the limit applied.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    fn check_len(&self, len: usize) -> Result<(), CodecError> {
+        let max = self
+            .max_len
+            .unwrap_or(u32::MAX as usize)
+            .min(u32::MAX as usize);
+        if len > max {
+            return Err(format!("Body of {len} bytes exceeds the maximum of {max} bytes").into());
+        }
+        Ok(())
+    }
+
+    fn encode_message(&self, message: &T, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let (tag, body) = (self.encode)(message)?;
+        self.check_len(body.len())?;
+        dst.reserve(HEADER_LEN + body.len());
+        dst.put_u8(tag);
+        Endian::Big.write_u32(body.len() as u32, dst);
+        dst.extend_from_slice(&body);
+        Ok(())
+    }
+}
+
+impl<T> Encoder<T> for Tagged<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        Encoder::<&T>::encode(self, &data)
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_message(&data, dst)
+    }
+}
+
+impl<'a, T> Encoder<&'a T> for Tagged<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: &'a T) -> Result<Vec<u8>, Self::Error> {
+        let mut encoded = BytesMut::new();
+        self.encode_message(data, &mut encoded)?;
+        Ok(encoded.to_vec())
+    }
+
+    fn encode_into(&mut self, data: &'a T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_message(data, dst)
+    }
+}
+
+impl<T> Decoder<T> for Tagged<T> {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T> {
+        let Some(header) = data.get(..HEADER_LEN) else {
+            return DecoderResult::Continue;
+        };
+        let tag = header[0];
+        let Some(decode) = self.decoders.get(&tag) else {
+            return DecoderResult::Error(format!("Unknown message tag {tag:#04x}").into());
+        };
+        let len = Endian::Big.read_u32([header[1], header[2], header[3], header[4]]);
+        let frame_len = match frame_len(HEADER_LEN, u64::from(len)) {
+            Ok(frame_len) => frame_len,
+            Err(e) => return DecoderResult::Error(e),
+        };
+        if let Err(e) = self.check_len(frame_len - HEADER_LEN) {
+            return DecoderResult::Error(e);
+        }
+        let Some(body) = data.get(HEADER_LEN..frame_len) else {
+            return DecoderResult::Continue;
+        };
+        match decode(body) {
+            Ok(message) => DecoderResult::Done(message, frame_len),
+            Err(e) => DecoderResult::Error(e),
+        }
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        HEADER_LEN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::String, vec};
+
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        Ping(u32),
+        Text(String),
+    }
+
+    fn codec() -> Tagged<Message> {
+        Tagged::new(|message| match message {
+            Message::Ping(seq) => Ok((1, seq.to_be_bytes().to_vec())),
+            Message::Text(text) => Ok((2, text.as_bytes().to_vec())),
+        })
+        .with_tag(1, |body| {
+            let seq: [u8; 4] = body.try_into().map_err(|_| "Ping body must be 4 bytes")?;
+            Ok(Message::Ping(u32::from_be_bytes(seq)))
+        })
+        .with_tag(2, |body| {
+            let text = core::str::from_utf8(body).map_err(CodecError::new)?;
+            Ok(Message::Text(text.into()))
+        })
+    }
+
+    #[test]
+    fn test_interleaved_variants() {
+        let mut codec = codec();
+        let messages = vec![
+            Message::Ping(7),
+            Message::Text("hello".into()),
+            Message::Ping(8),
+            Message::Text(String::new()),
+        ];
+        let mut data = Vec::new();
+        for message in &messages {
+            data.extend(codec.encode(message).unwrap());
+        }
+        assert_eq!(&data[..9], &[1, 0, 0, 0, 4, 0, 0, 0, 7]);
+
+        let mut decoded = Vec::new();
+        let mut rest = data.as_slice();
+        while let DecoderResult::Done(message, used) = codec.decode(rest) {
+            decoded.push(message);
+            rest = &rest[used..];
+        }
+        assert_eq!(decoded, messages);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_waits_for_body() {
+        let mut codec = codec();
+        let data = codec.encode(Message::Text("partial".into())).unwrap();
+        assert!(matches!(codec.decode(&data[..3]), DecoderResult::Continue));
+        assert!(matches!(codec.decode(&data[..8]), DecoderResult::Continue));
+    }
+
+    #[test]
+    fn test_unknown_tag() {
+        let mut codec = codec();
+        assert!(matches!(
+            codec.decode(&[9, 0, 0, 0, 0]),
+            DecoderResult::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_max_len() {
+        let mut codec = codec().with_max_len(4);
+        assert!(codec.encode(Message::Text("too long".into())).is_err());
+        assert!(matches!(
+            codec.decode(&[2, 0, 0, 0, 5, b'h']),
+            DecoderResult::Error(_)
+        ));
+    }
+}
@@ -0,0 +1,230 @@
+//! Varint length-prefixed framing: `<LEB128 length><payload>`.
+use alloc::{format, vec::Vec};
+
+use crate::{
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Maximum number of bytes an unsigned LEB128 encoded `u64` can occupy.
+const MAX_VARINT_LEN: usize = 10;
+
+/// Appends `value` to `dst` as an unsigned LEB128 varint.
+pub(crate) fn encode_varint(mut value: u64, dst: &mut Vec<u8>) {
+    while value >= 0x80 {
+        dst.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    dst.push(value as u8);
+}
+
+/// Reads an unsigned LEB128 varint from the start of `data`.
+///
+/// Returns `Ok(None)` if `data` ends before the varint does, otherwise the decoded value and
+/// the number of bytes it occupied.
+pub(crate) fn decode_varint(data: &[u8]) -> Result<Option<(u64, usize)>, CodecError> {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().enumerate() {
+        if i == MAX_VARINT_LEN {
+            break;
+        }
+        let bits = u64::from(byte & 0x7F);
+        let shift = 7 * i as u32;
+        if shift == 63 && bits > 1 {
+            break;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    if data.len() < MAX_VARINT_LEN && data.iter().all(|byte| byte & 0x80 != 0) {
+        Ok(None)
+    } else {
+        Err("Varint length prefix overflows 64 bits".into())
+    }
+}
+
+/// Codec framing each payload with an unsigned LEB128 varint length prefix.
+#[derive(Debug, Clone)]
+pub struct VarintDelimited {
+    max_len: Option<usize>,
+}
+
+impl VarintDelimited {
+    /// Creates a new varint-delimited codec with no payload length limit.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `VarintDelimited`.
+    pub fn new() -> Self {
+        Self { max_len: None }
+    }
+
+    /// Sets the maximum payload length accepted when encoding or decoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_len`: The maximum payload length in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the limit applied.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+}
+
+impl Default for VarintDelimited {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for VarintDelimited {
+    type Error = CodecError;
+
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        let len = data.as_ref().len();
+        let prefix = (u64::BITS - (len as u64 | 1).leading_zeros()).div_ceil(7);
+        Some(prefix as usize + len)
+    }
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let payload = data.as_ref();
+        if let Some(max) = self.max_len
+            && payload.len() > max
+        {
+            return Err(format!(
+                "Payload of {} bytes exceeds the maximum of {} bytes",
+                payload.len(),
+                max
+            )
+            .into());
+        }
+        let mut encoded = Vec::with_capacity(MAX_VARINT_LEN + payload.len());
+        encode_varint(payload.len() as u64, &mut encoded);
+        encoded.extend_from_slice(payload);
+        Ok(encoded)
+    }
+}
+
+impl Decoder<Vec<u8>> for VarintDelimited {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Vec<u8>> {
+        let (len, header) = match decode_varint(data) {
+            Ok(Some(prefix)) => prefix,
+            Ok(None) => return DecoderResult::Continue,
+            Err(e) => return DecoderResult::Error(e),
+        };
+        let Ok(len) = usize::try_from(len) else {
+            return DecoderResult::Error("Varint length prefix exceeds usize".into());
+        };
+        if let Some(max) = self.max_len
+            && len > max
+        {
+            return DecoderResult::Error(
+                format!("Frame of {len} bytes exceeds the maximum of {max} bytes").into(),
+            );
+        }
+        let Some(frame_len) = len.checked_add(header) else {
+            return DecoderResult::Error("Varint length prefix exceeds usize".into());
+        };
+        match data.get(header..frame_len) {
+            Some(payload) => DecoderResult::Done(payload.to_vec(), frame_len),
+            None => DecoderResult::Continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn varint(value: u64) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        encode_varint(value, &mut encoded);
+        encoded
+    }
+
+    #[test]
+    fn test_varint_boundaries() {
+        assert_eq!(varint(0), [0x00]);
+        assert_eq!(varint(127), [0x7F]);
+        assert_eq!(varint(128), [0x80, 0x01]);
+        assert_eq!(varint(16383), [0xFF, 0x7F]);
+        assert_eq!(varint(16384), [0x80, 0x80, 0x01]);
+        assert_eq!(varint((1 << 28) - 1).len(), 4);
+        assert_eq!(varint(1 << 28).len(), 5);
+        assert_eq!(varint(u64::MAX).len(), MAX_VARINT_LEN);
+        for value in [0, 127, 128, 16383, 16384, 1 << 28, u64::MAX] {
+            let encoded = varint(value);
+            assert_eq!(
+                decode_varint(&encoded).expect("Failed to decode"),
+                Some((value, encoded.len()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip_across_prefix_sizes() {
+        let mut codec = VarintDelimited::new();
+        for len in [0, 1, 127, 128, 16383, 16384] {
+            let payload = vec![0x5A; len];
+            let encoded = codec.encode(&payload).expect("Failed to encode");
+            assert_eq!(codec.size_hint(&&payload), Some(encoded.len()));
+            match codec.decode(&encoded) {
+                DecoderResult::Done(decoded, used) => {
+                    assert_eq!(decoded, payload);
+                    assert_eq!(used, encoded.len());
+                }
+                _ => panic!("Expected a decoded frame for {len} bytes"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_five_byte_prefix_waits_for_body() {
+        let mut codec = VarintDelimited::new();
+        let header = varint(1 << 28);
+        assert_eq!(header.len(), 5);
+        assert!(matches!(codec.decode(&header), DecoderResult::Continue));
+    }
+
+    #[test]
+    fn test_split_varint() {
+        let mut codec = VarintDelimited::new();
+        let encoded = codec.encode(vec![7u8; 16384]).unwrap();
+        // The three prefix bytes and the body arrive one byte at a time.
+        for end in 0..encoded.len() {
+            assert!(matches!(
+                codec.decode(&encoded[..end]),
+                DecoderResult::Continue
+            ));
+        }
+        assert!(matches!(
+            codec.decode(&encoded),
+            DecoderResult::Done(_, used) if used == encoded.len()
+        ));
+    }
+
+    #[test]
+    fn test_rejects_overflow_and_max_len() {
+        let mut codec = VarintDelimited::new();
+        let overflow = [0xFF; 11];
+        assert!(matches!(codec.decode(&overflow), DecoderResult::Error(_)));
+        let overflow = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02];
+        assert!(matches!(codec.decode(&overflow), DecoderResult::Error(_)));
+
+        let mut codec = VarintDelimited::new().with_max_len(1024);
+        assert!(codec.encode(vec![0u8; 1025]).is_err());
+        assert!(matches!(
+            codec.decode(&varint(1025)),
+            DecoderResult::Error(_)
+        ));
+    }
+}
@@ -0,0 +1,503 @@
+//! WebSocket data framing (RFC 6455, section 5), without the opening handshake.
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use bytes::{BufMut, BytesMut};
+
+use crate::{
+    codec::{Endian, frame_len},
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Largest payload a control frame may carry.
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// A WebSocket message, or a control frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WsFrame {
+    /// A UTF-8 text message.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// A ping, with application data the pong must echo.
+    Ping(Vec<u8>),
+    /// A pong, answering a ping.
+    Pong(Vec<u8>),
+    /// A close frame, with the status code and reason if the peer gave one.
+    Close(Option<(u16, String)>),
+}
+
+/// Codec for the server side of a WebSocket connection, after the HTTP upgrade.
+///
+/// Decoding unmasks client frames and reassembles fragmented messages, control frames arriving
+/// between fragments are decoded on their own. Encoded frames are never fragmented and, as
+/// RFC 6455 requires of servers, never masked. Extensions are not supported, frames with RSV
+/// bits set are rejected.
+#[derive(Debug, Clone, Default)]
+pub struct WebSocketFrame {
+    max_payload_len: Option<usize>,
+    /// Opcode and payload of a fragmented message still missing its final frame.
+    fragments: Option<(u8, Vec<u8>)>,
+}
+
+/// A frame header parsed from the wire.
+struct Header {
+    fin: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    header_len: usize,
+    payload_len: usize,
+}
+
+impl WebSocketFrame {
+    /// Creates a new WebSocket codec with no payload length limit.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `WebSocketFrame`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum payload length of a message, fragmented messages counting as a whole.
+    ///
+    /// Frames announcing a longer payload are rejected as soon as their header is decoded,
+    /// before the payload is buffered.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_payload_len`: The maximum payload length in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The codec with the limit applied.
+    pub fn with_max_payload_len(mut self, max_payload_len: usize) -> Self {
+        self.max_payload_len = Some(max_payload_len);
+        self
+    }
+
+    /// Parses the frame header at the start of `data`, `Ok(None)` meaning it is incomplete.
+    fn parse_header(&self, data: &[u8]) -> Result<Option<Header>, CodecError> {
+        let [first, second, ..] = *data else {
+            return Ok(None);
+        };
+        if first & 0x70 != 0 {
+            return Err("WebSocket frame has reserved bits set".into());
+        }
+        let fin = first & 0x80 != 0;
+        let opcode = first & 0x0F;
+        if !matches!(
+            opcode,
+            OPCODE_CONTINUATION
+                | OPCODE_TEXT
+                | OPCODE_BINARY
+                | OPCODE_CLOSE
+                | OPCODE_PING
+                | OPCODE_PONG
+        ) {
+            return Err(format!("Unknown WebSocket opcode {opcode:#x}").into());
+        }
+        let masked = second & 0x80 != 0;
+        let (len, mut header_len) = match second & 0x7F {
+            126 => match data.get(2..4) {
+                Some(len) => (u64::from(Endian::Big.read_u16([len[0], len[1]])), 4),
+                None => return Ok(None),
+            },
+            127 => match data.get(2..10) {
+                Some(len) => {
+                    let len = Endian::Big.read_uint(len);
+                    if len >> 63 != 0 {
+                        return Err("WebSocket payload length has its top bit set".into());
+                    }
+                    (len, 10)
+                }
+                None => return Ok(None),
+            },
+            len => (u64::from(len), 2),
+        };
+        let mask = if masked {
+            let Some(key) = data.get(header_len..header_len + 4) else {
+                return Ok(None);
+            };
+            header_len += 4;
+            Some([key[0], key[1], key[2], key[3]])
+        } else {
+            None
+        };
+        let Ok(payload_len) = usize::try_from(len) else {
+            return Err("WebSocket payload length exceeds addressable memory".into());
+        };
+        if opcode >= OPCODE_CLOSE && (!fin || payload_len > MAX_CONTROL_PAYLOAD) {
+            return Err("WebSocket control frame is fragmented or too long".into());
+        }
+        // Only continuations add to the buffered fragments, control frames stand alone.
+        let buffered = match &self.fragments {
+            Some((_, data)) if opcode == OPCODE_CONTINUATION => data.len(),
+            _ => 0,
+        };
+        if let Some(max) = self.max_payload_len
+            && buffered.saturating_add(payload_len) > max
+        {
+            return Err(format!(
+                "WebSocket payload of {} bytes exceeds the maximum of {max} bytes",
+                buffered.saturating_add(payload_len)
+            )
+            .into());
+        }
+        Ok(Some(Header {
+            fin,
+            opcode,
+            mask,
+            header_len,
+            payload_len,
+        }))
+    }
+
+    /// Builds the decoded frame of a complete message.
+    fn message(opcode: u8, payload: Vec<u8>) -> Result<WsFrame, CodecError> {
+        match opcode {
+            OPCODE_TEXT => String::from_utf8(payload)
+                .map(WsFrame::Text)
+                .map_err(|e| format!("WebSocket text frame is not valid UTF-8: {e}").into()),
+            OPCODE_BINARY => Ok(WsFrame::Binary(payload)),
+            OPCODE_PING => Ok(WsFrame::Ping(payload)),
+            OPCODE_PONG => Ok(WsFrame::Pong(payload)),
+            OPCODE_CLOSE => match payload.as_slice() {
+                [] => Ok(WsFrame::Close(None)),
+                [high, low, reason @ ..] => match core::str::from_utf8(reason) {
+                    Ok(reason) => Ok(WsFrame::Close(Some((
+                        Endian::Big.read_u16([*high, *low]),
+                        reason.to_string(),
+                    )))),
+                    Err(e) => Err(format!("WebSocket close reason is not valid UTF-8: {e}").into()),
+                },
+                [_] => Err("WebSocket close frame has a truncated status code".into()),
+            },
+            opcode => Err(format!("Unknown WebSocket opcode {opcode:#x}").into()),
+        }
+    }
+
+    fn write_frame(opcode: u8, payload: &[u8], dst: &mut BytesMut) {
+        dst.reserve(10 + payload.len());
+        dst.put_u8(0x80 | opcode);
+        match payload.len() {
+            len @ 0..=125 => dst.put_u8(len as u8),
+            len @ 126..=0xFFFF => {
+                dst.put_u8(126);
+                Endian::Big.write_u16(len as u16, dst);
+            }
+            len => {
+                dst.put_u8(127);
+                Endian::Big.write_u64(len as u64, dst);
+            }
+        }
+        dst.put_slice(payload);
+    }
+}
+
+impl Encoder<&WsFrame> for WebSocketFrame {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: &WsFrame) -> Result<Vec<u8>, Self::Error> {
+        let mut encoded = BytesMut::new();
+        self.encode_into(data, &mut encoded)?;
+        Ok(encoded.to_vec())
+    }
+
+    fn encode_into(&mut self, data: &WsFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (opcode, payload) = match data {
+            WsFrame::Text(text) => (OPCODE_TEXT, text.as_bytes()),
+            WsFrame::Binary(payload) => (OPCODE_BINARY, payload.as_slice()),
+            WsFrame::Ping(payload) => (OPCODE_PING, payload.as_slice()),
+            WsFrame::Pong(payload) => (OPCODE_PONG, payload.as_slice()),
+            WsFrame::Close(None) => (OPCODE_CLOSE, &[][..]),
+            WsFrame::Close(Some((code, reason))) => {
+                let mut payload = Vec::with_capacity(2 + reason.len());
+                payload.extend_from_slice(&code.to_be_bytes());
+                payload.extend_from_slice(reason.as_bytes());
+                if payload.len() > MAX_CONTROL_PAYLOAD {
+                    return Err("WebSocket close reason is too long".into());
+                }
+                Self::write_frame(OPCODE_CLOSE, &payload, dst);
+                return Ok(());
+            }
+        };
+        if opcode >= OPCODE_CLOSE && payload.len() > MAX_CONTROL_PAYLOAD {
+            return Err(format!(
+                "WebSocket control frame payload of {} bytes exceeds {MAX_CONTROL_PAYLOAD} bytes",
+                payload.len()
+            )
+            .into());
+        }
+        Self::write_frame(opcode, payload, dst);
+        Ok(())
+    }
+}
+
+impl Encoder<WsFrame> for WebSocketFrame {
+    type Error = CodecError;
+
+    fn encode(&mut self, data: WsFrame) -> Result<Vec<u8>, Self::Error> {
+        self.encode(&data)
+    }
+
+    fn encode_into(&mut self, data: WsFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_into(&data, dst)
+    }
+}
+
+impl Decoder<WsFrame> for WebSocketFrame {
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<WsFrame> {
+        let header = match self.parse_header(data) {
+            Ok(Some(header)) => header,
+            Ok(None) => return DecoderResult::Continue,
+            Err(e) => {
+                self.fragments = None;
+                return DecoderResult::Error(e);
+            }
+        };
+        let frame_len = match frame_len(header.header_len, header.payload_len as u64) {
+            Ok(frame_len) => frame_len,
+            Err(e) => {
+                self.fragments = None;
+                return DecoderResult::Error(e);
+            }
+        };
+        let Some(payload) = data.get(header.header_len..frame_len) else {
+            return DecoderResult::Continue;
+        };
+        let mut payload = payload.to_vec();
+        if let Some(mask) = header.mask {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+        }
+
+        let message = match (header.opcode, self.fragments.take()) {
+            (OPCODE_CONTINUATION, None) => Err("WebSocket continuation frame without a message"),
+            (OPCODE_CONTINUATION, Some((opcode, mut fragments))) => {
+                fragments.extend_from_slice(&payload);
+                if !header.fin {
+                    self.fragments = Some((opcode, fragments));
+                    return DecoderResult::ContinueFrom(frame_len);
+                }
+                Ok((opcode, fragments))
+            }
+            (OPCODE_TEXT | OPCODE_BINARY, Some(_)) => {
+                Err("WebSocket message started before the previous one finished")
+            }
+            (opcode, fragments) if opcode >= OPCODE_CLOSE => {
+                // Control frames may interleave with the fragments of a message.
+                self.fragments = fragments;
+                Ok((opcode, payload))
+            }
+            (opcode, None) if !header.fin => {
+                self.fragments = Some((opcode, payload));
+                return DecoderResult::ContinueFrom(frame_len);
+            }
+            (opcode, _) => Ok((opcode, payload)),
+        };
+        match message {
+            Ok((opcode, payload)) => match Self::message(opcode, payload) {
+                Ok(frame) => DecoderResult::Done(frame, frame_len),
+                Err(e) => DecoderResult::Error(e),
+            },
+            Err(e) => {
+                self.fragments = None;
+                DecoderResult::Error(e.into())
+            }
+        }
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        2
+    }
+
+    fn reset(&mut self) {
+        self.fragments = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn decode_all(codec: &mut WebSocketFrame, mut data: &[u8]) -> Vec<WsFrame> {
+        let mut frames = Vec::new();
+        loop {
+            match codec.decode(data) {
+                DecoderResult::Done(frame, used) => {
+                    frames.push(frame);
+                    data = &data[used..];
+                }
+                DecoderResult::ContinueFrom(skip) => data = &data[skip..],
+                DecoderResult::Continue | DecoderResult::NeedMore(_) => break,
+                DecoderResult::Error(e) | DecoderResult::ErrorAt(e, _) => panic!("{e}"),
+            }
+        }
+        assert!(data.is_empty(), "{} bytes left undecoded", data.len());
+        frames
+    }
+
+    #[test]
+    fn test_small_frame() {
+        let mut codec = WebSocketFrame::new();
+        let encoded = codec.encode(WsFrame::Text("Hello".to_string())).unwrap();
+        // The unmasked "Hello" example of RFC 6455, section 5.7.
+        assert_eq!(encoded, b"\x81\x05Hello");
+        assert_eq!(
+            decode_all(&mut codec, &encoded),
+            [WsFrame::Text("Hello".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_16_bit_length() {
+        let mut codec = WebSocketFrame::new();
+        let payload = vec![0xAB; 300];
+        let encoded = codec.encode(WsFrame::Binary(payload.clone())).unwrap();
+        assert_eq!(encoded[..4], [0x82, 126, 0x01, 0x2C]);
+        assert_eq!(encoded.len(), 4 + 300);
+        assert_eq!(decode_all(&mut codec, &encoded), [WsFrame::Binary(payload)]);
+    }
+
+    #[test]
+    fn test_64_bit_length() {
+        let mut codec = WebSocketFrame::new();
+        let payload = vec![0x5A; 70_000];
+        let encoded = codec.encode(WsFrame::Binary(payload.clone())).unwrap();
+        assert_eq!(encoded[..10], [0x82, 127, 0, 0, 0, 0, 0, 0x01, 0x11, 0x70]);
+        assert_eq!(decode_all(&mut codec, &encoded), [WsFrame::Binary(payload)]);
+    }
+
+    #[test]
+    fn test_extended_length_straddling_reads() {
+        let mut codec = WebSocketFrame::new();
+        let encoded = codec.encode(WsFrame::Binary(vec![1; 70_000])).unwrap();
+        for end in 0..12 {
+            assert!(
+                matches!(codec.decode(&encoded[..end]), DecoderResult::Continue),
+                "Decoded a frame from {end} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_masked_client_frame() {
+        let mut codec = WebSocketFrame::new();
+        // The masked "Hello" example of RFC 6455, section 5.7.
+        let frame = [
+            0x81, 0x85, 0x37, 0xFA, 0x21, 0x3D, 0x7F, 0x9F, 0x4D, 0x51, 0x58,
+        ];
+        assert_eq!(
+            decode_all(&mut codec, &frame),
+            [WsFrame::Text("Hello".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_fragmented_message_with_ping() {
+        let mut codec = WebSocketFrame::new();
+        // "Hel", a ping, then "lo", as in RFC 6455, section 5.4.
+        let mut data = vec![0x01, 0x03];
+        data.extend_from_slice(b"Hel");
+        data.extend_from_slice(&[0x89, 0x00]);
+        data.extend_from_slice(&[0x80, 0x02]);
+        data.extend_from_slice(b"lo");
+        assert_eq!(
+            decode_all(&mut codec, &data),
+            [WsFrame::Ping(vec![]), WsFrame::Text("Hello".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_ping_between_fragments_near_limit() {
+        let mut codec = WebSocketFrame::new().with_max_payload_len(10);
+        let mut data = vec![0x01, 0x08];
+        data.extend_from_slice(b"Hello wo");
+        // The ping doesn't count toward the fragmented message.
+        data.extend_from_slice(&[0x89, 0x04]);
+        data.extend_from_slice(b"ping");
+        data.extend_from_slice(&[0x80, 0x02]);
+        data.extend_from_slice(b"rl");
+        assert_eq!(
+            decode_all(&mut codec, &data),
+            [
+                WsFrame::Ping(b"ping".to_vec()),
+                WsFrame::Text("Hello worl".to_string())
+            ]
+        );
+
+        let mut data = vec![0x01, 0x08];
+        data.extend_from_slice(b"Hello wo");
+        data.extend_from_slice(&[0x80, 0x03]);
+        data.extend_from_slice(b"rld");
+        let DecoderResult::ContinueFrom(skip) = codec.decode(&data) else {
+            panic!("Expected the first fragment to be taken");
+        };
+        assert!(matches!(
+            codec.decode(&data[skip..]),
+            DecoderResult::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_close_frame() {
+        let mut codec = WebSocketFrame::new();
+        let close = WsFrame::Close(Some((1000, "bye".to_string())));
+        let encoded = codec.encode(&close).unwrap();
+        assert_eq!(encoded, b"\x88\x05\x03\xE8bye");
+        assert_eq!(decode_all(&mut codec, &encoded), [close]);
+        assert!(codec.encode(WsFrame::Ping(vec![0; 126])).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_frames() {
+        let mut codec = WebSocketFrame::new().with_max_payload_len(1024);
+        // Reserved bit set.
+        assert!(matches!(
+            codec.decode(&[0xC1, 0x00]),
+            DecoderResult::Error(_)
+        ));
+        // Fragmented ping.
+        assert!(matches!(
+            codec.decode(&[0x09, 0x00]),
+            DecoderResult::Error(_)
+        ));
+        // Reserved opcode.
+        assert!(matches!(
+            codec.decode(&[0x83, 0x00]),
+            DecoderResult::Error(_)
+        ));
+        // Continuation without a message.
+        assert!(matches!(
+            codec.decode(&[0x80, 0x00]),
+            DecoderResult::Error(_)
+        ));
+        // Over the limit, rejected from the header alone.
+        assert!(matches!(
+            codec.decode(&[0x82, 126, 0x10, 0x00]),
+            DecoderResult::Error(_)
+        ));
+        // Invalid UTF-8 text.
+        assert!(matches!(
+            codec.decode(&[0x81, 0x01, 0xFF]),
+            DecoderResult::Error(_)
+        ));
+    }
+}
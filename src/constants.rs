@@ -1,6 +1,13 @@
 //! Constants used in the frame-io crate.
 
-/// Initial size of the internal read buffer for MessageIo.
+/// Default initial size of the internal read buffer for MessageIo.
 pub const INITIAL_BUFFER_SIZE: usize = 1024;
-/// Size of the temporary buffer used for reading from streams.
+/// Default number of bytes requested from the stream per read.
 pub const TEMP_BUFFER_SIZE: usize = 1024;
+/// Most bytes a single read requests past `read_chunk_size` on the strength of a decoder's
+/// `NeedMore` hint. The hint comes from the frame's header, so the read buffer only grows with
+/// the bytes that actually arrive, whatever length a peer announces.
+pub const MAX_READ_AHEAD: usize = 64 * 1024;
+/// Default multiple of the initial buffer capacity the read buffer may reach before it is
+/// reallocated once the frame occupying it has been decoded.
+pub const BUFFER_SHRINK_FACTOR: usize = 4;
@@ -1,20 +1,343 @@
 //! Decoder result types and traits.
+use alloc::vec::Vec;
+use core::ops::{Deref, Range};
+
+use bytes::{Buf, BytesMut};
+
+use crate::error::CodecError;
 
 /// Represents the result of a decoding operation.
 /// - `Continue`: Indicates that more data is needed to decode a complete message.
+/// - `ContinueFrom(usize)`: Like `Continue`, but the decoder has taken what it needs from the
+///   given number of leading bytes (e.g. a header, or body bytes copied into its own state).
+///   Those bytes are dropped and the next call starts right after them, so large frames
+///   arriving in small chunks are not rescanned from the start every time.
+/// - `NeedMore(usize)`: Like `Continue`, but the decoder knows it needs at least this many
+///   bytes past the end of the input data, e.g. the rest of a body whose length it parsed from
+///   a header. Readers reserve room for them and read toward that amount, and don't call the
+///   decoder again until they are buffered. A frame announced past the reader's maximum frame
+///   size is rejected before it is read.
 /// - `Done(T, usize)`: Indicates that a message of type `T` has been successfully decoded,
 ///   along with the number of bytes used from the input data.
-/// - `Error(String)`: Indicates that an error occurred during decoding, with an error message.
-pub enum DecoderResult<T> {
+/// - `Error(E)`: Indicates that an error occurred during decoding.
+/// - `ErrorAt(E, usize)`: Like `Error`, along with the offset into the input data of the byte
+///   where decoding failed. Readers report it as a `DecodeError` carrying the offset.
+pub enum DecoderResult<T, E = CodecError> {
     Continue,
+    ContinueFrom(usize),
+    NeedMore(usize),
     Done(T, usize),
-    Error(String),
+    Error(E),
+    ErrorAt(E, usize),
+}
+
+impl<T, E> DecoderResult<T, E> {
+    /// Maps the decoded message of a `Done` result, leaving the other variants as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: The function applied to the decoded message.
+    ///
+    /// # Returns
+    ///
+    /// The result with the message mapped.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> DecoderResult<U, E> {
+        match self {
+            DecoderResult::Continue => DecoderResult::Continue,
+            DecoderResult::ContinueFrom(skip) => DecoderResult::ContinueFrom(skip),
+            DecoderResult::NeedMore(len) => DecoderResult::NeedMore(len),
+            DecoderResult::Done(msg, used) => DecoderResult::Done(f(msg), used),
+            DecoderResult::Error(e) => DecoderResult::Error(e),
+            DecoderResult::ErrorAt(e, offset) => DecoderResult::ErrorAt(e, offset),
+        }
+    }
+
+    /// Maps the error of an `Error` or `ErrorAt` result, leaving the other variants as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: The function applied to the error.
+    ///
+    /// # Returns
+    ///
+    /// The result with the error mapped.
+    pub fn map_err<F>(self, f: impl FnOnce(E) -> F) -> DecoderResult<T, F> {
+        match self {
+            DecoderResult::Continue => DecoderResult::Continue,
+            DecoderResult::ContinueFrom(skip) => DecoderResult::ContinueFrom(skip),
+            DecoderResult::NeedMore(len) => DecoderResult::NeedMore(len),
+            DecoderResult::Done(msg, used) => DecoderResult::Done(msg, used),
+            DecoderResult::Error(e) => DecoderResult::Error(f(e)),
+            DecoderResult::ErrorAt(e, offset) => DecoderResult::ErrorAt(f(e), offset),
+        }
+    }
 }
 
 /// Trait for decoding messages from a byte slice.
+///
+/// `decode` takes `&mut self`, so decoders may carry state between calls, e.g. a parsed header
+/// length that is still waiting for the rest of its body.
 pub trait Decoder<T> {
+    /// The error produced when the data can't be decoded.
+    type Error: core::error::Error + Send + Sync + 'static;
+
     /// Decodes a message from the given byte slice.
     ///
+    /// `data` comes straight off the wire, so this must never panic whatever it holds. Slice
+    /// it with `get`, and add lengths read from it with checked arithmetic, returning `Error`
+    /// for data that can't form a frame. The `fuzzing` feature's `fuzz::decode_all` exercises
+    /// the built-in codecs this way.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: A byte slice containing the data to decode.
+    ///
+    /// # Returns
+    ///
+    /// A DecoderResult indicating the outcome of the decoding operation.
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T, Self::Error>;
+
+    /// Decodes a message from the reader's own buffer, which the decoder may split frames off.
+    ///
+    /// Readers call this when decoding a message to keep, so a decoder of `Bytes` messages can
+    /// hand out `buf.split_to(n).freeze()` and share the buffer's allocation instead of copying
+    /// the payload. Results count bytes from the start of `buf` as it was passed in, and bytes
+    /// split off its front count toward the `used` of `Done` or the skip of `ContinueFrom`.
+    /// Other results must leave `buf` as it was. The default implementation calls `decode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf`: The buffered data to decode.
+    ///
+    /// # Returns
+    ///
+    /// A DecoderResult indicating the outcome of the decoding operation.
+    fn decode_bytes(&mut self, buf: &mut BytesMut) -> DecoderResult<T, Self::Error> {
+        self.decode(buf)
+    }
+
+    /// Decodes a message's bytes into `out`, a buffer the caller reuses across messages.
+    ///
+    /// Readers call this from `read_message_into`, so decoding a stream of byte payloads
+    /// doesn't allocate a fresh `Vec` per frame once `out` has grown to fit them. On `Done`,
+    /// `out` holds exactly the message's bytes, other results leave it as it was. The default
+    /// implementation calls `decode` and moves the message into `out`, replacing its
+    /// allocation. Decoders of byte payloads override it to copy the payload instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: A byte slice containing the data to decode.
+    /// * `out`: The buffer receiving the decoded bytes.
+    ///
+    /// # Returns
+    ///
+    /// A DecoderResult indicating the outcome of the decoding operation.
+    fn decode_into(&mut self, data: &[u8], out: &mut Vec<u8>) -> DecoderResult<(), Self::Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.decode(data).map(|message| *out = message.into())
+    }
+
+    /// Returns the fewest bytes `decode` needs to make any progress, e.g. a fixed header size.
+    ///
+    /// Readers keep reading until at least this many bytes are buffered before calling
+    /// `decode`, sparing calls that could only return `Continue`. The hint is asked again after
+    /// every call, so it may depend on the decoder's state. The default implementation returns
+    /// 0, `decode` is called whenever data is buffered.
+    ///
+    /// # Returns
+    ///
+    /// The minimum number of bytes worth decoding.
+    fn min_frame_hint(&self) -> usize {
+        0
+    }
+
+    /// Finds where decoding can resume after `decode` returned an error.
+    ///
+    /// Readers with resynchronization enabled call this with the same data that failed to
+    /// decode, drop the returned number of bytes and carry on decoding, instead of failing the
+    /// read. The default implementation returns `None`, the error is reported as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data that failed to decode.
+    ///
+    /// # Returns
+    ///
+    /// The number of leading bytes to discard to reach the next frame boundary, or `None` if
+    /// the data holds no boundary to resume from.
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        let _ = data;
+        None
+    }
+
+    /// Returns the decoder to the state it was created in, dropping any partly parsed frame.
+    ///
+    /// A decoder keeping state between calls may be left halfway through a frame when `decode`
+    /// fails. Readers with resynchronization enabled call this after every decode error, right
+    /// before `resync`, so decoding resumes from a clean state at the next frame boundary.
+    /// Settings such as limits must survive it, only per-frame state is cleared. The default
+    /// implementation does nothing, for decoders without such state.
+    fn reset(&mut self) {}
+}
+
+/// What readers do with the bytes buffered past a decoded frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrainPolicy {
+    /// Keep them for the next frame, as a byte stream needs.
+    #[default]
+    KeepRemaining,
+    /// Drop them, so every read yields at most one frame and trailing garbage is discarded.
+    /// Meant for datagram-like inputs whose reads each return one datagram.
+    DiscardRemaining,
+}
+
+/// Resets `decoder` and finds where decoding can resume, the recovery readers with
+/// resynchronization enabled run after a decode error.
+#[cfg(any(feature = "sync", feature = "async", feature = "fuzzing"))]
+pub(crate) fn reset_and_resync<T, D: Decoder<T>>(decoder: &mut D, data: &[u8]) -> Option<usize> {
+    decoder.reset();
+    decoder.resync(data)
+}
+
+/// Checks the number of bytes a decoder reported as used against the bytes it was given, for
+/// readers to drop the used bytes from their buffer without trusting the decoder not to report
+/// too many.
+///
+/// `split` bytes were split off the front of the buffer by `decode_bytes` and count toward
+/// `used`, `buffered` bytes are left in the buffer. Returns the number of them to drop.
+pub(crate) fn used_remainder(
+    used: usize,
+    split: usize,
+    buffered: usize,
+) -> Result<usize, &'static str> {
+    let rest = used
+        .checked_sub(split)
+        .ok_or("Decoder split off more bytes than it used")?;
+    if rest > buffered {
+        return Err("Decoder used more bytes than are buffered");
+    }
+    Ok(rest)
+}
+
+/// A frame decoded in place, its payload borrowed from the data given to
+/// `RefDecoder::decode_ref`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowedFrame<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> BorrowedFrame<'a> {
+    /// Creates a frame borrowing `payload`.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload`: The frame's payload, a slice of the decoder's input.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `BorrowedFrame`.
+    pub fn new(payload: &'a [u8]) -> Self {
+        Self { payload }
+    }
+
+    /// Returns the frame's payload, borrowed for as long as the decoder's input.
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+}
+
+impl Deref for BorrowedFrame<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.payload
+    }
+}
+
+/// The payload of a frame left in a reader's read buffer, returned by `read_message_arena`.
+///
+/// Derefs to the payload. The guard borrows the reader, so the frame's bytes stay put while it
+/// lives and are dropped from the buffer when the guard is. A guard passed to `mem::forget`
+/// leaves the frame in the buffer, to be read again by the next read.
+#[derive(Debug)]
+pub struct MessageGuard<'a> {
+    buffer: &'a mut BytesMut,
+    payload: Range<usize>,
+    used: usize,
+}
+
+impl<'a> MessageGuard<'a> {
+    /// Wraps the frame at the start of `buffer`, checking the decoder's results fit in it.
+    #[cfg(any(feature = "sync", feature = "async"))]
+    pub(crate) fn new(
+        buffer: &'a mut BytesMut,
+        payload: Range<usize>,
+        used: usize,
+    ) -> Result<Self, &'static str> {
+        if used > buffer.len() {
+            return Err("Decoder used more bytes than are buffered");
+        }
+        if payload.start > payload.end || payload.end > used {
+            return Err("Decoder returned a payload outside of its frame");
+        }
+        Ok(Self {
+            buffer,
+            payload,
+            used,
+        })
+    }
+
+    /// Returns the number of bytes the frame takes up in the buffer, including its framing.
+    pub fn frame_len(&self) -> usize {
+        self.used
+    }
+}
+
+impl Deref for MessageGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[self.payload.clone()]
+    }
+}
+
+impl Drop for MessageGuard<'_> {
+    fn drop(&mut self) {
+        self.buffer.advance(self.used);
+    }
+}
+
+/// Decodes a frame in place, giving the range of its payload in `data` instead of the payload,
+/// for readers keeping the frame in their buffer.
+#[cfg(any(feature = "sync", feature = "async"))]
+pub(crate) fn decode_range<D: RefDecoder>(
+    decoder: &mut D,
+    data: &[u8],
+) -> DecoderResult<(Range<usize>, usize), D::Error> {
+    match decoder.decode_ref(data) {
+        DecoderResult::Done(frame, used) => {
+            // The payload is a slice of `data`, so its offset follows from the pointers.
+            let start = (frame.as_ptr() as usize).wrapping_sub(data.as_ptr() as usize);
+            DecoderResult::Done((start..start.saturating_add(frame.len()), used), used)
+        }
+        result => result.map(|_| (0..0, 0)),
+    }
+}
+
+/// Trait for decoding frames in place, without copying their payload out of the read buffer.
+///
+/// Implemented by framings whose payload is a contiguous slice of the wire data, e.g. length
+/// prefixed or delimited frames. Readers hand the payload to a closure with
+/// `read_message_with`, or return it behind a guard with `read_message_arena`, before dropping
+/// the frame's bytes from their buffer.
+pub trait RefDecoder {
+    /// The error produced when the data can't be decoded.
+    type Error: core::error::Error + Send + Sync + 'static;
+
+    /// Decodes a frame from the given byte slice, borrowing its payload from `data`.
+    ///
     /// # Arguments
     ///
     /// * `data`: A byte slice containing the data to decode.
@@ -22,12 +345,30 @@ pub trait Decoder<T> {
     /// # Returns
     ///
     /// A DecoderResult indicating the outcome of the decoding operation.
-    fn decode(&mut self, data: &[u8]) -> DecoderResult<T>;
+    fn decode_ref<'a>(&mut self, data: &'a [u8]) -> DecoderResult<BorrowedFrame<'a>, Self::Error>;
+
+    /// Finds where decoding can resume after `decode_ref` returned an error, like
+    /// `Decoder::resync`. The default implementation returns `None`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data that failed to decode.
+    ///
+    /// # Returns
+    ///
+    /// The number of leading bytes to discard to reach the next frame boundary, or `None` if
+    /// the data holds no boundary to resume from.
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        let _ = data;
+        None
+    }
 }
 
 /// A no-op decoder implementation for the unit type `()`.
 impl Decoder<()> for () {
-    fn decode(&mut self, data: &[u8]) -> DecoderResult<Self> {
+    type Error = core::convert::Infallible;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<Self, Self::Error> {
         DecoderResult::Done((), data.len())
     }
 }
@@ -1,7 +1,13 @@
 //! Encoder trait for encoding data into bytes.
+use alloc::{vec, vec::Vec};
+
+use bytes::{Bytes, BytesMut};
 
 /// Trait for encoding messages into a byte vector.
 pub trait Encoder<T> {
+    /// The error produced when a message can't be encoded.
+    type Error: core::error::Error + Send + Sync + 'static;
+
     /// Encodes the given data into a byte vector.
     ///
     /// # Type Parameters
@@ -10,17 +16,77 @@ pub trait Encoder<T> {
     ///
     /// # Arguments
     ///
-    /// * `data`: A reference to the data of type `Self::Input` to be encoded
+    /// * `data`: The data to be encoded. Implementing the trait for an owned `T` hands the
+    ///   encoder the message by value, e.g. to move a `Bytes` payload onto the wire uncopied.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the encoded byte vector or the encoder's error.
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error>;
+
+    /// Encodes the given data, appending the bytes to `dst`.
+    ///
+    /// The message I/O writers call this with their internal buffer, so encoders that
+    /// override it avoid allocating a `Vec` per message. The default implementation calls
+    /// `encode` and copies the result into `dst`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data to be encoded.
+    /// * `dst`: The buffer to append the encoded bytes to. Anything appended before an error is
+    ///   returned is discarded by the writers.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the data was encoded.
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(&self.encode(data)?);
+        Ok(())
+    }
+
+    /// Returns how many bytes `data` encodes to, if the encoder can tell without encoding it.
+    ///
+    /// The message I/O writers reserve this much room in their buffer before calling
+    /// `encode_into`, so a large frame is encoded without growing the buffer step by step. An
+    /// upper bound works too, at the cost of the room left unused. The default implementation
+    /// returns `None`, nothing is reserved up front.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data about to be encoded.
     ///
     /// # Returns
     ///
-    /// A Result containing the encoded byte vector or an error message.
-    fn encode(&mut self, data: T) -> Result<Vec<u8>, String>;
+    /// The encoded length, or `None` if it isn't known in advance.
+    fn size_hint(&self, data: &T) -> Option<usize> {
+        let _ = data;
+        None
+    }
+
+    /// Encodes the given data into a list of slices written out back to back.
+    ///
+    /// `AsyncMessageIo::write_message_vectored` hands the slices to the stream with vectored
+    /// writes, so encoders can return e.g. a length prefix and an existing body without copying
+    /// them into one buffer. The default implementation returns the result of `encode` as a
+    /// single slice.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data to be encoded.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the encoded slices or the encoder's error.
+    fn encode_vectored(&mut self, data: T) -> Result<Vec<Bytes>, Self::Error> {
+        Ok(vec![Bytes::from(self.encode(data)?)])
+    }
 }
 
 /// A no-op encoder implementation for the unit type `()`.
 impl Encoder<()> for () {
-    fn encode(&mut self, _data: Self) -> Result<Vec<u8>, String> {
+    type Error = core::convert::Infallible;
+
+    fn encode(&mut self, _data: Self) -> Result<Vec<u8>, Self::Error> {
         Ok(Vec::new())
     }
 }
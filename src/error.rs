@@ -0,0 +1,278 @@
+//! Error types shared by the built-in codecs and the readers and writers.
+use alloc::{boxed::Box, string::String};
+use core::{error::Error, fmt};
+
+#[cfg(any(feature = "sync", feature = "async"))]
+use crate::framer::reader::ReadError;
+
+/// A general purpose error for codecs that don't need their own error type.
+///
+/// It wraps either a plain message or another error, which stays reachable through
+/// `CodecError::get_ref` and `CodecError::into_inner`. The wrapper is transparent: it displays
+/// as the wrapped error and `Error::source` continues with the wrapped error's source, so error
+/// chains don't show the message twice. String-based codecs can migrate by setting
+/// `type Error = CodecError` and converting their messages with `.into()`.
+#[derive(Debug)]
+pub struct CodecError(Box<dyn Error + Send + Sync>);
+
+impl CodecError {
+    /// Creates a new codec error.
+    ///
+    /// # Arguments
+    ///
+    /// * `error`: An error message or an error to wrap.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `CodecError`.
+    pub fn new(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self(error.into())
+    }
+
+    /// Returns a reference to the wrapped error.
+    pub fn get_ref(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.0
+    }
+
+    /// Consumes the error, returning the wrapped error.
+    pub fn into_inner(self) -> Box<dyn Error + Send + Sync> {
+        self.0
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for CodecError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+impl From<String> for CodecError {
+    fn from(message: String) -> Self {
+        Self::new(message)
+    }
+}
+
+impl From<&str> for CodecError {
+    fn from(message: &str) -> Self {
+        Self::new(message)
+    }
+}
+
+/// A decoding error at a known offset into the frame, reported by decoders through
+/// `DecoderResult::ErrorAt`.
+///
+/// Readers return it as the error of `MsgIoError::Decode`, reach it with
+/// `MsgIoError::decode_error::<DecodeError>()`. It displays as the wrapped error followed by
+/// the offset, and `Error::source` continues with the wrapped error's source.
+#[derive(Debug)]
+pub struct DecodeError {
+    offset: usize,
+    error: Box<dyn Error + Send + Sync>,
+}
+
+impl DecodeError {
+    /// Creates a new decode error.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: The offset of the offending byte from the start of the frame.
+    /// * `error`: An error message or an error to wrap.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `DecodeError`.
+    pub fn new(offset: usize, error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self {
+            offset,
+            error: error.into(),
+        }
+    }
+
+    /// Returns the offset of the offending byte from the start of the frame.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns a reference to the wrapped error.
+    pub fn get_ref(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.error
+    }
+
+    /// Consumes the error, returning the wrapped error.
+    pub fn into_inner(self) -> Box<dyn Error + Send + Sync> {
+        self.error
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {} of the frame", self.error, self.offset)
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Error of reading or writing messages, telling failures of the codec apart from failures of
+/// the stream.
+///
+/// `read_message` and `write_message` return it. Methods returning `io::Result` carry it
+/// inside an `io::Error`, and converting that `io::Error` back with `MsgIoError::from` recovers
+/// the variant, while errors of the stream itself become `MsgIoError::Io`. The conversion into
+/// `io::Error` keeps callers of the `io::Result` API compiling with `?`.
+///
+/// The `Io`, `Decode` and `Encode` variants display as the error they hold, and `Error::source`
+/// continues with that error's source, so error chains don't show the message twice.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum MsgIoError {
+    /// The stream failed, e.g. the connection was reset.
+    Io(std::io::Error),
+    /// The decoder rejected the received data. A `DecodeError` if the decoder reported the
+    /// offset of the offending byte, see `decode_error` to get the decoder's own error.
+    Decode(Box<dyn Error + Send + Sync>),
+    /// The encoder rejected a message, nothing of it was written. See `encode_error` to get the
+    /// encoder's own error.
+    Encode(Box<dyn Error + Send + Sync>),
+    /// A frame grew past the limit set with `with_max_frame_size`.
+    FrameTooLarge {
+        /// The number of bytes the frame took up when the limit was hit.
+        size: usize,
+        /// The limit in bytes.
+        max: usize,
+    },
+    /// The stream ended in the middle of a frame.
+    UnexpectedEof,
+}
+
+#[cfg(feature = "std")]
+impl MsgIoError {
+    /// Returns the kind the error has as an `io::Error`.
+    ///
+    /// Codec errors and oversized frames are `InvalidData`, a stream ending in the middle of a
+    /// frame `UnexpectedEof`, and errors of the stream keep their own kind.
+    pub fn kind(&self) -> std::io::ErrorKind {
+        match self {
+            MsgIoError::Io(e) => e.kind(),
+            MsgIoError::Decode(_) | MsgIoError::Encode(_) | MsgIoError::FrameTooLarge { .. } => {
+                std::io::ErrorKind::InvalidData
+            }
+            MsgIoError::UnexpectedEof => std::io::ErrorKind::UnexpectedEof,
+        }
+    }
+
+    /// Returns the decoder's error as its concrete type.
+    ///
+    /// An error the decoder reported with an offset is found both as the `DecodeError` and as
+    /// the decoder's own error type inside it.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `T`: The error type of the decoder, e.g. `CodecError`, or `DecodeError`.
+    ///
+    /// # Returns
+    ///
+    /// The error, or `None` if this is not `MsgIoError::Decode` or the error is not a `T`.
+    pub fn decode_error<T: Error + 'static>(&self) -> Option<&T> {
+        let MsgIoError::Decode(e) = self else {
+            return None;
+        };
+        e.downcast_ref().or_else(|| {
+            e.downcast_ref::<DecodeError>()
+                .and_then(|e| e.error.downcast_ref())
+        })
+    }
+
+    /// Returns the encoder's error as its concrete type.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `T`: The error type of the encoder.
+    ///
+    /// # Returns
+    ///
+    /// The error, or `None` if this is not `MsgIoError::Encode` or the error is not a `T`.
+    pub fn encode_error<T: Error + 'static>(&self) -> Option<&T> {
+        match self {
+            MsgIoError::Encode(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for MsgIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MsgIoError::Io(e) => e.fmt(f),
+            MsgIoError::Decode(e) | MsgIoError::Encode(e) => e.fmt(f),
+            MsgIoError::FrameTooLarge { max, .. } => {
+                write!(f, "Frame exceeds the maximum size of {max} bytes")
+            }
+            MsgIoError::UnexpectedEof => f.write_str("Stream ended in the middle of a frame"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for MsgIoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MsgIoError::Io(e) => e.source(),
+            MsgIoError::Decode(e) | MsgIoError::Encode(e) => e.source(),
+            MsgIoError::FrameTooLarge { .. } | MsgIoError::UnexpectedEof => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for MsgIoError {
+    fn from(error: std::io::Error) -> Self {
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<MsgIoError>())
+        {
+            let inner = error
+                .into_inner()
+                .expect("Checked for an inner error above");
+            return *inner.downcast().expect("Checked for a MsgIoError above");
+        }
+        MsgIoError::Io(error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<MsgIoError> for std::io::Error {
+    fn from(error: MsgIoError) -> Self {
+        match error {
+            MsgIoError::Io(e) => e,
+            error => std::io::Error::new(error.kind(), error),
+        }
+    }
+}
+
+#[cfg(any(feature = "sync", feature = "async"))]
+impl From<ReadError> for MsgIoError {
+    fn from(error: ReadError) -> Self {
+        match error {
+            ReadError::Decode(e) => MsgIoError::Decode(e),
+            ReadError::FrameTooLarge { size, max } => MsgIoError::FrameTooLarge { size, max },
+        }
+    }
+}
+
+#[cfg(any(feature = "sync", feature = "async"))]
+impl From<ReadError> for std::io::Error {
+    fn from(error: ReadError) -> Self {
+        MsgIoError::from(error).into()
+    }
+}
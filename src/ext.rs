@@ -0,0 +1,362 @@
+//! Combinators composing encoders and decoders without a bespoke wrapper struct.
+//!
+//! `EncoderExt` and `DecoderExt` are implemented for every encoder and decoder. Both offer
+//! `with_framing`, so importing either one is enough to frame a codec used both ways. With both
+//! in scope, call it as `EncoderExt::with_framing(codec, framing)` or use `Framed::new`.
+use alloc::{format, vec::Vec};
+use core::marker::PhantomData;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::CodecError,
+};
+
+/// Extension methods for every `Encoder`.
+pub trait EncoderExt<T>: Encoder<T> + Sized {
+    /// Converts messages with `f` before encoding them.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: The function turning the new message type into the one this encoder takes.
+    ///
+    /// # Returns
+    ///
+    /// An encoder of the messages `f` takes.
+    fn map_input<U, F>(self, f: F) -> MapInput<Self, F>
+    where
+        F: FnMut(U) -> T,
+    {
+        MapInput { inner: self, f }
+    }
+
+    /// Wraps every encoded message in a frame of `framing`, e.g. a length prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `framing`: The codec framing the encoded bytes.
+    ///
+    /// # Returns
+    ///
+    /// The framed codec.
+    fn with_framing<F>(self, framing: F) -> Framed<Self, F> {
+        Framed::new(self, framing)
+    }
+}
+
+impl<E: Encoder<T>, T> EncoderExt<T> for E {}
+
+/// Extension methods for every `Decoder`.
+pub trait DecoderExt<T>: Decoder<T> + Sized {
+    /// Converts decoded messages with `f`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: The function turning the decoded messages into the new message type.
+    ///
+    /// # Returns
+    ///
+    /// A decoder of the messages `f` returns.
+    fn map_output<U, F>(self, f: F) -> MapOutput<Self, F, T>
+    where
+        F: FnMut(T) -> U,
+    {
+        MapOutput {
+            inner: self,
+            f,
+            _message: PhantomData,
+        }
+    }
+
+    /// Decodes messages from the payload of every frame of `framing`, e.g. a length prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `framing`: The codec framing the encoded bytes.
+    ///
+    /// # Returns
+    ///
+    /// The framed codec.
+    fn with_framing<F>(self, framing: F) -> Framed<Self, F> {
+        Framed::new(self, framing)
+    }
+}
+
+impl<D: Decoder<T>, T> DecoderExt<T> for D {}
+
+/// Encoder converting messages before handing them to an inner encoder, created by
+/// `EncoderExt::map_input`.
+#[derive(Clone)]
+pub struct MapInput<E, F> {
+    inner: E,
+    f: F,
+}
+
+impl<E, F> MapInput<E, F> {
+    /// Consumes the wrapper, returning the inner encoder.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E, F, T, U> Encoder<U> for MapInput<E, F>
+where
+    E: Encoder<T>,
+    F: FnMut(U) -> T,
+{
+    type Error = E::Error;
+
+    fn encode(&mut self, data: U) -> Result<Vec<u8>, Self::Error> {
+        self.inner.encode((self.f)(data))
+    }
+
+    fn encode_into(&mut self, data: U, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode_into((self.f)(data), dst)
+    }
+
+    fn encode_vectored(&mut self, data: U) -> Result<Vec<Bytes>, Self::Error> {
+        self.inner.encode_vectored((self.f)(data))
+    }
+}
+
+/// Decoder converting the messages of an inner decoder, created by `DecoderExt::map_output`.
+///
+/// `T` is the type of the inner decoder's messages.
+pub struct MapOutput<D, F, T> {
+    inner: D,
+    f: F,
+    _message: PhantomData<fn(T)>,
+}
+
+impl<D: Clone, F: Clone, T> Clone for MapOutput<D, F, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            f: self.f.clone(),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<D, F, T> MapOutput<D, F, T> {
+    /// Consumes the wrapper, returning the inner decoder.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
+
+impl<D, F, T, U> Decoder<U> for MapOutput<D, F, T>
+where
+    D: Decoder<T>,
+    F: FnMut(T) -> U,
+{
+    type Error = D::Error;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<U, Self::Error> {
+        self.inner.decode(data).map(&mut self.f)
+    }
+
+    fn decode_bytes(&mut self, buf: &mut BytesMut) -> DecoderResult<U, Self::Error> {
+        self.inner.decode_bytes(buf).map(&mut self.f)
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        self.inner.min_frame_hint()
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        self.inner.resync(data)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// Codec carrying the bytes of an inner codec `C` in the frames of a framing codec `F`.
+///
+/// Encoding frames the inner encoder's bytes with `F`'s `Encoder<Vec<u8>>`. Decoding takes the
+/// payload of one `F` frame and requires the inner decoder to decode exactly one message from
+/// all of it.
+#[derive(Debug, Clone)]
+pub struct Framed<C, F> {
+    inner: C,
+    framing: F,
+}
+
+impl<C, F> Framed<C, F> {
+    /// Wraps `inner` in the frames of `framing`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: The codec encoding and decoding the frame payloads.
+    /// * `framing`: The codec framing the payloads on the wire.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Framed`.
+    pub fn new(inner: C, framing: F) -> Self {
+        Self { inner, framing }
+    }
+
+    /// Consumes the wrapper, returning the inner codec and the framing codec.
+    pub fn into_parts(self) -> (C, F) {
+        (self.inner, self.framing)
+    }
+}
+
+impl<C, F, T> Encoder<T> for Framed<C, F>
+where
+    C: Encoder<T>,
+    F: Encoder<Vec<u8>>,
+{
+    type Error = CodecError;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, Self::Error> {
+        let payload = self.inner.encode(data).map_err(CodecError::new)?;
+        self.framing.encode(payload).map_err(CodecError::new)
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let payload = self.inner.encode(data).map_err(CodecError::new)?;
+        self.framing
+            .encode_into(payload, dst)
+            .map_err(CodecError::new)
+    }
+}
+
+impl<C, F, T> Decoder<T> for Framed<C, F>
+where
+    C: Decoder<T>,
+    F: Decoder<Vec<u8>>,
+{
+    type Error = CodecError;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<T> {
+        let (payload, used) = match self.framing.decode(data) {
+            DecoderResult::Done(payload, used) => (payload, used),
+            DecoderResult::Continue => return DecoderResult::Continue,
+            DecoderResult::ContinueFrom(skip) => return DecoderResult::ContinueFrom(skip),
+            DecoderResult::NeedMore(len) => return DecoderResult::NeedMore(len),
+            DecoderResult::Error(e) => return DecoderResult::Error(CodecError::new(e)),
+            DecoderResult::ErrorAt(e, offset) => {
+                return DecoderResult::ErrorAt(CodecError::new(e), offset);
+            }
+        };
+        match self.inner.decode(&payload) {
+            DecoderResult::Done(message, inner_used) if inner_used == payload.len() => {
+                DecoderResult::Done(message, used)
+            }
+            DecoderResult::Done(_, inner_used) => DecoderResult::Error(
+                format!(
+                    "Frame holds {} bytes after the inner frame",
+                    payload.len() - inner_used
+                )
+                .into(),
+            ),
+            DecoderResult::Continue
+            | DecoderResult::ContinueFrom(_)
+            | DecoderResult::NeedMore(_) => {
+                DecoderResult::Error("Frame holds an incomplete inner frame".into())
+            }
+            // Offsets into the payload don't locate anything on the wire.
+            DecoderResult::Error(e) | DecoderResult::ErrorAt(e, _) => {
+                DecoderResult::Error(CodecError::new(e))
+            }
+        }
+    }
+
+    fn min_frame_hint(&self) -> usize {
+        self.framing.min_frame_hint()
+    }
+
+    fn resync(&mut self, data: &[u8]) -> Option<usize> {
+        self.framing.resync(data)
+    }
+
+    fn reset(&mut self) {
+        self.framing.reset();
+        self.inner.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{Cobs, Endian, LengthDelimited, PrefixWidth, Slip};
+    use alloc::{string::String, vec};
+
+    fn length_u32() -> LengthDelimited {
+        LengthDelimited::new(PrefixWidth::U32, Endian::Big)
+    }
+
+    #[test]
+    fn test_map_round_trip() {
+        let mut encoder = length_u32().map_input(|text: &str| text.as_bytes().to_vec());
+        let mut decoder = length_u32()
+            .map_output(|bytes: Vec<u8>| String::from_utf8(bytes).expect("Payload is UTF-8"));
+
+        let encoded = encoder.encode("mapped message").unwrap();
+        assert_eq!(&encoded[..4], &[0, 0, 0, 14]);
+        match decoder.decode(&encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, "mapped message");
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded message"),
+        }
+        assert_eq!(decoder.min_frame_hint(), 4);
+    }
+
+    #[test]
+    fn test_with_framing_round_trip() {
+        let mut codec = EncoderExt::<&[u8]>::with_framing(Cobs::new(), length_u32());
+        let mut encoded = codec.encode(&b"zero\0bytes"[..]).unwrap();
+        encoded.extend(codec.encode(&b""[..]).unwrap());
+        // The Cobs frame, delimiter included, sits behind the length prefix.
+        assert_eq!(&encoded[..4], &[0, 0, 0, 12]);
+
+        let mut decoded = Vec::new();
+        let mut rest = encoded.as_slice();
+        while let DecoderResult::Done(message, used) = Decoder::<Vec<u8>>::decode(&mut codec, rest)
+        {
+            decoded.push(message);
+            rest = &rest[used..];
+        }
+        assert_eq!(decoded, [&b"zero\0bytes"[..], b""]);
+        assert!(rest.is_empty());
+        assert!(matches!(
+            Decoder::<Vec<u8>>::decode(&mut codec, &encoded[..6]),
+            DecoderResult::Continue
+        ));
+    }
+
+    #[test]
+    fn test_with_framing_rejects_partial_inner_frame() {
+        let mut framing = length_u32();
+        // A Slip payload without its closing delimiter.
+        let encoded = framing.encode(vec![0x01, 0x02]).unwrap();
+        let mut codec = DecoderExt::<Vec<u8>>::with_framing(Slip::new(), length_u32());
+        assert!(matches!(codec.decode(&encoded), DecoderResult::Error(_)));
+    }
+
+    #[cfg(feature = "serde-json")]
+    #[test]
+    fn test_json_with_framing() {
+        use crate::codec::JsonCodec;
+
+        let mut codec =
+            EncoderExt::<Vec<u32>>::with_framing(JsonCodec::<Vec<u32>>::new(), length_u32());
+        let encoded = codec.encode(vec![1, 2, 3]).unwrap();
+        assert_eq!(&encoded[4..], b"[1,2,3]\n");
+        match codec.decode(&encoded) {
+            DecoderResult::Done(decoded, used) => {
+                assert_eq!(decoded, [1, 2, 3]);
+                assert_eq!(used, encoded.len());
+            }
+            _ => panic!("Expected a decoded message"),
+        }
+    }
+}
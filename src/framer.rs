@@ -0,0 +1,213 @@
+//! Sans-io message framing, for callers that drive the I/O themselves.
+use alloc::vec::Vec;
+
+use bytes::{Buf, BytesMut};
+
+use crate::{
+    constants::INITIAL_BUFFER_SIZE,
+    decoder::{Decoder, DecoderResult, used_remainder},
+    encoder::Encoder,
+    error::{CodecError, DecodeError},
+};
+
+#[cfg(any(feature = "sync", feature = "async"))]
+pub(crate) mod reader;
+
+/// Frames messages over bytes the caller moves itself, without owning any stream.
+///
+/// Received bytes are pushed in with `push_bytes` in whatever chunks they arrive, and complete
+/// messages are drained with `poll_message`. Outgoing messages are encoded with
+/// `encode_message` and the caller sends the bytes. This suits transports that aren't byte
+/// streams, e.g. datagrams, as well as tests that don't want any `Read` or `Write`.
+pub struct Framer<E, D> {
+    encoder: E,
+    decoder: D,
+    buffer: BytesMut,
+}
+
+impl<E, D> Framer<E, D> {
+    /// Creates a new framer with the given encoder and decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: An encoder that implements the `Encoder` trait.
+    /// * `decoder`: A decoder that implements the `Decoder` trait.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Framer`.
+    pub fn new(encoder: E, decoder: D) -> Self {
+        Self {
+            encoder,
+            decoder,
+            buffer: BytesMut::with_capacity(INITIAL_BUFFER_SIZE),
+        }
+    }
+
+    /// Appends received bytes to the framer's buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The bytes received, any amount and not necessarily whole frames.
+    pub fn push_bytes(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the bytes pushed but not yet decoded.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Decodes the next message from the pushed bytes.
+    ///
+    /// Call it repeatedly after every `push_bytes` until it returns `Ok(None)`, more than one
+    /// frame may have arrived at once.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// The decoded message, `Ok(None)` if the buffered bytes hold no complete frame yet, or the
+    /// decoder's error. Errors reported with an offset are wrapped in a `DecodeError`.
+    pub fn poll_message<M>(&mut self) -> Result<Option<M>, CodecError>
+    where
+        D: Decoder<M>,
+    {
+        while !self.buffer.is_empty() && self.buffer.len() >= self.decoder.min_frame_hint() {
+            match decode_step(&mut self.decoder, &mut self.buffer, true, D::decode_bytes)? {
+                DecoderResult::Continue | DecoderResult::NeedMore(_) => break,
+                DecoderResult::ContinueFrom(_) => {}
+                DecoderResult::Done(msg, _) => return Ok(Some(msg)),
+                DecoderResult::Error(e) => return Err(CodecError::new(e)),
+                DecoderResult::ErrorAt(e, offset) => {
+                    return Err(CodecError::new(DecodeError::new(offset, e)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Encodes a message into the bytes to send.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg`: The message to encode.
+    ///
+    /// # Returns
+    ///
+    /// The encoded frame, or the encoder's error.
+    pub fn encode_message<M>(&mut self, msg: M) -> Result<Vec<u8>, E::Error>
+    where
+        E: Encoder<M>,
+    {
+        self.encoder.encode(msg)
+    }
+
+    /// Consumes the framer, returning the encoder, the decoder and the bytes not yet decoded.
+    pub fn into_parts(self) -> (E, D, BytesMut) {
+        (self.encoder, self.decoder, self.buffer)
+    }
+}
+
+/// Runs `decode` over `buffer` and drops the bytes it reports used from the front of the
+/// buffer: those taken with `ContinueFrom`, and the frame's on `Done` if `consume` is set.
+///
+/// `decode` may split bytes off the front of the buffer as `decode_bytes` does, they count
+/// toward the bytes it reports used. A decoder breaking the `DecoderResult` contract fails the
+/// step instead of panicking on `advance`. This is the state machine every reader drives, from
+/// `Framer` to the stream handlers and the tokio codec adapter.
+pub(crate) fn decode_step<D, M, Err>(
+    decoder: &mut D,
+    buffer: &mut BytesMut,
+    consume: bool,
+    decode: impl FnOnce(&mut D, &mut BytesMut) -> DecoderResult<M, Err>,
+) -> Result<DecoderResult<M, Err>, &'static str> {
+    let len = buffer.len();
+    let result = decode(decoder, buffer);
+    // Bytes the decoder split off the buffer count toward what it reports as used.
+    let split = len.saturating_sub(buffer.len());
+    match &result {
+        DecoderResult::ContinueFrom(used) => {
+            buffer.advance(used_remainder(*used, split, buffer.len())?);
+        }
+        DecoderResult::Done(_, used) if consume => {
+            buffer.advance(used_remainder(*used, split, buffer.len())?);
+        }
+        DecoderResult::Done(..) => {}
+        _ if split > 0 => return Err("Decoder split off bytes without using them"),
+        _ => {}
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{Cobs, Endian, LengthDelimited, PrefixWidth};
+    use alloc::vec;
+
+    #[test]
+    fn test_arbitrary_chunks() {
+        let codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let mut framer = Framer::new(codec.clone(), codec);
+        let messages: Vec<Vec<u8>> = (0..20u8).map(|i| vec![i; i as usize * 7]).collect();
+        let mut wire = Vec::new();
+        for message in &messages {
+            wire.extend(framer.encode_message(message).unwrap());
+        }
+
+        let mut received = Vec::new();
+        let mut rest = wire.as_slice();
+        // Chunk sizes cycle through 1 to 5 bytes, splitting prefixes and payloads alike.
+        for size in (1..=5).cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at(size.min(rest.len()));
+            rest = tail;
+            framer.push_bytes(chunk);
+            while let Some(message) = framer.poll_message::<Vec<u8>>().unwrap() {
+                received.push(message);
+            }
+        }
+        assert_eq!(received, messages);
+        assert!(framer.buffered().is_empty());
+    }
+
+    #[test]
+    fn test_several_frames_in_one_push() {
+        let mut framer = Framer::new(Cobs::new(), Cobs::new());
+        let mut wire = framer.encode_message(b"one").unwrap();
+        wire.extend(framer.encode_message(b"two").unwrap());
+        wire.extend(framer.encode_message(b"thr").unwrap());
+        framer.push_bytes(&wire[..wire.len() - 2]);
+
+        let first: Option<Vec<u8>> = framer.poll_message().unwrap();
+        assert_eq!(first.as_deref(), Some(&b"one"[..]));
+        let second: Option<Vec<u8>> = framer.poll_message().unwrap();
+        assert_eq!(second.as_deref(), Some(&b"two"[..]));
+        assert_eq!(framer.poll_message::<Vec<u8>>().unwrap(), None);
+
+        framer.push_bytes(&wire[wire.len() - 2..]);
+        let third: Option<Vec<u8>> = framer.poll_message().unwrap();
+        assert_eq!(third.as_deref(), Some(&b"thr"[..]));
+    }
+
+    #[test]
+    fn test_error_keeps_offset() {
+        let mut framer = Framer::new(Cobs::new(), Cobs::new());
+        framer.push_bytes(&[0x02, 0x11, 0x05, 0x22, 0x00]);
+        let err = framer.poll_message::<Vec<u8>>().unwrap_err();
+        let err = err.into_inner();
+        let decode_error = err
+            .downcast_ref::<DecodeError>()
+            .expect("Expected a DecodeError");
+        assert_eq!(decode_error.offset(), 2);
+    }
+}
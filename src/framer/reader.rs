@@ -0,0 +1,503 @@
+//! The read side of the stream handlers, shared by `sync::MessageIo` and
+//! `async::AsyncMessageIo`: the read buffer, the decoder and the bookkeeping of the frame being
+//! decoded. The handlers only move bytes between the stream and the buffer.
+use std::{error::Error, mem::MaybeUninit, ops::Range};
+
+use bytes::{Buf, BufMut, BytesMut};
+
+use super::decode_step;
+use crate::{
+    constants::{BUFFER_SHRINK_FACTOR, MAX_READ_AHEAD, TEMP_BUFFER_SIZE},
+    decoder::{Decoder, DecoderResult, DrainPolicy, MessageGuard, reset_and_resync},
+    error::DecodeError,
+    observer::Observer,
+};
+
+/// Why the buffered bytes can't be decoded, converted to `MsgIoError` by the handlers.
+#[derive(Debug)]
+pub(crate) enum ReadError {
+    /// The decoder failed, or broke the `DecoderResult` contract.
+    Decode(Box<dyn Error + Send + Sync>),
+    /// The frame outgrew the limit set with `with_max_frame_size`.
+    FrameTooLarge { size: usize, max: usize },
+}
+
+/// The read buffer and decoder of a handler, with the settings of its `with_*` methods.
+pub(crate) struct FrameReader<D> {
+    pub(crate) decoder: D,
+    pub(crate) buffer: BytesMut,
+    /// Bytes of the frame in progress the decoder already took with `ContinueFrom`.
+    consumed: usize,
+    /// Buffer length the decoder asked for with `NeedMore`, it isn't called again before.
+    wanted: usize,
+    pub(crate) buffer_capacity: usize,
+    pub(crate) shrink_threshold: Option<usize>,
+    pub(crate) grow_increment: usize,
+    pub(crate) read_chunk_size: usize,
+    pub(crate) max_frame_size: Option<usize>,
+    pub(crate) max_buffered_bytes: Option<usize>,
+    pub(crate) resync: bool,
+    pub(crate) drain_policy: DrainPolicy,
+    pub(crate) message_limit: Option<u64>,
+    pub(crate) bytes_read: u64,
+    pub(crate) messages_read: u64,
+}
+
+impl<D> FrameReader<D> {
+    /// Creates a reader with an empty buffer of `capacity` bytes and default settings.
+    pub(crate) fn new(decoder: D, capacity: usize) -> Self {
+        Self {
+            decoder,
+            buffer: BytesMut::with_capacity(capacity),
+            consumed: 0,
+            wanted: 0,
+            buffer_capacity: capacity,
+            shrink_threshold: None,
+            grow_increment: 0,
+            read_chunk_size: TEMP_BUFFER_SIZE,
+            max_frame_size: None,
+            max_buffered_bytes: None,
+            resync: false,
+            drain_policy: DrainPolicy::KeepRemaining,
+            message_limit: None,
+            bytes_read: 0,
+            messages_read: 0,
+        }
+    }
+
+    /// Reallocates the buffer at `capacity`, keeping the bytes it holds.
+    pub(crate) fn set_buffer_capacity(&mut self, capacity: usize) {
+        let mut buffer = BytesMut::with_capacity(capacity.max(self.buffer.len()));
+        buffer.extend_from_slice(&self.buffer);
+        self.buffer = buffer;
+        self.buffer_capacity = capacity;
+    }
+
+    /// Replaces the decoder, forgetting what the previous one knew of the frame in progress.
+    pub(crate) fn set_decoder(&mut self, decoder: D) {
+        self.decoder = decoder;
+        self.consumed = 0;
+        self.wanted = 0;
+    }
+
+    /// Bytes of the frame in progress the decoder already took with `ContinueFrom`.
+    pub(crate) fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Whether no part of a frame is buffered or held by the decoder, so the end of the stream
+    /// is a clean one.
+    pub(crate) fn at_frame_boundary(&self) -> bool {
+        self.buffer.is_empty() && self.consumed == 0
+    }
+
+    /// Whether the reader has read as many messages as `with_message_limit` allows.
+    pub(crate) fn message_limit_reached(&self) -> bool {
+        self.message_limit
+            .is_some_and(|limit| self.messages_read >= limit)
+    }
+
+    /// Decodes a message from the buffered data alone, `None` meaning the decoder needs more.
+    ///
+    /// Messages to consume are decoded with `decode_bytes`, peeked ones with `decode` so the
+    /// buffer is left untouched. `observer` is the handler's, told of consumed messages and of
+    /// decoder errors.
+    pub(crate) fn decode<M>(
+        &mut self,
+        consume: bool,
+        observer: Option<&dyn Observer>,
+    ) -> Result<Option<M>, ReadError>
+    where
+        D: Decoder<M>,
+    {
+        if consume {
+            self.decode_by(
+                true,
+                observer,
+                D::min_frame_hint,
+                D::decode_bytes,
+                reset_and_resync::<M, D>,
+            )
+        } else {
+            self.decode_by(
+                false,
+                observer,
+                D::min_frame_hint,
+                |decoder, buf| decoder.decode(buf),
+                reset_and_resync::<M, D>,
+            )
+        }
+    }
+
+    /// Like `decode`, with `min_len`, `decode` and `resync` standing in for the decoder's.
+    /// `decode` may split bytes off the front of the buffer as `decode_bytes` does.
+    pub(crate) fn decode_by<M, Err>(
+        &mut self,
+        consume: bool,
+        observer: Option<&dyn Observer>,
+        min_len: impl Fn(&D) -> usize,
+        mut decode: impl FnMut(&mut D, &mut BytesMut) -> DecoderResult<M, Err>,
+        mut resync: impl FnMut(&mut D, &[u8]) -> Option<usize>,
+    ) -> Result<Option<M>, ReadError>
+    where
+        Err: Into<Box<dyn Error + Send + Sync>>,
+    {
+        if self.message_limit_reached() {
+            return Ok(None);
+        }
+        while !self.buffer.is_empty() {
+            let capacity = self.buffer.capacity();
+            let result = if self.buffer.len() < min_len(&self.decoder).max(self.wanted) {
+                DecoderResult::Continue
+            } else {
+                decode_step(&mut self.decoder, &mut self.buffer, consume, &mut decode)
+                    .map_err(|e| ReadError::Decode(e.into()))?
+            };
+            match result {
+                DecoderResult::Continue => {}
+                DecoderResult::ContinueFrom(skip) => {
+                    self.consumed += skip;
+                    self.wanted = 0;
+                }
+                DecoderResult::NeedMore(len) => {
+                    self.wanted = self.buffer.len().saturating_add(len);
+                }
+                DecoderResult::Done(msg, _) if !consume => return Ok(Some(msg)),
+                DecoderResult::Done(msg, used) => {
+                    if self.drain_policy == DrainPolicy::DiscardRemaining {
+                        self.buffer.clear();
+                    }
+                    if let Some(observer) = observer {
+                        observer.on_message_read(self.consumed + used);
+                    }
+                    self.consumed = 0;
+                    self.wanted = 0;
+                    self.messages_read += 1;
+                    self.shrink_buffer(capacity);
+                    return Ok(Some(msg));
+                }
+                DecoderResult::Error(e) => {
+                    let e = observe_decode_error(observer, e);
+                    if self.resync_buffer(&mut resync) {
+                        continue;
+                    }
+                    return Err(ReadError::Decode(e));
+                }
+                DecoderResult::ErrorAt(e, offset) => {
+                    let e = observe_decode_error(observer, e);
+                    if self.resync_buffer(&mut resync) {
+                        continue;
+                    }
+                    let e = DecodeError::new(self.consumed + offset, e);
+                    return Err(ReadError::Decode(Box::new(e)));
+                }
+            }
+            let size = self.consumed + self.buffer.len().max(self.wanted);
+            if let Some(max) = self.max_frame_size
+                && size > max
+            {
+                return Err(ReadError::FrameTooLarge { size, max });
+            }
+            break;
+        }
+        Ok(None)
+    }
+
+    /// Counts the frame the buffer starts with as read and wraps it in a `MessageGuard`.
+    pub(crate) fn message_guard(
+        &mut self,
+        payload: Range<usize>,
+        used: usize,
+        observer: Option<&dyn Observer>,
+    ) -> Result<MessageGuard<'_>, ReadError> {
+        if self.drain_policy == DrainPolicy::DiscardRemaining {
+            self.buffer.truncate(used);
+        }
+        let guard = MessageGuard::new(&mut self.buffer, payload, used)
+            .map_err(|e| ReadError::Decode(e.into()))?;
+        if let Some(observer) = observer {
+            observer.on_message_read(self.consumed + used);
+        }
+        self.consumed = 0;
+        self.wanted = 0;
+        self.messages_read += 1;
+        Ok(guard)
+    }
+
+    /// Drops the buffered bytes before the next frame boundary found by `resync`, if the reader
+    /// resynchronizes after errors. Returns whether decoding can carry on.
+    fn resync_buffer(&mut self, resync: impl FnOnce(&mut D, &[u8]) -> Option<usize>) -> bool {
+        if self.resync
+            && let Some(skip) = resync(&mut self.decoder, &self.buffer)
+            && (1..=self.buffer.len()).contains(&skip)
+        {
+            self.buffer.advance(skip);
+            self.consumed = 0;
+            self.wanted = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Reallocates the buffer at its configured capacity if it grew past the shrink threshold,
+    /// `capacity` being the buffer's capacity before the decoded frame was dropped.
+    fn shrink_buffer(&mut self, capacity: usize) {
+        let threshold = self
+            .shrink_threshold
+            .unwrap_or(self.buffer_capacity.saturating_mul(BUFFER_SHRINK_FACTOR));
+        if capacity > threshold && self.buffer.len() <= self.buffer_capacity {
+            let mut buffer = BytesMut::with_capacity(self.buffer_capacity);
+            buffer.extend_from_slice(&self.buffer);
+            self.buffer = buffer;
+        }
+    }
+
+    /// Returns how many bytes the next read may request, `read_chunk_size` or the rest of what
+    /// the decoder asked for with `NeedMore` if that is more, shortened to the room left under
+    /// `max_buffered_bytes`.
+    ///
+    /// `NeedMore` is only a hint: what it adds is capped at `MAX_READ_AHEAD` and at the room left
+    /// under `max_frame_size`, so a hostile length prefix can't make the reader reserve, and
+    /// zero, gigabytes up front.
+    fn read_len(&self) -> usize {
+        let mut ahead = self
+            .wanted
+            .saturating_sub(self.buffer.len())
+            .min(MAX_READ_AHEAD);
+        if let Some(max) = self.max_frame_size {
+            ahead = ahead.min(max.saturating_sub(self.consumed + self.buffer.len()));
+        }
+        let len = self.read_chunk_size.max(ahead);
+        match self.max_buffered_bytes {
+            Some(max) if self.buffer.len() < max => len.min(max - self.buffer.len()),
+            _ => len,
+        }
+    }
+
+    /// Reserves room for the next read from the stream and returns it, the start of the
+    /// buffer's spare capacity.
+    pub(crate) fn read_spare(&mut self) -> &mut [MaybeUninit<u8>] {
+        let len = self.read_len();
+        if self.buffer.capacity() - self.buffer.len() < len {
+            self.buffer.reserve(len.max(self.grow_increment));
+        }
+        &mut self.buffer.spare_capacity_mut()[..len]
+    }
+
+    /// Appends the `n` bytes a read filled at the start of `read_spare` to the buffer.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` bytes of the spare capacity must be initialized, `n` no more than the
+    /// length of the slice `read_spare` returned.
+    pub(crate) unsafe fn advance_read(&mut self, n: usize) {
+        // SAFETY: the caller guarantees the bytes are initialized.
+        unsafe { self.buffer.advance_mut(n) };
+        self.bytes_read += n as u64;
+    }
+}
+
+/// Reports a decoder error to the observer, if any.
+fn observe_decode_error(
+    observer: Option<&dyn Observer>,
+    error: impl Into<Box<dyn Error + Send + Sync>>,
+) -> Box<dyn Error + Send + Sync> {
+    let error = error.into();
+    if let Some(observer) = observer {
+        observer.on_decode_error(&error.to_string());
+    }
+    error
+}
+
+/// Defines the `with_*` methods configuring the read side of a handler keeping its
+/// `FrameReader` in a `reader` field.
+macro_rules! reader_settings {
+    () => {
+        /// Sets the initial capacity of the read buffer.
+        ///
+        /// The buffer still grows as needed, a capacity matching the expected frame size avoids
+        /// reallocating while a large frame arrives. Defaults to `INITIAL_BUFFER_SIZE`.
+        ///
+        /// # Arguments
+        ///
+        /// * `capacity`: The capacity in bytes.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the capacity applied.
+        pub fn with_buffer_capacity(mut self, capacity: usize) -> Self {
+            self.reader.set_buffer_capacity(capacity);
+            self
+        }
+
+        /// Sets the read buffer capacity above which the buffer is reallocated after a frame.
+        ///
+        /// Decoding a frame doesn't free the memory it occupied, so after one huge frame the
+        /// buffer would keep its allocation for good. Once a frame is decoded from a buffer whose
+        /// capacity exceeded this threshold, and what remains fits the capacity set with
+        /// `with_buffer_capacity`, the buffer is reallocated at that capacity. Defaults to
+        /// `BUFFER_SHRINK_FACTOR` times that capacity.
+        ///
+        /// # Arguments
+        ///
+        /// * `shrink_threshold`: The threshold in bytes, `usize::MAX` never reallocates.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the threshold applied.
+        pub fn with_shrink_threshold(mut self, shrink_threshold: usize) -> Self {
+            self.reader.shrink_threshold = Some(shrink_threshold);
+            self
+        }
+
+        /// Sets the minimum number of bytes the read buffer grows by when a read doesn't fit.
+        ///
+        /// By default the buffer reserves just the room of the next read, so a large frame read
+        /// in `read_chunk_size` chunks by a decoder that can't announce its length with `NeedMore`
+        /// may reallocate the buffer many times. Reserving in steps of at least `grow_increment`
+        /// bounds that to one reallocation per step. Defaults to `0`.
+        ///
+        /// # Arguments
+        ///
+        /// * `grow_increment`: The step in bytes.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the step applied.
+        pub fn with_grow_increment(mut self, grow_increment: usize) -> Self {
+            self.reader.grow_increment = grow_increment;
+            self
+        }
+
+        /// Sets the maximum number of bytes requested from the stream per read.
+        ///
+        /// Defaults to `TEMP_BUFFER_SIZE`.
+        ///
+        /// # Arguments
+        ///
+        /// * `read_chunk_size`: The read size in bytes.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the read size applied.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `read_chunk_size` is zero.
+        pub fn with_read_chunk_size(mut self, read_chunk_size: usize) -> Self {
+            assert!(read_chunk_size > 0, "Read chunk size must be non-zero");
+            self.reader.read_chunk_size = read_chunk_size;
+            self
+        }
+
+        /// Sets the maximum number of bytes a single frame may occupy.
+        ///
+        /// Once the internal buffer grows past this limit without the decoder producing a
+        /// message, `read_message` fails with `io::ErrorKind::InvalidData`. By default there is no
+        /// limit.
+        ///
+        /// # Arguments
+        ///
+        /// * `max_frame_size`: The maximum frame size in bytes.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the limit applied.
+        pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+            self.reader.max_frame_size = Some(max_frame_size);
+            self
+        }
+
+        /// Caps how much undelivered data the read buffer may hold.
+        ///
+        /// The stream is only read when no complete frame is buffered, and each read is shortened
+        /// so the buffer stays within this many bytes. A slow consumer thus leaves pipelined frames
+        /// in the stream, applying backpressure to the peer, and reading resumes as frames are
+        /// consumed. A single frame larger than the cap is still read whole, bound those with
+        /// `with_max_frame_size`. By default only `read_chunk_size` bounds each read.
+        ///
+        /// # Arguments
+        ///
+        /// * `max_buffered_bytes`: The maximum number of buffered bytes.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the limit applied.
+        pub fn with_max_buffered_bytes(mut self, max_buffered_bytes: usize) -> Self {
+            self.reader.max_buffered_bytes = Some(max_buffered_bytes);
+            self
+        }
+
+        /// Sets whether the reader skips past frames that fail to decode.
+        ///
+        /// When enabled, a decode error makes the reader call `Decoder::reset`, ask
+        /// `Decoder::resync` for the next frame boundary, drop everything before it, and keep
+        /// reading. The error is only returned if the decoder can't find a boundary. Disabled by
+        /// default.
+        ///
+        /// # Arguments
+        ///
+        /// * `resync`: `true` to resynchronize after decode errors.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the option applied.
+        pub fn with_resync(mut self, resync: bool) -> Self {
+            self.reader.resync = resync;
+            self
+        }
+
+        /// Sets what happens to the bytes buffered past a decoded frame.
+        ///
+        /// Bytes left after a frame normally start the next one. With
+        /// `DrainPolicy::DiscardRemaining` they are dropped once the frame is decoded, giving
+        /// datagram-like inputs, one datagram per read, exactly one frame per datagram. On a byte
+        /// stream a read may return several frames, so it would drop whole frames. Defaults to
+        /// `DrainPolicy::KeepRemaining`.
+        ///
+        /// # Arguments
+        ///
+        /// * `drain_policy`: The policy to apply.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the policy applied.
+        pub fn with_drain_policy(mut self, drain_policy: $crate::decoder::DrainPolicy) -> Self {
+            self.reader.drain_policy = drain_policy;
+            self
+        }
+
+        /// Sets how many messages the reader decodes before it reports the end of the stream, e.g.
+        /// to cap the requests a server takes per connection.
+        ///
+        /// Once `messages_read` reaches the limit, reads return `Ok(None)` as at a clean end of the
+        /// stream, without reading any further. The sync `try_read_message`, whose `Ok(None)` means
+        /// no message yet, returns an error of kind `io::ErrorKind::QuotaExceeded` instead. Bytes
+        /// already buffered past the last message stay available through `into_parts`. Unlimited
+        /// by default.
+        ///
+        /// # Arguments
+        ///
+        /// * `limit`: The number of messages to read.
+        ///
+        /// # Returns
+        ///
+        /// The instance with the limit applied.
+        pub fn with_message_limit(mut self, limit: usize) -> Self {
+            self.reader.message_limit = Some(limit as u64);
+            self
+        }
+
+        /// Number of bytes read from the stream so far.
+        pub fn bytes_read(&self) -> u64 {
+            self.reader.bytes_read
+        }
+
+        /// Number of messages decoded and consumed so far, peeked messages are not counted.
+        pub fn messages_read(&self) -> u64 {
+            self.reader.messages_read
+        }
+    };
+}
+
+pub(crate) use reader_settings;
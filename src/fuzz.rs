@@ -0,0 +1,204 @@
+//! Entry point feeding arbitrary bytes to the built-in decoders, for fuzzing.
+//!
+//! `Decoder::decode` must never panic, whatever the input: on data that doesn't form a valid
+//! frame it returns `Error` or `ErrorAt`, and on data that may still become one it returns
+//! `Continue`, `ContinueFrom` or `NeedMore`. `decode_all` checks that contract for every
+//! built-in codec. The `cargo fuzz` target in `fuzz/` calls it with generated inputs.
+use alloc::vec::Vec;
+
+use crate::{
+    codec::{
+        Checksum, Checksummed, Cobs, DelimiterCodec, Endian, FixedLength, Hdlc, LengthDelimited,
+        LinesCodec, PrefixWidth, Slip, SuffixChecksum, SuffixLength, Tagged, VarintDelimited,
+        WebSocketFrame,
+    },
+    decoder::{Decoder, DecoderResult, RefDecoder, reset_and_resync},
+};
+
+/// Runs every built-in decoder over `data`, panicking only if a decoder panics or breaks the
+/// `DecoderResult` contract, e.g. by reporting more bytes used than it was given.
+///
+/// The first byte picks the codecs' settings, such as the width and byte order of length
+/// prefixes and whether limits are set, so a fuzzer explores those along with the frames.
+///
+/// # Arguments
+///
+/// * `data`: The fuzzer's input.
+pub fn decode_all(data: &[u8]) {
+    let Some((&config, data)) = data.split_first() else {
+        return;
+    };
+    let width = [
+        PrefixWidth::U8,
+        PrefixWidth::U16,
+        PrefixWidth::U32,
+        PrefixWidth::U64,
+    ][usize::from(config & 0x03)];
+    let endian = if config & 0x04 == 0 {
+        Endian::Big
+    } else {
+        Endian::Little
+    };
+    let limited = config & 0x08 != 0;
+    let max_len = usize::from(config >> 4) * 16;
+
+    let mut length = LengthDelimited::new(width, endian);
+    let mut suffix = SuffixLength::new(width, endian);
+    let mut varint = VarintDelimited::new();
+    let mut lines = LinesCodec::new().with_strip_carriage_return(config & 0x10 != 0);
+    let mut websocket = WebSocketFrame::new();
+    if limited {
+        length = length.with_max_len(max_len);
+        suffix = suffix.with_max_len(max_len);
+        varint = varint.with_max_len(max_len);
+        lines = lines.with_max_length(max_len);
+        websocket = websocket.with_max_payload_len(max_len);
+    }
+    let checksum = if config & 0x20 == 0 {
+        Checksum::Crc32
+    } else {
+        Checksum::Crc32c
+    };
+
+    drain_ref(&mut length.clone(), data);
+    drain::<Vec<u8>, _>(&mut length.clone(), data);
+    drain::<Vec<u8>, _>(&mut suffix, data);
+    drain::<Vec<u8>, _>(&mut varint, data);
+    drain_ref(&mut FixedLength::new(usize::from(config >> 4) + 1), data);
+    drain::<Vec<u8>, _>(&mut Cobs::new(), data);
+    drain::<Vec<u8>, _>(&mut Slip::new(), data);
+    drain::<Vec<u8>, _>(&mut Hdlc::new().with_fcs(config & 0x10 != 0), data);
+    drain_ref(
+        &mut DelimiterCodec::new(&b"\r\n"[..]).with_reject_embedded(limited),
+        data,
+    );
+    drain(&mut lines, data);
+    drain(&mut websocket, data);
+    drain::<Vec<u8>, _>(
+        &mut Checksummed::with_checksum(length.clone(), checksum),
+        data,
+    );
+    let mut suffix_checksum = SuffixChecksum::new(checksum);
+    if limited {
+        suffix_checksum = suffix_checksum.with_max_len(max_len);
+    }
+    drain::<(u8, Vec<u8>), _>(&mut suffix_checksum, data);
+    drain(&mut tagged(), data);
+
+    #[cfg(feature = "flate2")]
+    drain::<Vec<u8>, _>(
+        &mut crate::codec::Compressed::new(length.clone()).with_max_decompressed_len(1 << 16),
+        data,
+    );
+    #[cfg(feature = "crypto")]
+    drain::<Vec<u8>, _>(
+        &mut crate::codec::Encrypted::new(
+            length.clone(),
+            [config; 32],
+            crate::codec::Role::Responder,
+        ),
+        data,
+    );
+    #[cfg(feature = "serde-json")]
+    drain(&mut crate::codec::JsonCodec::<Vec<u32>>::new(), data);
+    #[cfg(feature = "bincode")]
+    drain(&mut crate::codec::BincodeCodec::<Vec<u32>>::new(), data);
+    #[cfg(feature = "rmp-serde")]
+    drain(&mut crate::codec::MessagePackCodec::<Vec<u32>>::new(), data);
+    #[cfg(feature = "prost")]
+    drain(&mut crate::codec::ProstCodec::<Vec<u8>>::new(), data);
+}
+
+/// A tagged codec of byte payloads with two registered tags.
+fn tagged() -> Tagged<Vec<u8>> {
+    Tagged::new(|payload: &Vec<u8>| Ok((1, payload.clone())))
+        .with_tag(1, |body| Ok(body.to_vec()))
+        .with_tag(2, |body| match body {
+            [] => Err("Empty body".into()),
+            _ => Ok(body.to_vec()),
+        })
+}
+
+/// Decodes frames from `data` until the decoder needs more, skipping past errors like a reader
+/// with resync enabled.
+fn drain<T, D: Decoder<T>>(decoder: &mut D, data: &[u8]) {
+    drain_by(
+        decoder,
+        data,
+        |decoder, data| decoder.decode(data).map(drop),
+        reset_and_resync::<T, D>,
+    );
+}
+
+/// Like `drain`, decoding frames in place with `decode_ref`.
+fn drain_ref<D: RefDecoder>(decoder: &mut D, data: &[u8]) {
+    drain_by(
+        decoder,
+        data,
+        |decoder, data| decoder.decode_ref(data).map(drop),
+        D::resync,
+    );
+}
+
+fn drain_by<D, E>(
+    decoder: &mut D,
+    mut data: &[u8],
+    mut decode: impl FnMut(&mut D, &[u8]) -> DecoderResult<(), E>,
+    mut resync: impl FnMut(&mut D, &[u8]) -> Option<usize>,
+) {
+    while !data.is_empty() {
+        let skip = match decode(decoder, data) {
+            DecoderResult::Done((), used) => used,
+            DecoderResult::ContinueFrom(skip) => skip,
+            DecoderResult::Continue | DecoderResult::NeedMore(_) => return,
+            DecoderResult::Error(_) => resync(decoder, data).unwrap_or(0),
+            DecoderResult::ErrorAt(_, offset) => {
+                assert!(
+                    offset <= data.len(),
+                    "Error offset past the end of the data"
+                );
+                resync(decoder, data).unwrap_or(0)
+            }
+        };
+        assert!(
+            skip <= data.len(),
+            "Decoder used more bytes than it was given"
+        );
+        if skip == 0 {
+            return;
+        }
+        data = &data[skip..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `decode_all` over pseudo-random inputs, a stand-in for the fuzzer in plain test
+    /// runs.
+    #[test]
+    fn test_random_inputs() {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..20_000 {
+            let len = (next() % 64) as usize;
+            let input: Vec<u8> = (0..len)
+                .map(|_| match next() % 8 {
+                    // Bias toward bytes that mean something to some framing.
+                    0 => 0x00,
+                    1 => 0x7E,
+                    2 => 0xC0,
+                    3 => 0xFF,
+                    _ => next() as u8,
+                })
+                .collect();
+            decode_all(&input);
+        }
+    }
+}
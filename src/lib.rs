@@ -2,44 +2,87 @@
 //!
 //! It supports both sync and async I/O models via feature flags, though my main intention is for async.
 //! The core traits `Encoder` and `Decoder` allow the user to implement the logic for decoding their messages.
+//! Ready-made codecs for common framings live in the `codec` module, and the `ext` module
+//! composes codecs with `map_input`, `map_output` and `with_framing`.
+//! `framer::Framer` runs them without any stream, for callers that move the bytes themselves.
 //!
 //! # Features
 //!
+//! - `std` (default): Links the standard library, required by every I/O feature below. Without
+//!   it the crate is `no_std` and only needs `alloc`, keeping the `Encoder` and `Decoder` traits
+//!   and the framing codecs that are pure byte manipulation.
+//! - `no_std`: Marks a build without `std`, e.g. `--no-default-features --features no_std`.
 //! - `sync`: Enables synchronous I/O support using standard library traits.
 //! - `async` (default): Enables asynchronous I/O support using `futures` traits.
 //! - `tokio`: Enables integration with the `tokio` runtime and its I/O traits.
+//! - `tls`: Enables `tokio::MessageTokio::with_tls` for connections of `tokio-rustls`.
+//! - `testing`: Enables the `testing` module, in-memory duplex streams for codec tests, and
+//!   `codec::assert_roundtrip`.
+//! - `fuzzing`: Enables the `fuzz` module, the entry point of the `cargo fuzz` target in `fuzz/`.
+//! - `serde-json`: Enables the JSON codec, `codec::JsonCodec`.
+//! - `bincode`: Enables the bincode codec, `codec::BincodeCodec`.
+//! - `prost`: Enables the protobuf codec, `codec::ProstCodec`.
+//! - `rmp-serde`: Enables the MessagePack codec, `codec::MessagePackCodec`.
+//! - `flate2`: Enables per-frame deflate compression, `codec::Compressed`.
+//! - `crypto`: Enables per-frame ChaCha20-Poly1305 encryption, `codec::Encrypted`.
 //!
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 #[cfg(feature = "async")]
 pub mod r#async;
+pub mod codec;
 pub mod constants;
 pub mod decoder;
 pub mod encoder;
+pub mod error;
+pub mod ext;
+pub mod framer;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+pub mod observer;
 #[cfg(feature = "sync")]
 pub mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
 #[cfg(feature = "tokio")]
 pub mod tokio;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
+    #[cfg(feature = "async")]
+    use super::r#async;
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    use super::codec;
+    #[cfg(feature = "sync")]
+    use super::constants;
     #[cfg(feature = "sync")]
     use super::sync;
     #[cfg(feature = "tokio")]
     use super::tokio as tokio_crate;
-    use super::{decoder, encoder};
+    use super::{decoder, encoder, error};
+    use std::convert::Infallible;
 
+    #[cfg(any(feature = "sync", feature = "tokio"))]
     struct RawEncoder;
+    #[cfg(any(feature = "sync", feature = "tokio"))]
     impl encoder::Encoder<&Vec<u8>> for RawEncoder {
-        fn encode(&mut self, data: &Vec<u8>) -> Result<Vec<u8>, String> {
+        type Error = Infallible;
+
+        fn encode(&mut self, data: &Vec<u8>) -> Result<Vec<u8>, Infallible> {
             Ok(data.clone())
         }
     }
 
     struct Uint16FramedEncoder;
     impl encoder::Encoder<&Vec<u8>> for Uint16FramedEncoder {
-        fn encode(&mut self, data: &Vec<u8>) -> Result<Vec<u8>, String> {
+        type Error = error::CodecError;
+
+        fn encode(&mut self, data: &Vec<u8>) -> Result<Vec<u8>, error::CodecError> {
             let len = data.len();
             if len > u16::MAX as usize {
-                return Err("Data too large to encode".to_string());
+                return Err("Data too large to encode".into());
             }
             let mut encoded = Vec::with_capacity(2 + len);
             encoded.extend_from_slice(&(len as u16).to_be_bytes());
@@ -47,15 +90,19 @@ mod tests {
             Ok(encoded)
         }
     }
+    #[cfg(any(feature = "sync", feature = "async"))]
     struct Uint16FramedDecoder;
+    #[cfg(any(feature = "sync", feature = "async"))]
     impl decoder::Decoder<Vec<u8>> for Uint16FramedDecoder {
-        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<Vec<u8>> {
+        type Error = Infallible;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<Vec<u8>, Infallible> {
             match data.len() {
                 len if len >= 2 => {
                     let msg_len = u16::from_be_bytes([data[0], data[1]]) as usize;
                     if let Some(data) = data.get(2..2 + msg_len) {
                         let msg = data.to_vec();
-                        return decoder::DecoderResult::Done(msg, 2 + msg_len);
+                        decoder::DecoderResult::Done(msg, 2 + msg_len)
                     } else {
                         decoder::DecoderResult::Continue
                     }
@@ -65,6 +112,48 @@ mod tests {
         }
     }
 
+    /// A u16 framed decoder that remembers the parsed frame length across `Continue` results.
+    #[cfg(feature = "sync")]
+    struct StatefulFramedDecoder {
+        pending: Option<usize>,
+        header_parses: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+    #[cfg(feature = "sync")]
+    impl decoder::Decoder<Vec<u8>> for StatefulFramedDecoder {
+        type Error = Infallible;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<Vec<u8>, Infallible> {
+            let msg_len = match self.pending {
+                Some(len) => len,
+                None if data.len() >= 2 => {
+                    self.header_parses.set(self.header_parses.get() + 1);
+                    let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+                    self.pending = Some(len);
+                    len
+                }
+                None => return decoder::DecoderResult::Continue,
+            };
+            match data.get(2..2 + msg_len) {
+                Some(payload) => {
+                    self.pending = None;
+                    decoder::DecoderResult::Done(payload.to_vec(), 2 + msg_len)
+                }
+                None => decoder::DecoderResult::Continue,
+            }
+        }
+    }
+
+    /// A reader that hands out at most the given number of bytes per read call.
+    #[cfg(feature = "sync")]
+    struct TrickleReader<R>(R, usize);
+    #[cfg(feature = "sync")]
+    impl<R: std::io::Read> std::io::Read for TrickleReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
     #[cfg(feature = "sync")]
     #[test]
     fn test_sync_message_io() {
@@ -104,13 +193,112 @@ mod tests {
         let incomplete_data = b"\x00\x10hello".to_vec(); // Declares length 16, but only 5 bytes provided
         let _ = writer.write_message(&incomplete_data);
         drop(writer); // Close writer to simulate end of stream
-        let read_result = reader.read_message();
+        let read_result = reader.read_message::<Vec<u8>>();
         assert!(
-            matches!(read_result, Ok(None)),
-            "Expected None for incomplete message"
+            matches!(read_result, Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof),
+            "Expected UnexpectedEof for incomplete message"
+        );
+    }
+
+    #[cfg(feature = "sync")]
+    struct CountingReader<R> {
+        inner: R,
+        reads: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+    #[cfg(feature = "sync")]
+    impl<R: std::io::Read> std::io::Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads.set(self.reads.get() + 1);
+            self.inner.read(buf)
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_buffered_messages() {
+        use encoder::Encoder;
+
+        let mut framed = Uint16FramedEncoder.encode(&b"first".to_vec()).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"second".to_vec()).unwrap());
+
+        let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+        let stream = CountingReader {
+            inner: std::io::Cursor::new(framed),
+            reads: reads.clone(),
+        };
+        let mut reader = sync::MessageIo::new_reader(stream, Uint16FramedDecoder);
+
+        // Both frames arrive in a single read, the second must come from the buffer.
+        let first = reader
+            .read_message::<Vec<u8>>()
+            .expect("Failed to read message");
+        assert_eq!(first, Some(b"first".to_vec()));
+        let second = reader
+            .read_message::<Vec<u8>>()
+            .expect("Failed to read message");
+        assert_eq!(second, Some(b"second".to_vec()));
+        assert_eq!(reads.get(), 1, "Expected a single underlying read");
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_read_messages() {
+        use encoder::Encoder;
+
+        let mut framed = Vec::new();
+        for i in 0..5u8 {
+            framed.extend(Uint16FramedEncoder.encode(&vec![i; 3]).unwrap());
+        }
+        let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+        let stream = CountingReader {
+            inner: std::io::Cursor::new(framed),
+            reads: reads.clone(),
+        };
+        let mut reader = sync::MessageIo::new_reader(stream, Uint16FramedDecoder);
+
+        let mut out = vec![b"earlier".to_vec()];
+        let count = reader
+            .read_messages(&mut out)
+            .expect("Failed to read messages");
+        assert_eq!(count, 5);
+        assert_eq!(out.len(), 6);
+        assert_eq!(out[1..], (0..5u8).map(|i| vec![i; 3]).collect::<Vec<_>>());
+        assert_eq!(reads.get(), 1, "Expected a single underlying read");
+        assert_eq!(reader.messages_read(), 5);
+
+        assert_eq!(
+            reader
+                .read_messages(&mut out)
+                .expect("Failed to read messages"),
+            0
         );
     }
 
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_read_messages() {
+        use encoder::Encoder;
+        use tokio::io::AsyncWriteExt;
+
+        let (rx, mut tx) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let mut framed = Vec::new();
+        for i in 0..5u8 {
+            framed.extend(Uint16FramedEncoder.encode(&vec![i; 3]).unwrap());
+        }
+        tx.write_all(&framed).await.expect("Failed to write burst");
+
+        let mut out = Vec::new();
+        let count = reader
+            .read_messages(&mut out)
+            .await
+            .expect("Failed to read messages");
+        assert_eq!(count, 5);
+        assert_eq!(out, (0..5u8).map(|i| vec![i; 3]).collect::<Vec<_>>());
+        assert_eq!(reader.bytes_read(), framed.len() as u64);
+    }
+
     #[cfg(feature = "tokio")]
     #[tokio::test]
     async fn test_async_message_io() {
@@ -145,8 +333,3569 @@ mod tests {
         drop(writer); // Close writer to simulate end of stream
         let read_result = reader.read_message::<Vec<u8>>().await;
         assert!(
-            matches!(read_result, Ok(None)),
-            "Expected None for incomplete message"
+            matches!(read_result, Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof),
+            "Expected UnexpectedEof for incomplete message"
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_buffered_messages() {
+        use encoder::Encoder;
+
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, RawEncoder);
+
+        // Write both frames with a single write_all, then close the stream.
+        let mut framed = Uint16FramedEncoder.encode(&b"first".to_vec()).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"second".to_vec()).unwrap());
+        writer
+            .write_message(&framed)
+            .await
+            .expect("Failed to write message");
+        drop(writer);
+
+        let first = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(first, Some(b"first".to_vec()));
+        let second = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(second, Some(b"second".to_vec()));
+        let end = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert!(end.is_none(), "Expected end of stream");
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_stateful_decoder() {
+        use encoder::Encoder;
+
+        let mut framed = Uint16FramedEncoder.encode(&b"hello".to_vec()).unwrap();
+        framed.extend(
+            Uint16FramedEncoder
+                .encode(&b"stateful world".to_vec())
+                .unwrap(),
+        );
+
+        let header_parses = std::rc::Rc::new(std::cell::Cell::new(0));
+        let decoder = StatefulFramedDecoder {
+            pending: None,
+            header_parses: header_parses.clone(),
+        };
+        let stream = TrickleReader(std::io::Cursor::new(framed), 1);
+        let mut reader = sync::MessageIo::new_reader(stream, decoder);
+
+        let first = reader
+            .read_message::<Vec<u8>>()
+            .expect("Failed to read message");
+        assert_eq!(first, Some(b"hello".to_vec()));
+        let second = reader
+            .read_message::<Vec<u8>>()
+            .expect("Failed to read message");
+        assert_eq!(second, Some(b"stateful world".to_vec()));
+
+        // Each header is parsed once, even though the body trickled in over many reads.
+        assert_eq!(header_parses.get(), 2);
+    }
+
+    struct FrameA(u8);
+    struct FrameB(u16);
+    struct MultiFrameEncoder;
+    impl encoder::Encoder<FrameA> for MultiFrameEncoder {
+        type Error = Infallible;
+
+        fn encode(&mut self, data: FrameA) -> Result<Vec<u8>, Infallible> {
+            Ok(vec![b'A', data.0])
+        }
+    }
+    impl encoder::Encoder<FrameB> for MultiFrameEncoder {
+        type Error = Infallible;
+
+        fn encode(&mut self, data: FrameB) -> Result<Vec<u8>, Infallible> {
+            let mut encoded = vec![b'B'];
+            encoded.extend_from_slice(&data.0.to_be_bytes());
+            Ok(encoded)
+        }
+    }
+
+    #[test]
+    fn test_encoder_multiple_message_types() {
+        use encoder::Encoder;
+
+        // A single encoder type can serve several message types.
+        let mut encoder = MultiFrameEncoder;
+        assert_eq!(encoder.encode(FrameA(7)), Ok(vec![b'A', 7]));
+        assert_eq!(encoder.encode(FrameB(0x0102)), Ok(vec![b'B', 1, 2]));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_max_frame_size() {
+        // A length prefix claiming ~4 GiB, followed by a steady stream of junk.
+        let mut bogus = vec![0xFF, 0xFF, 0xFF, 0xFF];
+        bogus.extend(vec![0xAB; 8192]);
+        let decoder = codec::LengthDelimited::new(codec::PrefixWidth::U32, codec::Endian::Big);
+        let mut reader = sync::MessageIo::new_reader(std::io::Cursor::new(bogus), decoder)
+            .with_max_frame_size(4096);
+
+        let err = reader
+            .read_message::<Vec<u8>>()
+            .expect_err("Expected the frame size limit to trigger");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_read_chunk_size() {
+        use encoder::Encoder;
+
+        let mut codec = codec::LengthDelimited::new(codec::PrefixWidth::U32, codec::Endian::Big);
+        let payload = vec![0x5A; 256 * 1024];
+        let framed = codec.encode(&payload).unwrap();
+
+        let read_frame = |chunk: Option<usize>| {
+            let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+            let stream = CountingReader {
+                inner: std::io::Cursor::new(framed.clone()),
+                reads: reads.clone(),
+            };
+            let mut reader = sync::MessageIo::new_reader(stream, codec.clone())
+                .with_buffer_capacity(framed.len());
+            if let Some(chunk) = chunk {
+                reader = reader.with_read_chunk_size(chunk);
+            }
+            let message = reader
+                .read_message::<Vec<u8>>()
+                .expect("Failed to read message");
+            assert_eq!(message.as_ref(), Some(&payload));
+            reads.get()
+        };
+
+        let default_reads = read_frame(None);
+        let large_reads = read_frame(Some(64 * 1024));
+        assert!(default_reads > framed.len() / constants::TEMP_BUFFER_SIZE);
+        assert_eq!(large_reads, framed.len().div_ceil(64 * 1024));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_large_frame_single_read() {
+        use encoder::Encoder;
+
+        let mut codec = codec::LengthDelimited::new(codec::PrefixWidth::U32, codec::Endian::Big);
+        let payload: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+        let framed = codec.encode(&payload).unwrap();
+
+        let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+        let stream = CountingReader {
+            inner: std::io::Cursor::new(framed.clone()),
+            reads: reads.clone(),
+        };
+        let mut reader =
+            sync::MessageIo::new_reader(stream, codec).with_read_chunk_size(framed.len());
+        let message = reader
+            .read_message::<Vec<u8>>()
+            .expect("Failed to read message");
+        assert_eq!(message, Some(payload));
+        assert_eq!(reads.get(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_max_frame_size() {
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let decoder = codec::LengthDelimited::new(codec::PrefixWidth::U32, codec::Endian::Big);
+        let mut reader =
+            tokio_crate::MessageTokio::new_reader(rx, decoder).with_max_frame_size(4096);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, RawEncoder);
+
+        let mut bogus = vec![0xFF, 0xFF, 0xFF, 0xFF];
+        bogus.extend(vec![0xAB; 8192]);
+        writer
+            .write_message(&bogus)
+            .await
+            .expect("Failed to write message");
+
+        let err = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect_err("Expected the frame size limit to trigger");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_builder() {
+        use tokio_util::compat::TokioAsyncReadCompatExt;
+
+        let builder = r#async::AsyncMessageIoBuilder::new()
+            .initial_capacity(64 * 1024)
+            .max_frame_size(4096)
+            .read_chunk_size(512);
+        let decoder = codec::LengthDelimited::new(codec::PrefixWidth::U32, codec::Endian::Big);
+        let (_, buffer) = builder
+            .build_reader(futures_util::io::Cursor::new(Vec::new()), decoder)
+            .into_parts();
+        assert!(buffer.capacity() >= 64 * 1024);
+
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let decoder = codec::LengthDelimited::new(codec::PrefixWidth::U32, codec::Endian::Big);
+        let mut reader = builder.build_reader(rx.compat(), decoder);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, RawEncoder);
+
+        let mut frame = 100u32.to_be_bytes().to_vec();
+        frame.extend(vec![0x11; 100]);
+        let mut bogus = vec![0xFF, 0xFF, 0xFF, 0xFF];
+        bogus.extend(vec![0xAB; 8192]);
+        frame.extend(bogus);
+        writer
+            .write_message(&frame)
+            .await
+            .expect("Failed to write message");
+
+        let received = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(vec![0x11; 100]));
+        let err = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect_err("Expected the frame size limit to trigger");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum TaggedError {
+        BadTag(u8),
+        Empty,
+    }
+    impl std::fmt::Display for TaggedError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::BadTag(tag) => write!(f, "Unexpected tag {tag:#04x}"),
+                Self::Empty => write!(f, "Refusing to encode an empty message"),
+            }
+        }
+    }
+    impl std::error::Error for TaggedError {}
+
+    /// Single byte messages that must start with `b'T'`, with its own error type.
+    struct TaggedCodec;
+    impl encoder::Encoder<&[u8]> for TaggedCodec {
+        type Error = TaggedError;
+
+        fn encode(&mut self, data: &[u8]) -> Result<Vec<u8>, TaggedError> {
+            match data {
+                [] => Err(TaggedError::Empty),
+                [byte, ..] => Ok(vec![b'T', *byte]),
+            }
+        }
+    }
+    impl decoder::Decoder<u8> for TaggedCodec {
+        type Error = TaggedError;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<u8, TaggedError> {
+            match data {
+                [b'T', byte, ..] => decoder::DecoderResult::Done(*byte, 2),
+                [b'T'] => decoder::DecoderResult::Continue,
+                [tag, ..] => decoder::DecoderResult::Error(TaggedError::BadTag(*tag)),
+                [] => decoder::DecoderResult::Continue,
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_error_type() {
+        use decoder::{Decoder, DecoderResult};
+        use encoder::Encoder;
+
+        let mut codec = TaggedCodec;
+        assert_eq!(codec.encode(&[][..]), Err(TaggedError::Empty));
+        match codec.decode(b"X1") {
+            DecoderResult::Error(e) => assert_eq!(e, TaggedError::BadTag(b'X')),
+            _ => panic!("Expected a decode error"),
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_custom_error_type() {
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(b"TaX".to_vec()), TaggedCodec);
+        assert_eq!(
+            reader.read_message::<u8>().expect("Failed to read message"),
+            Some(b'a')
+        );
+        let err = reader
+            .read_message::<u8>()
+            .expect_err("Expected a decode error");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        // The decoder's own error is kept as the error of `MsgIoError::Decode`.
+        assert!(matches!(err, error::MsgIoError::Decode(_)));
+        assert_eq!(
+            err.decode_error::<TaggedError>(),
+            Some(&TaggedError::BadTag(b'X'))
+        );
+        assert_eq!(err.encode_error::<TaggedError>(), None);
+        // The error displays as the decoder's own, so it isn't repeated as the source.
+        assert_eq!(err.to_string(), TaggedError::BadTag(b'X').to_string());
+        assert!(std::error::Error::source(&err).is_none());
+
+        let mut writer = sync::MessageIo::new_writer(Vec::new(), TaggedCodec);
+        let err = writer
+            .write_message(&[][..])
+            .expect_err("Expected an encode error");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(err, error::MsgIoError::Encode(_)));
+        assert_eq!(err.encode_error::<TaggedError>(), Some(&TaggedError::Empty));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_error_variants() {
+        use error::MsgIoError;
+
+        struct ResetStream;
+        impl std::io::Read for ResetStream {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::ConnectionReset.into())
+            }
+        }
+        let mut reader = sync::MessageIo::new_reader(ResetStream, Uint16FramedDecoder);
+        let err = reader.read_message::<Vec<u8>>().unwrap_err();
+        assert!(
+            matches!(&err, MsgIoError::Io(e) if e.kind() == std::io::ErrorKind::ConnectionReset)
+        );
+
+        // A frame of 10 bytes, of which 6 arrived.
+        let partial = b"\x00\x0aabcdef".to_vec();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(partial.clone()), Uint16FramedDecoder)
+                .with_max_frame_size(4);
+        let err = reader.read_message::<Vec<u8>>().unwrap_err();
+        assert!(matches!(err, MsgIoError::FrameTooLarge { size: 8, max: 4 }));
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(partial), Uint16FramedDecoder);
+        let err = reader.read_message::<Vec<u8>>().unwrap_err();
+        assert!(matches!(err, MsgIoError::UnexpectedEof));
+
+        // The variant survives methods returning `io::Error`.
+        let mut reader = sync::MessageIo::new_reader(
+            std::io::Cursor::new(b"\xFF\n".to_vec()),
+            codec::LinesCodec::new(),
+        );
+        let err = reader.read_messages::<String>(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = MsgIoError::from(err);
+        assert!(err.decode_error::<error::CodecError>().is_some());
+        let err = std::io::Error::from(MsgIoError::Io(std::io::ErrorKind::BrokenPipe.into()));
+        assert!(err.get_ref().is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_custom_error_type() {
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, TaggedCodec);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, RawEncoder);
+        writer
+            .write_message(&b"X".to_vec())
+            .await
+            .expect("Failed to write message");
+
+        let err = reader
+            .read_message::<u8>()
+            .await
+            .expect_err("Expected a decode error");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.decode_error::<TaggedError>(),
+            Some(&TaggedError::BadTag(b'X'))
         );
     }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_read_message_timeout() {
+        use encoder::Encoder;
+        use tokio::io::AsyncWriteExt;
+
+        let (rx, mut tx) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let framed = Uint16FramedEncoder.encode(&b"patience".to_vec()).unwrap();
+
+        // Half a frame, then the peer stalls.
+        tx.write_all(&framed[..5])
+            .await
+            .expect("Failed to write chunk");
+        let err = reader
+            .read_message_timeout::<Vec<u8>>(std::time::Duration::from_millis(50))
+            .await
+            .expect_err("Expected the read to time out");
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+        // The buffered half is kept, the rest of the frame completes it.
+        tx.write_all(&framed[5..])
+            .await
+            .expect("Failed to write chunk");
+        let received = reader
+            .read_message_timeout::<Vec<u8>>(std::time::Duration::from_secs(5))
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(b"patience".to_vec()));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_read_message_deadline() {
+        use encoder::Encoder;
+        use std::io::Write;
+        use std::time::{Duration, Instant};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let mut tx = std::net::TcpStream::connect(listener.local_addr().unwrap())
+            .expect("Failed to connect");
+        let (rx, _) = listener.accept().expect("Failed to accept");
+        let mut reader = sync::MessageIo::new_reader(rx, Uint16FramedDecoder);
+        let framed = Uint16FramedEncoder.encode(&b"patience".to_vec()).unwrap();
+
+        // Half a frame, then the peer stalls.
+        tx.write_all(&framed[..5]).expect("Failed to write chunk");
+        let start = Instant::now();
+        let err = reader
+            .read_message_deadline::<Vec<u8>>(start + Duration::from_millis(50))
+            .expect_err("Expected the deadline to pass");
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // The buffered half is kept, the rest of the frame completes it.
+        tx.write_all(&framed[5..]).expect("Failed to write chunk");
+        let received = reader
+            .read_message_deadline::<Vec<u8>>(Instant::now() + Duration::from_secs(5))
+            .expect("Failed to read message");
+        assert_eq!(received, Some(b"patience".to_vec()));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_peek_message() {
+        use encoder::Encoder;
+
+        let mut framed = Uint16FramedEncoder.encode(&b"first".to_vec()).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"second".to_vec()).unwrap());
+        let stream = TrickleReader(std::io::Cursor::new(framed), 1);
+        let mut reader = sync::MessageIo::new_reader(stream, Uint16FramedDecoder);
+
+        // Peeking reads from the stream until the first frame is complete.
+        let peeked = reader.peek_message().expect("Failed to peek message");
+        assert_eq!(peeked, Some(b"first".to_vec()));
+        let peeked = reader.peek_message().expect("Failed to peek message");
+        assert_eq!(peeked, Some(b"first".to_vec()));
+        let read = reader.read_message().expect("Failed to read message");
+        assert_eq!(read, Some(b"first".to_vec()));
+        let read = reader.read_message().expect("Failed to read message");
+        assert_eq!(read, Some(b"second".to_vec()));
+        assert!(matches!(reader.peek_message::<Vec<u8>>(), Ok(None)));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_u24_prefix_split_across_reads() {
+        use encoder::Encoder;
+
+        let mut codec = codec::LengthDelimited::u24_le();
+        let mut framed = codec.encode(vec![0x11; 300]).unwrap();
+        framed.extend(codec.encode(b"next").unwrap());
+        assert_eq!(framed[..3], [0x2C, 0x01, 0x00]);
+        // Every read returns a single byte, so each prefix arrives in three reads.
+        let stream = TrickleReader(std::io::Cursor::new(framed), 1);
+        let mut reader = sync::MessageIo::new_reader(stream, codec);
+
+        let first: Option<Vec<u8>> = reader.read_message().expect("Failed to read message");
+        assert_eq!(first, Some(vec![0x11; 300]));
+        let second: Option<Vec<u8>> = reader.read_message().expect("Failed to read message");
+        assert_eq!(second.as_deref(), Some(&b"next"[..]));
+        assert_eq!(reader.bytes_read(), 310);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_message_limit() {
+        use encoder::Encoder;
+
+        let mut framed = Vec::new();
+        for i in 0..5u8 {
+            framed.extend(Uint16FramedEncoder.encode(&vec![i]).unwrap());
+        }
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(framed), Uint16FramedDecoder)
+                .with_message_limit(3);
+
+        let read: Vec<Vec<u8>> = reader.iter().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(read, [vec![0], vec![1], vec![2]]);
+        assert!(matches!(reader.read_message::<Vec<u8>>(), Ok(None)));
+        assert_eq!(reader.messages_read(), 3);
+        // A limit stop isn't mistaken for a dropped peer by non-blocking reads.
+        let err = reader.try_read_message::<Vec<u8>>().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::QuotaExceeded);
+        // The frames past the limit are left unread.
+        let (_, leftover) = reader.into_parts();
+        assert_eq!(&leftover[..], b"\x00\x01\x03\x00\x01\x04");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_message_limit_stops_batch() {
+        use encoder::Encoder;
+
+        let mut framed = Vec::new();
+        for i in 0..5u8 {
+            framed.extend(Uint16FramedEncoder.encode(&vec![i]).unwrap());
+        }
+        let mut reader = r#async::AsyncMessageIo::new_reader(
+            futures_util::io::Cursor::new(framed),
+            Uint16FramedDecoder,
+        )
+        .with_message_limit(3);
+
+        let mut read: Vec<Vec<u8>> = Vec::new();
+        assert_eq!(reader.read_messages(&mut read).await.unwrap(), 3);
+        assert_eq!(read, [vec![0], vec![1], vec![2]]);
+        assert_eq!(reader.read_messages(&mut read).await.unwrap(), 0);
+        assert!(matches!(reader.read_message::<Vec<u8>>().await, Ok(None)));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_raw_bytes_and_slices() {
+        use bytes::Bytes;
+        use codec::{LengthDelimited, RawDecoder, RawEncoder};
+
+        let mut writer = sync::MessageIo::new_writer::<Bytes>(
+            Vec::new(),
+            RawEncoder::new(LengthDelimited::u32_be()),
+        );
+        writer
+            .write_message(Bytes::from_static(b"from bytes"))
+            .expect("Failed to write message");
+        writer
+            .write_message(&b"from a slice"[..])
+            .expect("Failed to write message");
+
+        let stream = std::io::Cursor::new(writer.into_inner());
+        let mut reader =
+            sync::MessageIo::new_reader(stream, RawDecoder::new(LengthDelimited::u32_be()));
+        let first: Option<Bytes> = reader.read_message().expect("Failed to read message");
+        assert_eq!(first.as_deref(), Some(&b"from bytes"[..]));
+        let second: Option<Bytes> = reader.read_message().expect("Failed to read message");
+        assert_eq!(second.as_deref(), Some(&b"from a slice"[..]));
+        assert!(matches!(reader.read_message::<Bytes>(), Ok(None)));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_has_buffered_message() {
+        use encoder::Encoder;
+
+        // Both frames arrive in the same read.
+        let mut framed = Uint16FramedEncoder.encode(&b"first".to_vec()).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"second".to_vec()).unwrap());
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(framed), Uint16FramedDecoder);
+
+        assert!(!reader.has_buffered_message::<Vec<u8>>().unwrap());
+        let read = reader.read_message().expect("Failed to read message");
+        assert_eq!(read, Some(b"first".to_vec()));
+        assert!(reader.has_buffered_message::<Vec<u8>>().unwrap());
+        // Checking twice doesn't consume the message.
+        assert!(reader.has_buffered_message::<Vec<u8>>().unwrap());
+        let read = reader.read_message().expect("Failed to read message");
+        assert_eq!(read, Some(b"second".to_vec()));
+        assert!(!reader.has_buffered_message::<Vec<u8>>().unwrap());
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_encode_message() {
+        /// Prefixes each message with its sequence number.
+        #[derive(Default)]
+        struct Numbering(u8);
+
+        impl encoder::Encoder<&[u8]> for Numbering {
+            type Error = Infallible;
+
+            fn encode(&mut self, data: &[u8]) -> Result<Vec<u8>, Infallible> {
+                let mut frame = vec![self.0, data.len() as u8];
+                frame.extend_from_slice(data);
+                self.0 += 1;
+                Ok(frame)
+            }
+        }
+
+        let messages: [&[u8]; 2] = [b"first", b"second"];
+        let mut encoding = sync::MessageIo::new_writer(Vec::new(), Numbering::default());
+        let mut writing = sync::MessageIo::new_writer(Vec::new(), Numbering::default());
+        let mut encoded = Vec::new();
+        for message in messages {
+            encoded.extend(encoding.encode_message(message).expect("Failed to encode"));
+            writing.write_message(message).expect("Failed to write");
+        }
+        assert_eq!(&encoded, writing.get_ref());
+        assert!(encoding.get_ref().is_empty());
+        assert_eq!(encoding.messages_written(), 0);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_skip_message() {
+        use encoder::Encoder;
+
+        let mut framed = Uint16FramedEncoder.encode(&b"unknown".to_vec()).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"wanted".to_vec()).unwrap());
+        let stream = TrickleReader(std::io::Cursor::new(framed), 1);
+        let mut reader = sync::MessageIo::new_reader(stream, Uint16FramedDecoder);
+
+        let peeked: Option<Vec<u8>> = reader.peek_message().expect("Failed to peek message");
+        assert_eq!(peeked.as_deref(), Some(&b"unknown"[..]));
+        let skipped = reader
+            .skip_message::<Vec<u8>>()
+            .expect("Failed to skip message");
+        assert_eq!(skipped, Some(9));
+        let read = reader.read_message().expect("Failed to read message");
+        assert_eq!(read, Some(b"wanted".to_vec()));
+        assert!(matches!(reader.skip_message::<Vec<u8>>(), Ok(None)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_skip_message() {
+        use encoder::Encoder;
+
+        let mut framed = Uint16FramedEncoder.encode(&b"unknown".to_vec()).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"wanted".to_vec()).unwrap());
+        let stream =
+            StagedReader([Some(framed[..4].to_vec()), None, Some(framed[4..].to_vec())].into());
+        let mut reader = r#async::AsyncMessageIo::new_reader(stream, Uint16FramedDecoder);
+
+        let skipped = reader
+            .skip_message::<Vec<u8>>()
+            .await
+            .expect("Failed to skip message");
+        assert_eq!(skipped, Some(9));
+        let read: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(read.as_deref(), Some(&b"wanted"[..]));
+        assert!(matches!(reader.skip_message::<Vec<u8>>().await, Ok(None)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_peek_message() {
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, Uint16FramedEncoder);
+        writer
+            .write_message(&b"hello".to_vec())
+            .await
+            .expect("Failed to write message");
+
+        let peeked = reader
+            .peek_message::<Vec<u8>>()
+            .await
+            .expect("Failed to peek message");
+        let read = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(peeked, Some(b"hello".to_vec()));
+        assert_eq!(read, peeked);
+    }
+
+    #[cfg(all(feature = "sync", unix))]
+    #[test]
+    fn test_sync_rw_shutdown() {
+        let (left, right) =
+            std::os::unix::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut left = sync::MessageIo::new_rw::<Vec<u8>>(left, codec::LengthDelimited::u16_be())
+            .with_write_high_water_mark(1024);
+        let mut right = sync::MessageIo::new_rw::<Vec<u8>>(right, codec::LengthDelimited::u16_be());
+
+        right
+            .write_message(b"in flight")
+            .expect("Failed to write message");
+        // Both frames still sit in the write buffer when the shutdown starts.
+        left.write_message(b"one").expect("Failed to write message");
+        left.write_message(b"two").expect("Failed to write message");
+        left.shutdown().expect("Failed to shut down");
+
+        let mut received = Vec::new();
+        while let Some(message) = right.read_message::<Vec<u8>>().expect("Failed to read") {
+            received.push(message);
+        }
+        assert_eq!(received, [b"one".to_vec(), b"two".to_vec()]);
+
+        // Reads on the side that shut down keep working until the peer is done too.
+        right
+            .write_message(b"reply")
+            .expect("Failed to write message");
+        right.shutdown().expect("Failed to shut down");
+        let received: Vec<Vec<u8>> = left.iter().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(received, [b"in flight".to_vec(), b"reply".to_vec()]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_rw_shutdown() {
+        let (left, right) = tokio::io::duplex(1024);
+        let codec = codec::LengthDelimited::u16_be();
+        let mut left = tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(left, codec.clone())
+            .with_write_high_water_mark(1024);
+        let mut right = tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(right, codec);
+
+        left.write_message(b"one")
+            .await
+            .expect("Failed to write message");
+        left.write_message(b"two")
+            .await
+            .expect("Failed to write message");
+        left.shutdown().await.expect("Failed to shut down");
+
+        let mut received = Vec::new();
+        while let Some(message) = right.read_message::<Vec<u8>>().await.unwrap() {
+            received.push(message);
+        }
+        assert_eq!(received, [b"one".to_vec(), b"two".to_vec()]);
+
+        right
+            .write_message(b"reply")
+            .await
+            .expect("Failed to write message");
+        right.shutdown().await.expect("Failed to shut down");
+        let received: Option<Vec<u8>> = left.read_message().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"reply"[..]));
+        assert!(matches!(left.read_message::<Vec<u8>>().await, Ok(None)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_close_write() {
+        let (left, right) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let codec = codec::LengthDelimited::new(codec::PrefixWidth::U16, codec::Endian::Big);
+        let mut left = tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(left, codec.clone());
+        let mut right = tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(right, codec);
+
+        left.write_message(b"last words")
+            .await
+            .expect("Failed to write message");
+        left.close_write().await.expect("Failed to close writer");
+
+        let received = right
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(b"last words".to_vec()));
+        assert!(matches!(right.read_message::<Vec<u8>>().await, Ok(None)));
+
+        // The other direction is still open.
+        right
+            .write_message(b"reply")
+            .await
+            .expect("Failed to write message");
+        let received = left
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(b"reply".to_vec()));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_write_message_len() {
+        let mut writer = sync::MessageIo::new_writer(SharedWriter::default(), Uint16FramedEncoder);
+        let payload = b"twelve bytes".to_vec();
+        let written = writer
+            .write_message(&payload)
+            .expect("Failed to write message");
+        assert_eq!(written, 14);
+        let written = writer
+            .write_message_flush(&b"".to_vec())
+            .expect("Failed to write message");
+        assert_eq!(written, 2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_message_len() {
+        let (_rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, Uint16FramedEncoder);
+        let payload = b"twelve bytes".to_vec();
+        let written = writer
+            .write_message(&payload)
+            .await
+            .expect("Failed to write message");
+        assert_eq!(written, 14);
+        assert_eq!(writer.bytes_written(), 14);
+    }
+
+    /// A writer counting its write calls, appending to a shared buffer.
+    #[cfg(feature = "sync")]
+    #[derive(Clone, Default)]
+    struct CountingWriter {
+        output: SharedWriter,
+        writes: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+    #[cfg(feature = "sync")]
+    impl std::io::Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes.set(self.writes.get() + 1);
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_write_high_water_mark() {
+        let stream = CountingWriter::default();
+        let mut writer = sync::MessageIo::new_writer(stream.clone(), Uint16FramedEncoder)
+            .with_write_high_water_mark(256);
+        for i in 0..100u8 {
+            writer
+                .write_message(&vec![i])
+                .expect("Failed to write message");
+        }
+        // 3 bytes per message, written out every 86 messages.
+        assert_eq!(stream.writes.get(), 1);
+        assert_eq!(writer.bytes_written(), 258);
+        writer.flush().expect("Failed to flush");
+        assert_eq!(stream.writes.get(), 2);
+        assert_eq!(writer.bytes_written(), 300);
+
+        let written = stream.output.0.take();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(written), Uint16FramedDecoder);
+        for i in 0..100u8 {
+            let message = reader
+                .read_message::<Vec<u8>>()
+                .expect("Failed to read message");
+            assert_eq!(message, Some(vec![i]));
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_high_water_mark() {
+        use futures_util::SinkExt;
+
+        let recorder = VectoredRecorder {
+            written: Vec::new(),
+            slice_counts: Vec::new(),
+            limit: usize::MAX,
+        };
+        let mut writer = r#async::AsyncMessageIo::new_writer(recorder, Uint16FramedEncoder)
+            .with_write_high_water_mark(256);
+        let messages: Vec<Vec<u8>> = (0..100u8).map(|i| vec![i]).collect();
+        for message in &messages[..50] {
+            writer
+                .write_message(message)
+                .await
+                .expect("Failed to write message");
+        }
+        for message in &messages[50..] {
+            writer.feed(message).await.expect("Failed to feed message");
+        }
+        assert_eq!(writer.bytes_written(), 258);
+        writer.flush().await.expect("Failed to flush");
+        assert_eq!(writer.bytes_written(), 300);
+
+        let recorder = writer.into_inner();
+        assert_eq!(recorder.slice_counts.len(), 2);
+        let mut reader = r#async::AsyncMessageIo::new_reader(
+            futures_util::io::Cursor::new(recorder.written),
+            Uint16FramedDecoder,
+        );
+        let mut received = Vec::new();
+        reader
+            .read_messages(&mut received)
+            .await
+            .expect("Failed to read messages");
+        assert_eq!(received, messages);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_iter() {
+        let messages = vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()];
+        let output = SharedWriter::default();
+        let mut writer = sync::MessageIo::new_writer(output.clone(), Uint16FramedEncoder);
+        writer
+            .write_messages(&messages)
+            .expect("Failed to write messages");
+
+        let written = output.0.take();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(written), Uint16FramedDecoder);
+        let received = reader
+            .iter::<Vec<u8>>()
+            .collect::<std::io::Result<Vec<_>>>()
+            .expect("Failed to read messages");
+        assert_eq!(received, messages);
+        assert_eq!(reader.messages_read(), 3);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_read_message_with() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+        use encoder::Encoder;
+
+        let mut codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let mut framed = codec.encode(b"a,b,c").unwrap();
+        framed.extend(codec.encode(b"d,e").unwrap());
+        let stream = TrickleReader(std::io::Cursor::new(framed), 1);
+        let mut reader = sync::MessageIo::new_reader(stream, codec);
+
+        let count_fields = |payload: &[u8]| payload.split(|&byte| byte == b',').count();
+        let fields = reader
+            .read_message_with(count_fields)
+            .expect("Failed to read message");
+        assert_eq!(fields, Some(3));
+        let fields = reader
+            .read_message_with(count_fields)
+            .expect("Failed to read message");
+        assert_eq!(fields, Some(2));
+        let fields = reader
+            .read_message_with(count_fields)
+            .expect("Failed to read message");
+        assert_eq!(fields, None);
+        assert_eq!(reader.messages_read(), 2);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_read_message_arena() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+        use encoder::Encoder;
+
+        let mut codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let mut framed = codec.encode(b"first").unwrap();
+        framed.extend(codec.encode(b"second").unwrap());
+        let mut reader = sync::MessageIo::new_reader(std::io::Cursor::new(framed), codec);
+
+        let guard = reader
+            .read_message_arena()
+            .expect("Failed to read message")
+            .expect("Expected a frame");
+        assert_eq!(&*guard, b"first");
+        assert_eq!(guard.frame_len(), 7);
+        // Without the guard's drop the frame stays in the buffer and is read again.
+        std::mem::forget(guard);
+        let guard = reader
+            .read_message_arena()
+            .expect("Failed to read message")
+            .expect("Expected a frame");
+        assert_eq!(&*guard, b"first");
+        drop(guard);
+
+        let guard = reader
+            .read_message_arena()
+            .expect("Failed to read message")
+            .expect("Expected a frame");
+        assert_eq!(&*guard, b"second");
+        drop(guard);
+        assert!(reader.read_message_arena().unwrap().is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_read_message_with() {
+        use codec::{DelimiterCodec, FixedLength};
+        use encoder::Encoder;
+
+        let mut codec = DelimiterCodec::new(b"\n".as_slice());
+        let framed = codec.encode(b"1 2 3 4").unwrap();
+        let mut reader =
+            r#async::AsyncMessageIo::new_reader(futures_util::io::Cursor::new(framed), codec);
+        let words = reader
+            .read_message_with(|payload| payload.split(|&byte| byte == b' ').count())
+            .await
+            .expect("Failed to read message");
+        assert_eq!(words, Some(4));
+
+        let stream = futures_util::io::Cursor::new(b"abcdefgh".to_vec());
+        let mut reader = r#async::AsyncMessageIo::new_reader(stream, FixedLength::new(4));
+        let first = reader
+            .read_message_with(|record| record[0])
+            .await
+            .expect("Failed to read message");
+        assert_eq!(first, Some(b'a'));
+        let first = reader
+            .read_message_with(|record| record[0])
+            .await
+            .expect("Failed to read message");
+        assert_eq!(first, Some(b'e'));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_max_buffered_bytes() {
+        use encoder::Encoder;
+
+        // 100 frames of 10 bytes, all available to the first read.
+        let mut framed = Vec::new();
+        for i in 0..100u8 {
+            framed.extend(Uint16FramedEncoder.encode(&vec![i; 8]).unwrap());
+        }
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(framed), Uint16FramedDecoder)
+                .with_max_buffered_bytes(64);
+        for i in 0..100u8 {
+            let message = reader
+                .read_message::<Vec<u8>>()
+                .expect("Failed to read message");
+            assert_eq!(message, Some(vec![i; 8]));
+            let buffered = reader.bytes_read() - reader.messages_read() * 10;
+            assert!(buffered <= 64, "{buffered} bytes buffered");
+        }
+        assert_eq!(reader.read_message::<Vec<u8>>().unwrap(), None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_max_buffered_bytes() {
+        use encoder::Encoder;
+
+        let mut framed = Vec::new();
+        for i in 0..100u8 {
+            framed.extend(Uint16FramedEncoder.encode(&vec![i; 8]).unwrap());
+        }
+        let mut reader = r#async::AsyncMessageIo::new_reader(
+            futures_util::io::Cursor::new(framed),
+            Uint16FramedDecoder,
+        )
+        .with_max_buffered_bytes(25);
+        for i in 0..100u8 {
+            let message = reader
+                .read_message::<Vec<u8>>()
+                .await
+                .expect("Failed to read message");
+            assert_eq!(message, Some(vec![i; 8]));
+            let buffered = reader.bytes_read() - reader.messages_read() * 10;
+            assert!(buffered <= 25, "{buffered} bytes buffered");
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_decode_error_offset() {
+        let data = b"good\nbad\xFF\n".to_vec();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(data), codec::LinesCodec::new());
+        let line = reader
+            .read_message::<String>()
+            .expect("Failed to read line");
+        assert_eq!(line.as_deref(), Some("good"));
+
+        let err = reader
+            .read_message::<String>()
+            .expect_err("Expected invalid UTF-8 to fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let decode_error = err
+            .decode_error::<error::DecodeError>()
+            .expect("Expected a DecodeError");
+        assert_eq!(decode_error.offset(), 3);
+        // The codec's own error is found inside it.
+        assert!(err.decode_error::<error::CodecError>().is_some());
+        assert!(err.to_string().contains("at offset 3 of the frame"));
+    }
+
+    /// Decodes 8-byte records, counting its `decode` calls.
+    #[cfg(feature = "sync")]
+    struct CountingRecordDecoder {
+        calls: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+    #[cfg(feature = "sync")]
+    impl decoder::Decoder<Vec<u8>> for CountingRecordDecoder {
+        type Error = Infallible;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<Vec<u8>, Infallible> {
+            self.calls.set(self.calls.get() + 1);
+            match data.get(..8) {
+                Some(record) => decoder::DecoderResult::Done(record.to_vec(), 8),
+                None => decoder::DecoderResult::Continue,
+            }
+        }
+
+        fn min_frame_hint(&self) -> usize {
+            8
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_min_frame_hint() {
+        let stream = TrickleReader(std::io::Cursor::new(b"recordA_recordB_".to_vec()), 1);
+        let calls = std::rc::Rc::default();
+        let decoder = CountingRecordDecoder {
+            calls: std::rc::Rc::clone(&calls),
+        };
+        let mut reader = sync::MessageIo::new_reader(stream, decoder);
+        let record = reader.read_message().expect("Failed to read record");
+        assert_eq!(record, Some(b"recordA_".to_vec()));
+        let record = reader.read_message().expect("Failed to read record");
+        assert_eq!(record, Some(b"recordB_".to_vec()));
+        assert_eq!(reader.read_message::<Vec<u8>>().unwrap(), None);
+
+        // One byte arrives per read, yet decode only runs once a whole record is buffered.
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_get_mut_raw_bytes() {
+        use encoder::Encoder;
+        use std::io::Read;
+
+        let mut data = Uint16FramedEncoder.encode(&b"header".to_vec()).unwrap();
+        data.extend_from_slice(b"raw trailer");
+        // Reads stop at the frame boundary, so nothing past the frame is read ahead.
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(data), Uint16FramedDecoder)
+                .with_read_chunk_size(8);
+        let message = reader.read_message().expect("Failed to read message");
+        assert_eq!(message, Some(b"header".to_vec()));
+        assert_eq!(reader.get_ref().position(), 8);
+
+        let mut raw = Vec::new();
+        reader
+            .get_mut()
+            .read_to_end(&mut raw)
+            .expect("Failed to read raw bytes");
+        assert_eq!(raw, b"raw trailer");
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_set_decoder() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+        use encoder::Encoder;
+
+        let mut handshake = LengthDelimited::new(PrefixWidth::U8, Endian::Big);
+        let mut session = LengthDelimited::new(PrefixWidth::U32, Endian::Little);
+        let mut data = handshake.encode(b"upgrade").unwrap();
+        data.extend(session.encode(b"session frame").unwrap());
+
+        // Both frames arrive in one read, the second waits in the buffer for the new decoder.
+        let mut reader = sync::MessageIo::new_reader(std::io::Cursor::new(data), handshake);
+        let message = reader.read_message().expect("Failed to read handshake");
+        assert_eq!(message, Some(b"upgrade".to_vec()));
+        reader.set_decoder(session);
+        let message = reader.read_message().expect("Failed to read session frame");
+        assert_eq!(message, Some(b"session frame".to_vec()));
+        assert_eq!(reader.read_message::<Vec<u8>>().unwrap(), None);
+    }
+
+    /// A reader failing every other read with `Interrupted`, starting with the first.
+    #[cfg(feature = "sync")]
+    struct InterruptingReader<R> {
+        inner: R,
+        interrupt: bool,
+    }
+    #[cfg(feature = "sync")]
+    impl<R: std::io::Read> std::io::Read for InterruptingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_read_retries_interrupted() {
+        use encoder::Encoder;
+
+        let framed = Uint16FramedEncoder
+            .encode(&b"interrupted".to_vec())
+            .unwrap();
+        let stream = InterruptingReader {
+            inner: TrickleReader(std::io::Cursor::new(framed), 1),
+            interrupt: false,
+        };
+        let mut reader = sync::MessageIo::new_reader(stream, Uint16FramedDecoder);
+        let message = reader.read_message().expect("Failed to read message");
+        assert_eq!(message, Some(b"interrupted".to_vec()));
+        assert_eq!(reader.read_message::<Vec<u8>>().unwrap(), None);
+    }
+
+    /// An async stream replaying staged reads, `None` standing for a read that isn't ready.
+    #[cfg(feature = "async")]
+    struct StagedReader(std::collections::VecDeque<Option<Vec<u8>>>);
+    #[cfg(feature = "async")]
+    impl futures_util::AsyncRead for StagedReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            match self.0.pop_front() {
+                Some(Some(chunk)) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    std::task::Poll::Ready(Ok(chunk.len()))
+                }
+                Some(None) => {
+                    cx.waker().wake_by_ref();
+                    std::task::Poll::Pending
+                }
+                None => std::task::Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_poll_read_message() {
+        use encoder::Encoder;
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+
+        let framed = Uint16FramedEncoder.encode(&b"polled".to_vec()).unwrap();
+        let stream = StagedReader(
+            [
+                None,
+                Some(framed[..3].to_vec()),
+                None,
+                Some(framed[3..].to_vec()),
+            ]
+            .into(),
+        );
+        let mut reader = r#async::AsyncMessageIo::new_reader(stream, Uint16FramedDecoder);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut poll = || Pin::new(&mut reader).poll_read_message::<Vec<u8>>(&mut cx);
+        assert!(poll().is_pending());
+        // The first half is buffered before the stream stalls again.
+        assert!(poll().is_pending());
+        match poll() {
+            Poll::Ready(Ok(message)) => assert_eq!(message, Some(b"polled".to_vec())),
+            other => panic!("Expected a message, got {other:?}"),
+        }
+        assert!(matches!(poll(), Poll::Ready(Ok(None))));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_read_message_cancel_safe() {
+        use encoder::Encoder;
+        use std::{
+            future::Future,
+            pin::pin,
+            task::{Context, Waker},
+        };
+
+        let framed = Uint16FramedEncoder
+            .encode(&b"cancelled midway".to_vec())
+            .unwrap();
+        let stream =
+            StagedReader([Some(framed[..5].to_vec()), None, Some(framed[5..].to_vec())].into());
+        let mut reader = r#async::AsyncMessageIo::new_reader(stream, Uint16FramedDecoder);
+
+        {
+            let mut read = pin!(reader.read_message::<Vec<u8>>());
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(read.as_mut().poll(&mut cx).is_pending());
+            // Dropped partway through the frame, like a losing `select!` branch.
+        }
+        assert_eq!(reader.bytes_read(), 5);
+
+        let message: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(message.as_deref(), Some(&b"cancelled midway"[..]));
+        assert_eq!(reader.read_message::<Vec<u8>>().await.unwrap(), None);
+    }
+
+    /// An async writer appending to a shared buffer, taking at most as many bytes per write as
+    /// staged, `None` standing for a write that isn't ready.
+    #[cfg(feature = "tokio")]
+    #[derive(Default)]
+    struct StagedWriter {
+        written: std::rc::Rc<std::cell::RefCell<Vec<u8>>>,
+        stages: std::collections::VecDeque<Option<usize>>,
+    }
+    #[cfg(feature = "tokio")]
+    impl futures_util::AsyncWrite for StagedWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let len = match self.stages.pop_front() {
+                Some(Some(max)) => buf.len().min(max),
+                Some(None) => {
+                    cx.waker().wake_by_ref();
+                    return std::task::Poll::Pending;
+                }
+                None => buf.len(),
+            };
+            self.written.borrow_mut().extend_from_slice(&buf[..len]);
+            std::task::Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_message_resumes_after_cancel() {
+        use std::{
+            future::Future,
+            pin::pin,
+            task::{Context, Waker},
+        };
+
+        let message = b"cancelled midway".to_vec();
+        let stream = StagedWriter {
+            stages: [Some(5), None].into(),
+            ..Default::default()
+        };
+        let written = stream.written.clone();
+        let mut writer = r#async::AsyncMessageIo::new_writer(stream, Uint16FramedEncoder);
+
+        {
+            let mut write = pin!(writer.write_message(&message));
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(write.as_mut().poll(&mut cx).is_pending());
+            // Dropped partway through the frame, like a losing `select!` branch.
+        }
+        assert_eq!(writer.bytes_written(), 5);
+
+        // The next call finishes the interrupted frame, then writes its own message.
+        let len = writer.write_message(&b"next".to_vec()).await.unwrap();
+        assert_eq!(len, 6);
+        assert_eq!(writer.bytes_written(), 24);
+        assert_eq!(writer.messages_written(), 2);
+
+        let written = written.borrow().clone();
+        let mut reader = r#async::AsyncMessageIo::new_reader(
+            futures_util::io::Cursor::new(written),
+            Uint16FramedDecoder,
+        );
+        let read: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(read, Some(message));
+        let read: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(read.as_deref(), Some(&b"next"[..]));
+        assert_eq!(reader.read_message::<Vec<u8>>().await.unwrap(), None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_message_after_error() {
+        let stream = StagedWriter {
+            stages: [Some(3), Some(0)].into(),
+            ..Default::default()
+        };
+        let written = stream.written.clone();
+        let mut writer = r#async::AsyncMessageIo::new_writer(stream, Uint16FramedEncoder);
+
+        let error = writer.write_message(&b"first".to_vec()).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::WriteZero);
+
+        // The failed write must not swallow the next message.
+        let len = writer.write_message(&b"second".to_vec()).await.unwrap();
+        assert_eq!(len, 8);
+        assert_eq!(writer.messages_written(), 2);
+        // The rest of the failed frame goes out first, so the stream still carries whole frames.
+        assert_eq!(&written.borrow()[..], b"\x00\x05first\x00\x06second");
+    }
+
+    /// Decodes frames with a big endian length prefix of the given number of bytes, asking for
+    /// the rest of each body with `NeedMore` once the prefix is read, however long it says it is.
+    #[cfg(feature = "sync")]
+    struct NeedMoreDecoder(usize);
+    #[cfg(feature = "sync")]
+    impl decoder::Decoder<Vec<u8>> for NeedMoreDecoder {
+        type Error = Infallible;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<Vec<u8>, Infallible> {
+            let Some(prefix) = data.get(..self.0) else {
+                return decoder::DecoderResult::Continue;
+            };
+            let len = prefix
+                .iter()
+                .fold(0usize, |len, &byte| (len << 8) | usize::from(byte));
+            let frame_len = self.0 + len;
+            match data.get(self.0..frame_len) {
+                Some(body) => decoder::DecoderResult::Done(body.to_vec(), frame_len),
+                None => decoder::DecoderResult::NeedMore(frame_len - data.len()),
+            }
+        }
+    }
+
+    /// Records the length of every buffer it is asked to fill.
+    #[cfg(feature = "sync")]
+    struct ReadLenRecorder<R> {
+        inner: R,
+        lens: std::rc::Rc<std::cell::RefCell<Vec<usize>>>,
+    }
+    #[cfg(feature = "sync")]
+    impl<R: std::io::Read> std::io::Read for ReadLenRecorder<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.lens.borrow_mut().push(buf.len());
+            self.inner.read(buf)
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_need_more() {
+        use encoder::Encoder;
+
+        let body = vec![0x5a; 1000];
+        let mut framed = Uint16FramedEncoder.encode(&body).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"tail".to_vec()).unwrap());
+
+        let lens = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let stream = ReadLenRecorder {
+            inner: std::io::Cursor::new(framed),
+            lens: lens.clone(),
+        };
+        let mut reader =
+            sync::MessageIo::new_reader(stream, NeedMoreDecoder(2)).with_read_chunk_size(16);
+        let received: Option<Vec<u8>> = reader.read_message().unwrap();
+        assert_eq!(received, Some(body));
+        // The first read parses the prefix, the second asks for the whole rest of the body.
+        assert_eq!(*lens.borrow(), [16, 1002 - 16]);
+
+        let received: Option<Vec<u8>> = reader.read_message().unwrap();
+        assert_eq!(received.as_deref(), Some(&b"tail"[..]));
+        assert_eq!(lens.borrow()[2], 16);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_need_more_exceeds_max_frame_size() {
+        use encoder::Encoder;
+
+        let framed = Uint16FramedEncoder.encode(&vec![0; 1000]).unwrap();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(framed), NeedMoreDecoder(2))
+                .with_read_chunk_size(16)
+                .with_max_frame_size(100);
+        // The announced length is rejected before the body is read.
+        let err = reader.read_message::<Vec<u8>>().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(reader.bytes_read(), 16);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_need_more_hostile_length() {
+        let mut framed = u32::MAX.to_be_bytes().to_vec();
+        framed.extend_from_slice(&[0; 1000]);
+
+        let lens = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let stream = ReadLenRecorder {
+            inner: std::io::Cursor::new(framed),
+            lens: lens.clone(),
+        };
+        let mut reader = sync::MessageIo::new_reader(stream, NeedMoreDecoder(4));
+        let err = reader.read_message::<Vec<u8>>().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        // The 4 GiB announced never turns into a 4 GiB read; the buffer only grows with the
+        // bytes that arrived.
+        assert!(
+            lens.borrow()
+                .iter()
+                .all(|&len| len <= constants::MAX_READ_AHEAD)
+        );
+        let (_, buffer) = reader.into_parts();
+        assert!(buffer.capacity() <= 2 * constants::MAX_READ_AHEAD);
+    }
+
+    /// A stream whose writes never complete, like a peer that stopped reading.
+    #[cfg(feature = "tokio")]
+    struct StalledWriter;
+    #[cfg(feature = "tokio")]
+    impl futures_util::AsyncWrite for StalledWriter {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            _buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Pending
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Pending
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Pending
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_message_timeout() {
+        let mut writer = r#async::AsyncMessageIo::new_writer(StalledWriter, Uint16FramedEncoder);
+        let err = writer
+            .write_message_timeout(&b"stuck".to_vec(), std::time::Duration::from_millis(50))
+            .await
+            .expect_err("Expected the write to time out");
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(writer.bytes_written(), 0);
+    }
+
+    /// Decodes u16 framed payloads as `Bytes`, split off the reader's buffer without a copy.
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    struct BytesFramedDecoder;
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    impl decoder::Decoder<bytes::Bytes> for BytesFramedDecoder {
+        type Error = Infallible;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<bytes::Bytes, Infallible> {
+            Uint16FramedDecoder
+                .decode(data)
+                .map(|payload| bytes::Bytes::copy_from_slice(&payload))
+        }
+
+        fn decode_bytes(
+            &mut self,
+            buf: &mut bytes::BytesMut,
+        ) -> decoder::DecoderResult<bytes::Bytes, Infallible> {
+            use bytes::Buf;
+
+            let Some(prefix) = buf.get(..2) else {
+                return decoder::DecoderResult::Continue;
+            };
+            let len = u16::from_be_bytes([prefix[0], prefix[1]]) as usize;
+            if buf.len() < 2 + len {
+                return decoder::DecoderResult::Continue;
+            }
+            buf.advance(2);
+            decoder::DecoderResult::Done(buf.split_to(len).freeze(), 2 + len)
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_decode_bytes() {
+        use encoder::Encoder;
+
+        let mut framed = Uint16FramedEncoder.encode(&b"first".to_vec()).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"second".to_vec()).unwrap());
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(framed), BytesFramedDecoder);
+        let first: bytes::Bytes = reader.read_message().unwrap().unwrap();
+        let second: bytes::Bytes = reader.read_message().unwrap().unwrap();
+        assert_eq!((&first[..], &second[..]), (&b"first"[..], &b"second"[..]));
+        // Both payloads still point into the read buffer they arrived in, one prefix apart.
+        assert_eq!(
+            second.as_ptr(),
+            first.as_ptr().wrapping_add(first.len() + 2)
+        );
+
+        // Peeking leaves the buffer alone and decodes a copy.
+        let framed = Uint16FramedEncoder.encode(&b"peeked".to_vec()).unwrap();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(framed), BytesFramedDecoder);
+        let peeked: Option<bytes::Bytes> = reader.peek_message().unwrap();
+        let read: Option<bytes::Bytes> = reader.read_message().unwrap();
+        assert_eq!(peeked, read);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_decode_bytes() {
+        use encoder::Encoder;
+
+        let mut framed = Uint16FramedEncoder.encode(&b"first".to_vec()).unwrap();
+        framed.extend(Uint16FramedEncoder.encode(&b"second".to_vec()).unwrap());
+        let mut reader = r#async::AsyncMessageIo::new_reader(
+            futures_util::io::Cursor::new(framed),
+            BytesFramedDecoder,
+        );
+        let first: bytes::Bytes = reader.read_message().await.unwrap().unwrap();
+        let second: bytes::Bytes = reader.read_message().await.unwrap().unwrap();
+        assert_eq!((&first[..], &second[..]), (&b"first"[..], &b"second"[..]));
+        assert_eq!(
+            second.as_ptr(),
+            first.as_ptr().wrapping_add(first.len() + 2)
+        );
+    }
+
+    /// Records every observer callback as a line of text.
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    #[derive(Clone, Default)]
+    struct RecordingObserver(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    impl RecordingObserver {
+        fn events(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    impl super::observer::Observer for RecordingObserver {
+        fn on_message_read(&self, bytes: usize) {
+            self.0.lock().unwrap().push(format!("read {bytes}"));
+        }
+
+        fn on_message_written(&self, bytes: usize) {
+            self.0.lock().unwrap().push(format!("written {bytes}"));
+        }
+
+        fn on_decode_error(&self, error: &str) {
+            self.0.lock().unwrap().push(format!("error {error}"));
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_observer() {
+        let observer = RecordingObserver::default();
+        let mut writer = sync::MessageIo::new_writer::<&[u8]>(Vec::new(), codec::Cobs::new())
+            .with_observer(observer.clone());
+        writer.write_message(b"hello").unwrap();
+        writer.write_messages(&[b"a", b"b"]).unwrap();
+        assert_eq!(observer.events(), ["written 7", "written 3", "written 3"]);
+
+        let mut wire = writer.into_inner();
+        // A code byte pointing past the zero that ends the frame.
+        wire.extend_from_slice(&[0x02, 0x11, 0x05, 0x22, 0x00]);
+        let observer = RecordingObserver::default();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(wire), codec::Cobs::new())
+                .with_observer(observer.clone());
+        for _ in 0..3 {
+            reader.read_message::<Vec<u8>>().unwrap();
+        }
+        assert!(reader.read_message::<Vec<u8>>().is_err());
+        let events = observer.events();
+        assert_eq!(events[..3], ["read 7", "read 3", "read 3"]);
+        assert_eq!(events.len(), 4);
+        assert!(events[3].starts_with("error "), "{events:?}");
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_observer_waits_for_write() {
+        let observer = RecordingObserver::default();
+        let mut writer = sync::MessageIo::new_writer::<&[u8]>(Vec::new(), codec::Cobs::new())
+            .with_write_high_water_mark(64)
+            .with_observer(observer.clone());
+        writer.write_message(b"hello").unwrap();
+        writer.write_messages(&[b"a", b"b"]).unwrap();
+        // Still in the write buffer.
+        assert!(observer.events().is_empty());
+        writer.flush().unwrap();
+        assert_eq!(observer.events(), ["written 7", "written 3", "written 3"]);
+
+        struct BrokenPipe;
+        impl std::io::Write for BrokenPipe {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let observer = RecordingObserver::default();
+        let mut writer = sync::MessageIo::new_writer::<&[u8]>(BrokenPipe, codec::Cobs::new())
+            .with_observer(observer.clone());
+        assert!(writer.write_message(b"hello").is_err());
+        assert!(observer.events().is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_observer_waits_for_write() {
+        let observer = RecordingObserver::default();
+        let (a, b) = ::tokio::net::UnixStream::pair().expect("Failed to create socket pair");
+        let codec = codec::LengthDelimited::new(codec::PrefixWidth::U16, codec::Endian::Big);
+        let mut writer = tokio_crate::MessageTokio::new_writer::<_, _, Vec<u8>>(a, codec.clone())
+            .with_write_high_water_mark(64)
+            .with_observer(observer.clone());
+        let mut peer = tokio_crate::MessageTokio::new_reader::<_, _, Vec<u8>>(b, codec);
+
+        writer.write_message(b"ping".to_vec()).await.unwrap();
+        writer.write_message(Vec::new()).await.unwrap();
+        assert!(observer.events().is_empty());
+        writer.flush().await.unwrap();
+        assert_eq!(observer.events(), ["written 6", "written 2"]);
+        let received: Option<Vec<u8>> = peer.read_message().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"ping"[..]));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_observer_failed_write_then_retry() {
+        let observer = RecordingObserver::default();
+        let stream = StagedWriter {
+            stages: [Some(3), Some(0)].into(),
+            ..Default::default()
+        };
+        let mut writer = r#async::AsyncMessageIo::new_writer(stream, Uint16FramedEncoder)
+            .with_observer(observer.clone());
+
+        writer.write_message(&b"first".to_vec()).await.unwrap_err();
+        assert!(observer.events().is_empty());
+        // The retry finishes the failed frame first, which is reported then.
+        writer.write_message(&b"second".to_vec()).await.unwrap();
+        assert_eq!(observer.events(), ["written 7", "written 8"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_observer_split() {
+        let observer = RecordingObserver::default();
+        let (a, b) = ::tokio::net::UnixStream::pair().expect("Failed to create socket pair");
+        let codec = codec::LengthDelimited::new(codec::PrefixWidth::U16, codec::Endian::Big);
+        let io = tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(a, codec.clone())
+            .with_observer(observer.clone());
+        let mut peer = tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(b, codec);
+        let (mut reader, mut writer) = io.split();
+
+        writer.write_message(b"ping").await.unwrap();
+        let received: Option<Vec<u8>> = peer.read_message().await.unwrap();
+        peer.write_message(received.unwrap()).await.unwrap();
+        let echoed: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(echoed.as_deref(), Some(&b"ping"[..]));
+        assert_eq!(observer.events(), ["written 6", "read 6"]);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_try_read_message() {
+        use encoder::Encoder;
+        use std::io::Write;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let mut tx = std::net::TcpStream::connect(listener.local_addr().unwrap())
+            .expect("Failed to connect");
+        let (rx, _) = listener.accept().expect("Failed to accept");
+        rx.set_nonblocking(true)
+            .expect("Failed to set non-blocking");
+        let mut reader = sync::MessageIo::new_reader(rx, Uint16FramedDecoder);
+
+        // Polls until the reader returns something other than `Ok(None)`.
+        let poll = |reader: &mut sync::MessageIo<_, _, _>| {
+            for _ in 0..500 {
+                match reader.try_read_message::<Vec<u8>>() {
+                    Ok(None) => std::thread::sleep(std::time::Duration::from_millis(2)),
+                    result => return result,
+                }
+            }
+            panic!("No message arrived");
+        };
+
+        let framed = Uint16FramedEncoder
+            .encode(&b"half and half".to_vec())
+            .unwrap();
+        tx.write_all(&framed[..6]).unwrap();
+        assert!(matches!(reader.try_read_message::<Vec<u8>>(), Ok(None)));
+        // Let the first half arrive, it stays buffered while the rest is missing.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(matches!(reader.try_read_message::<Vec<u8>>(), Ok(None)));
+        assert_eq!(reader.bytes_read(), 6);
+
+        tx.write_all(&framed[6..]).unwrap();
+        let received = poll(&mut reader).expect("Failed to read message");
+        assert_eq!(received.as_deref(), Some(&b"half and half"[..]));
+
+        drop(tx);
+        let err = poll(&mut reader).expect_err("Expected the end of the stream");
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    /// Reports using one more byte than it was given, or skipping one more with `ContinueFrom`
+    /// if the data starts with `+`.
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    struct OverreportingDecoder;
+
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    impl decoder::Decoder<Vec<u8>> for OverreportingDecoder {
+        type Error = Infallible;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<Vec<u8>, Infallible> {
+            match data {
+                [b'+', ..] => decoder::DecoderResult::ContinueFrom(data.len() + 1),
+                _ => decoder::DecoderResult::Done(data.to_vec(), data.len() + 1),
+            }
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_decoder_overreporting_used_fails_read() {
+        // Used to panic advancing the read buffer past its end.
+        for data in [&b"frame"[..], b"+frame"] {
+            let mut io = sync::MessageIo::new_reader(data, OverreportingDecoder);
+            let err = io
+                .read_message::<Vec<u8>>()
+                .expect_err("Expected the decoder's used length to be rejected");
+            assert!(matches!(err, error::MsgIoError::Decode(_)));
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), "Decoder used more bytes than are buffered");
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_decoder_overreporting_used_fails_read() {
+        for data in [&b"frame"[..], b"+frame"] {
+            let stream = StagedReader([Some(data.to_vec())].into());
+            let mut reader = r#async::AsyncMessageIo::new_reader(stream, OverreportingDecoder);
+            let err = reader
+                .read_message::<Vec<u8>>()
+                .await
+                .expect_err("Expected the decoder's used length to be rejected");
+            assert!(matches!(err, error::MsgIoError::Decode(_)));
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_max_len_rejects_header_without_body() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+        use std::{
+            io::{self, Write},
+            os::unix::net::UnixStream,
+            time::Duration,
+        };
+
+        let (rx, mut tx) = UnixStream::pair().expect("Failed to create UnixStream pair");
+        // Bounds the test should the reader wait for the body that never comes.
+        rx.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        tx.write_all(&[0x7F, 0xFF, 0xFF, 0xFF]).unwrap();
+
+        let codec = LengthDelimited::new(PrefixWidth::U32, Endian::Big).with_max_len(1024);
+        let mut io = sync::MessageIo::new_reader(rx, codec);
+        let err = io.read_message::<Vec<u8>>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // The writer is still open, nothing but the rejected header was sent.
+        drop(tx);
+    }
+
+    #[cfg(feature = "sync")]
+    mod counting_alloc {
+        use std::{
+            alloc::{GlobalAlloc, Layout, System},
+            cell::Cell,
+        };
+
+        /// Counts the allocations of the current thread, so tests running in parallel don't
+        /// skew each other's counts.
+        struct CountingAlloc;
+
+        thread_local! {
+            static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+        }
+
+        unsafe impl GlobalAlloc for CountingAlloc {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                ALLOCATIONS.with(|count| count.set(count.get() + 1));
+                unsafe { System.alloc(layout) }
+            }
+
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                unsafe { System.dealloc(ptr, layout) }
+            }
+
+            unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+                ALLOCATIONS.with(|count| count.set(count.get() + 1));
+                unsafe { System.realloc(ptr, layout, new_size) }
+            }
+        }
+
+        #[global_allocator]
+        static GLOBAL: CountingAlloc = CountingAlloc;
+
+        pub fn allocations() -> usize {
+            ALLOCATIONS.with(Cell::get)
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_read_message_into_reuses_buffer() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+        use encoder::Encoder;
+
+        let mut codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let mut data = Vec::new();
+        for i in 0..1000u32 {
+            let payload = vec![i as u8; 16 + (i % 48) as usize];
+            data.extend(codec.encode(payload).unwrap());
+        }
+        // The largest payload first, so `out` reaches its final size on the first read.
+        data.splice(0..0, codec.encode(vec![0xAA; 64]).unwrap());
+
+        // Room for a read chunk on top of a partial frame, so the read buffer doesn't grow either.
+        let mut io =
+            sync::MessageIo::new_reader(data.as_slice(), codec).with_buffer_capacity(8 * 1024);
+        let mut out = Vec::new();
+        assert!(io.read_message_into::<Vec<u8>>(&mut out).unwrap());
+        assert_eq!(out, [0xAA; 64]);
+
+        let before = counting_alloc::allocations();
+        for i in 0..1000u32 {
+            assert!(io.read_message_into::<Vec<u8>>(&mut out).unwrap());
+            assert_eq!(out.len(), 16 + (i % 48) as usize);
+            assert!(out.iter().all(|&byte| byte == i as u8));
+        }
+        assert_eq!(counting_alloc::allocations(), before);
+        assert!(!io.read_message_into::<Vec<u8>>(&mut out).unwrap());
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_counters() {
+        let messages = [b"a".to_vec(), b"bb".to_vec(), b"cccc".to_vec()];
+        let output = SharedWriter::default();
+        let mut writer = sync::MessageIo::new_writer(output.clone(), Uint16FramedEncoder);
+        writer
+            .write_message(&messages[0])
+            .expect("Failed to write message");
+        writer
+            .write_messages(&messages[1..])
+            .expect("Failed to write messages");
+        // 2 byte prefix per message.
+        assert_eq!(writer.bytes_written(), 13);
+        assert_eq!(writer.messages_written(), 3);
+
+        let written = output.0.take();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(written), Uint16FramedDecoder);
+        reader
+            .peek_message::<Vec<u8>>()
+            .expect("Failed to peek message");
+        while reader
+            .read_message::<Vec<u8>>()
+            .expect("Failed to read message")
+            .is_some()
+        {}
+        assert_eq!(reader.bytes_read(), 13);
+        assert_eq!(reader.messages_read(), 3);
+        assert_eq!(reader.bytes_written(), 0);
+        assert_eq!(reader.messages_written(), 0);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_counters() {
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, Uint16FramedEncoder);
+        for message in [b"a".to_vec(), b"bb".to_vec(), b"cccc".to_vec()] {
+            writer
+                .write_message(&message)
+                .await
+                .expect("Failed to write message");
+        }
+        assert_eq!(writer.bytes_written(), 13);
+        assert_eq!(writer.messages_written(), 3);
+        drop(writer);
+        while reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message")
+            .is_some()
+        {}
+        assert_eq!(reader.bytes_read(), 13);
+        assert_eq!(reader.messages_read(), 3);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_grow_increment() {
+        /// Frames with a 4 byte length prefix, never asking for the rest with `NeedMore`. Counts
+        /// how often the read buffer's capacity changed between calls.
+        #[derive(Default)]
+        struct GrowthCounter {
+            capacity: usize,
+            reallocations: usize,
+        }
+
+        impl decoder::Decoder<usize> for GrowthCounter {
+            type Error = Infallible;
+
+            fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<usize, Infallible> {
+                match data.first_chunk::<4>() {
+                    Some(prefix) if data.len() >= 4 + u32::from_be_bytes(*prefix) as usize => {
+                        let len = u32::from_be_bytes(*prefix) as usize;
+                        decoder::DecoderResult::Done(len, 4 + len)
+                    }
+                    _ => decoder::DecoderResult::Continue,
+                }
+            }
+
+            fn decode_bytes(
+                &mut self,
+                buf: &mut bytes::BytesMut,
+            ) -> decoder::DecoderResult<usize, Infallible> {
+                if self.capacity != 0 && buf.capacity() != self.capacity {
+                    self.reallocations += 1;
+                }
+                self.capacity = buf.capacity();
+                self.decode(buf)
+            }
+        }
+
+        let read_frame = |grow_increment| {
+            // A 1 MiB frame, read in the default chunks of `TEMP_BUFFER_SIZE` bytes.
+            let mut data = (1u32 << 20).to_be_bytes().to_vec();
+            data.resize(4 + (1 << 20), 0);
+            let mut reader =
+                sync::MessageIo::new_reader(std::io::Cursor::new(data), GrowthCounter::default())
+                    .with_grow_increment(grow_increment);
+            let len = reader
+                .read_message::<usize>()
+                .expect("Failed to read message");
+            assert_eq!(len, Some(1 << 20));
+            reader.decoder_mut().reallocations
+        };
+
+        assert!(read_frame(256 * 1024) <= 3);
+        assert!(read_frame(0) > 3);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_drain_policy() {
+        use decoder::DrainPolicy;
+        use encoder::Encoder;
+
+        /// Returns one datagram per read.
+        struct Datagrams(std::collections::VecDeque<Vec<u8>>);
+        impl std::io::Read for Datagrams {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let Some(datagram) = self.0.pop_front() else {
+                    return Ok(0);
+                };
+                buf[..datagram.len()].copy_from_slice(&datagram);
+                Ok(datagram.len())
+            }
+        }
+
+        // A frame followed by trailing bytes that happen to form a frame themselves.
+        let mut first = Uint16FramedEncoder.encode(&b"first".to_vec()).unwrap();
+        first.extend(b"\x00\x01!");
+        let second = Uint16FramedEncoder.encode(&b"second".to_vec()).unwrap();
+
+        let read_all = |policy| {
+            let stream = Datagrams([first.clone(), second.clone()].into());
+            let mut reader =
+                sync::MessageIo::new_reader(stream, Uint16FramedDecoder).with_drain_policy(policy);
+            let mut messages = Vec::new();
+            while let Some(message) = reader
+                .read_message::<Vec<u8>>()
+                .expect("Failed to read message")
+            {
+                messages.push(message);
+            }
+            messages
+        };
+
+        assert_eq!(
+            read_all(DrainPolicy::KeepRemaining),
+            [&b"first"[..], b"!", b"second"]
+        );
+        assert_eq!(
+            read_all(DrainPolicy::DiscardRemaining),
+            [&b"first"[..], b"second"]
+        );
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_resync() {
+        // The second line isn't valid UTF-8.
+        let data = b"first\n\xFF\xFE broken\nrecovered\n".to_vec();
+
+        let mut reader = sync::MessageIo::new_reader(
+            std::io::Cursor::new(data.clone()),
+            codec::LinesCodec::new(),
+        );
+        assert_eq!(
+            reader
+                .read_message::<String>()
+                .expect("Failed to read message"),
+            Some("first".to_string())
+        );
+        let err = reader
+            .read_message::<String>()
+            .expect_err("Expected a decode error without resync");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(data), codec::LinesCodec::new())
+                .with_resync(true);
+        let mut lines = Vec::new();
+        while let Some(line) = reader
+            .read_message::<String>()
+            .expect("Failed to read message")
+        {
+            lines.push(line);
+        }
+        assert_eq!(lines, ["first", "recovered"]);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_resync_resets_decoder() {
+        /// Lines starting with `+` are parts of the next line, `!` is a corrupt line.
+        #[derive(Default)]
+        struct PartsDecoder {
+            parts: String,
+            reset: bool,
+        }
+
+        impl decoder::Decoder<(String, bool)> for PartsDecoder {
+            type Error = error::CodecError;
+
+            fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<(String, bool)> {
+                let Some(end) = data.iter().position(|&b| b == b'\n') else {
+                    return decoder::DecoderResult::Continue;
+                };
+                let line = std::str::from_utf8(&data[..end]).expect("Test lines are UTF-8");
+                if line == "!" {
+                    return decoder::DecoderResult::Error("Corrupt line".into());
+                }
+                if let Some(part) = line.strip_prefix('+') {
+                    self.parts.push_str(part);
+                    return decoder::DecoderResult::ContinueFrom(end + 1);
+                }
+                let message = std::mem::take(&mut self.parts) + line;
+                // Whether `reset` ran before this frame was decoded.
+                decoder::DecoderResult::Done((message, std::mem::take(&mut self.reset)), end + 1)
+            }
+
+            fn resync(&mut self, data: &[u8]) -> Option<usize> {
+                data.iter().position(|&b| b == b'\n').map(|pos| pos + 1)
+            }
+
+            fn reset(&mut self) {
+                self.parts.clear();
+                self.reset = true;
+            }
+        }
+
+        let data = b"+stale\n!\nfresh\n+a\nb\n".to_vec();
+        // One byte per read, so parts taken with `ContinueFrom` are followed by more reads.
+        let stream = TrickleReader(std::io::Cursor::new(data), 1);
+        let mut reader =
+            sync::MessageIo::new_reader(stream, PartsDecoder::default()).with_resync(true);
+        let mut messages = Vec::new();
+        while let Some(message) = reader
+            .read_message::<(String, bool)>()
+            .expect("Failed to read message")
+        {
+            messages.push(message);
+        }
+        assert_eq!(
+            messages,
+            [("fresh".to_string(), true), ("ab".to_string(), false)]
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_resync() {
+        use encoder::Encoder;
+        use tokio::io::AsyncWriteExt;
+
+        let (rx, mut tx) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader =
+            tokio_crate::MessageTokio::new_reader(rx, codec::Cobs::new()).with_resync(true);
+
+        // A COBS code overrunning its frame, followed by a valid frame.
+        let mut data = vec![0x05, 0x11, 0x00];
+        data.extend(codec::Cobs::new().encode(b"valid\0frame").unwrap());
+        tx.write_all(&data).await.expect("Failed to write frames");
+        drop(tx);
+
+        let received = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(b"valid\0frame".to_vec()));
+        assert!(matches!(reader.read_message::<Vec<u8>>().await, Ok(None)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_message_stream() {
+        use futures_util::StreamExt;
+
+        let (rx, tx) = tokio::io::duplex(64);
+        let reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, Uint16FramedEncoder);
+
+        let messages = vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()];
+        for message in &messages {
+            writer
+                .write_message(message)
+                .await
+                .expect("Failed to write message");
+        }
+        drop(writer); // Close writer to end the stream
+
+        let received: Vec<std::io::Result<Vec<u8>>> =
+            reader.into_stream::<Vec<u8>>().collect().await;
+        let received: Vec<Vec<u8>> = received
+            .into_iter()
+            .collect::<std::io::Result<_>>()
+            .expect("Failed to read message");
+        assert_eq!(received, messages);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_message_sink() {
+        use futures_util::StreamExt;
+
+        let (rx, tx) = tokio::io::duplex(64);
+        let codec = || codec::LengthDelimited::new(codec::PrefixWidth::U16, codec::Endian::Big);
+        let reader = tokio_crate::MessageTokio::new_reader(rx, codec());
+        let writer = tokio_crate::MessageTokio::new_writer::<_, _, Vec<u8>>(tx, codec());
+
+        let messages = vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()];
+        let (forwarded, received) = tokio::join!(
+            futures_util::stream::iter(messages.clone())
+                .map(Ok)
+                .forward(writer),
+            reader.into_stream::<Vec<u8>>().collect::<Vec<_>>()
+        );
+        forwarded.expect("Failed to forward messages");
+
+        // forward() closes the sink, so the reader sees the end of the stream.
+        let received: Vec<Vec<u8>> = received
+            .into_iter()
+            .collect::<std::io::Result<_>>()
+            .expect("Failed to read message");
+        assert_eq!(received, messages);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_tokio_connect_accept() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+
+        let codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let listener = ::tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        let addr = listener.local_addr().unwrap();
+        let server = async {
+            let (stream, _) = listener.accept().await.expect("Failed to accept");
+            let mut server = tokio_crate::MessageTokio::accept(stream, codec.clone())
+                .expect("Failed to frame connection");
+            let request: Option<Vec<u8>> = server.read_message().await.unwrap();
+            server
+                .write_message_flush(b"pong")
+                .await
+                .expect("Failed to write reply");
+            request
+        };
+        let client = async {
+            let mut client = tokio_crate::MessageTokio::connect(addr, codec.clone())
+                .await
+                .expect("Failed to connect");
+            client
+                .write_message_flush(b"ping")
+                .await
+                .expect("Failed to write request");
+            let reply: Option<Vec<u8>> = client.read_message().await.unwrap();
+            assert!(client.into_inner().into_inner().nodelay().unwrap());
+            reply
+        };
+        let (request, reply) = ::tokio::join!(server, client);
+        assert_eq!(request, Some(b"ping".to_vec()));
+        assert_eq!(reply, Some(b"pong".to_vec()));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_tokio_spawn_keepalive() {
+        use std::{sync::Arc, time::Duration};
+
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader =
+            tokio_crate::MessageTokio::new_reader(rx, codec::LengthDelimited::u16_be());
+        let writer = Arc::new(tokio::sync::Mutex::new(
+            tokio_crate::MessageTokio::new_writer::<_, _, Vec<u8>>(
+                tx,
+                codec::LengthDelimited::u16_be(),
+            ),
+        ));
+        let keepalive =
+            tokio_crate::spawn_keepalive(&writer, Duration::from_millis(20), || b"ping".to_vec());
+
+        for _ in 0..2 {
+            let message: Option<Vec<u8>> = reader
+                .read_message_timeout(Duration::from_secs(1))
+                .await
+                .expect("Expected a keepalive");
+            assert_eq!(message.as_deref(), Some(&b"ping"[..]));
+        }
+        // Other writes go through the same lock and never interleave with a keepalive.
+        writer
+            .lock()
+            .await
+            .write_message_flush(b"data".to_vec())
+            .await
+            .expect("Failed to write message");
+        loop {
+            let message: Option<Vec<u8>> = reader
+                .read_message_timeout(Duration::from_secs(1))
+                .await
+                .expect("Failed to read message");
+            if message.as_deref() == Some(&b"data"[..]) {
+                break;
+            }
+            assert_eq!(message.as_deref(), Some(&b"ping"[..]));
+        }
+
+        keepalive.stop().await.expect("Keepalive task failed");
+        let count = writer.lock().await.messages_written();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(writer.lock().await.messages_written(), count);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_tokio_spawn_reader_writer() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+
+        let codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let (a, b) = ::tokio::net::UnixStream::pair().expect("Failed to create socket pair");
+        let writer = tokio_crate::MessageTokio::new_writer::<_, _, Vec<u8>>(a, codec.clone());
+        let reader = tokio_crate::MessageTokio::new_reader::<_, _, Vec<u8>>(b, codec);
+
+        let (out_tx, out_rx) = ::tokio::sync::mpsc::channel::<Vec<u8>>(4);
+        let (in_tx, mut in_rx) = ::tokio::sync::mpsc::channel::<Vec<u8>>(4);
+        let writer = writer.spawn_writer(out_rx);
+        let reader = reader.spawn_reader(in_tx);
+
+        let messages: Vec<Vec<u8>> = (0..20u8).map(|i| vec![i; i as usize]).collect();
+        for message in &messages {
+            out_tx
+                .send(message.clone())
+                .await
+                .expect("Writer task ended");
+        }
+        drop(out_tx);
+
+        let mut received = Vec::new();
+        while let Some(message) = in_rx.recv().await {
+            received.push(message);
+        }
+        assert_eq!(received, messages);
+        writer.await.unwrap().expect("Writer task failed");
+        reader.await.unwrap().expect("Reader task failed");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_tokio_write_queue() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+
+        let codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let (a, b) = ::tokio::net::UnixStream::pair().expect("Failed to create socket pair");
+        let writer = tokio_crate::MessageTokio::new_writer::<_, _, Vec<u8>>(a, codec.clone());
+        let mut reader = tokio_crate::MessageTokio::new_reader::<_, _, Vec<u8>>(b, codec);
+        let (handle, task) = writer.spawn_write_queue::<Vec<u8>>(2);
+
+        // Each producer sends frames of its own id, long enough to span several writes.
+        let producers: Vec<_> = (0..4u8)
+            .map(|id| {
+                let handle = handle.clone();
+                ::tokio::spawn(async move {
+                    for seq in 0..50u8 {
+                        let mut message = vec![id; 4096];
+                        message[1] = seq;
+                        handle.send(message).await.expect("Writer task ended");
+                    }
+                })
+            })
+            .collect();
+        drop(handle);
+
+        let mut next_seq = [0u8; 4];
+        while let Some(message) = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message")
+        {
+            let id = message[0];
+            assert_eq!(message.len(), 4096);
+            assert!(message[2..].iter().all(|&b| b == id), "Interleaved frame");
+            // Frames of one producer arrive in the order they were sent.
+            assert_eq!(message[1], next_seq[id as usize]);
+            next_seq[id as usize] += 1;
+        }
+        assert_eq!(next_seq, [50; 4]);
+        for producer in producers {
+            producer.await.unwrap();
+        }
+        task.await.unwrap().expect("Writer task failed");
+    }
+
+    /// Records how many bytes of each buffer it is asked to fill were already initialized.
+    #[cfg(feature = "tokio")]
+    struct InitRecorder<R> {
+        inner: R,
+        initialized: std::sync::Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+    #[cfg(feature = "tokio")]
+    impl<R: ::tokio::io::AsyncRead + Unpin> ::tokio::io::AsyncRead for InitRecorder<R> {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut ::tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.initialized
+                .lock()
+                .unwrap()
+                .push(buf.initialized().len());
+            std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_tokio_read_message_native() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+
+        let codec = LengthDelimited::new(PrefixWidth::U32, Endian::Big);
+        let (a, b) = ::tokio::net::UnixStream::pair().expect("Failed to create socket pair");
+        let initialized = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let b = InitRecorder {
+            inner: b,
+            initialized: initialized.clone(),
+        };
+        let mut writer = tokio_crate::MessageTokio::new_writer::<_, _, Vec<u8>>(a, codec.clone());
+        let mut reader = tokio_crate::MessageTokio::new_reader::<_, _, Vec<u8>>(b, codec);
+
+        let large: Vec<u8> = (0..1 << 20).map(|i| (i % 251) as u8).collect();
+        let sent = large.clone();
+        let writer = ::tokio::spawn(async move {
+            writer.write_message(sent).await?;
+            writer.write_message(b"small".to_vec()).await?;
+            writer.write_message(b"last".to_vec()).await?;
+            writer.close_write().await
+        });
+
+        let received: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(received, Some(large));
+        let received: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"small"[..]));
+        let received: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"last"[..]));
+        assert_eq!(reader.read_message::<Vec<u8>>().await.unwrap(), None);
+        assert_eq!(reader.bytes_read(), (4 + (1 << 20) + 4 + 5 + 4 + 4) as u64);
+        writer.await.unwrap().expect("Writer failed");
+        // Every read went to the tokio stream with the spare capacity left uninitialized,
+        // `Compat` would have handed over zeroed buffers.
+        let initialized = initialized.lock().unwrap();
+        assert!(!initialized.is_empty());
+        assert!(initialized.iter().all(|&len| len == 0));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_tokio_from_blocking() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+        use std::io::{self, PipeReader, PipeWriter, Read, Write};
+
+        struct PipeStream {
+            reader: PipeReader,
+            writer: PipeWriter,
+        }
+
+        impl Read for PipeStream {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.reader.read(buf)
+            }
+        }
+
+        impl Write for PipeStream {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.writer.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.writer.flush()
+            }
+        }
+
+        let (reader, mut echo_writer) = io::pipe().expect("Failed to create pipe");
+        let (mut echo_reader, writer) = io::pipe().expect("Failed to create pipe");
+        // Echoes the 2 byte length prefix and the 14 byte message back.
+        let echo = std::thread::spawn(move || {
+            let mut frame = [0; 16];
+            echo_reader.read_exact(&mut frame)?;
+            echo_writer.write_all(&frame)
+        });
+
+        let codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let mut io = tokio_crate::MessageTokio::from_blocking::<_, _, Vec<u8>>(
+            PipeStream { reader, writer },
+            codec,
+        );
+        io.write_message(b"hello blocking".to_vec()).await.unwrap();
+        io.flush().await.unwrap();
+        let received: Option<Vec<u8>> = io.read_message().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"hello blocking"[..]));
+        echo.join().unwrap().expect("Echo failed");
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_tls_echo() {
+        use codec::{Endian, LengthDelimited, PrefixWidth};
+        use std::sync::Arc;
+        use tokio_rustls::{
+            TlsAcceptor, TlsConnector,
+            rustls::{
+                ClientConfig, RootCertStore, ServerConfig,
+                pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject},
+            },
+        };
+
+        let ca = CertificateDer::from_pem_slice(include_bytes!("../testdata/tls/ca.pem")).unwrap();
+        let cert =
+            CertificateDer::from_pem_slice(include_bytes!("../testdata/tls/server.pem")).unwrap();
+        let key =
+            PrivateKeyDer::from_pem_slice(include_bytes!("../testdata/tls/server.key")).unwrap();
+        let server_config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)
+            .unwrap();
+        let mut roots = RootCertStore::empty();
+        roots.add(ca).unwrap();
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let listener = ::tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let codec = LengthDelimited::new(PrefixWidth::U32, Endian::Big);
+
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let server_codec = codec.clone();
+        let server = ::tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await?;
+            let tls = acceptor.accept(tcp).await?;
+            let mut io =
+                tokio_crate::MessageTokio::with_tls::<_, _, Vec<u8>>(tls.into(), server_codec);
+            while let Some(message) = io.read_message::<Vec<u8>>().await? {
+                io.write_message(message).await?;
+                io.flush().await?;
+            }
+            io.close_write().await
+        });
+
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let tcp = ::tokio::net::TcpStream::connect(addr).await.unwrap();
+        let tls = connector
+            .connect(ServerName::try_from("localhost").unwrap(), tcp)
+            .await
+            .unwrap();
+        // The client stream works with `new_rw` as is.
+        let mut client = tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(tls, codec);
+        for message in [&b"over tls"[..], b"", &[0xAB; 20_000]] {
+            client.write_message(message.to_vec()).await.unwrap();
+            client.flush().await.unwrap();
+            let echoed: Option<Vec<u8>> = client.read_message().await.unwrap();
+            assert_eq!(echoed.as_deref(), Some(message));
+        }
+        client.close_write().await.unwrap();
+        assert_eq!(client.read_message::<Vec<u8>>().await.unwrap(), None);
+        server.await.unwrap().expect("Server failed");
+    }
+
+    /// Writes a 4 byte header and the payload in separate steps, with an exact size hint.
+    #[cfg(feature = "tokio")]
+    struct ExactHintEncoder;
+
+    #[cfg(feature = "tokio")]
+    impl encoder::Encoder<Vec<u8>> for ExactHintEncoder {
+        type Error = Infallible;
+
+        fn encode(&mut self, data: Vec<u8>) -> Result<Vec<u8>, Infallible> {
+            let mut encoded = (data.len() as u32).to_be_bytes().to_vec();
+            encoded.extend_from_slice(&data);
+            Ok(encoded)
+        }
+
+        fn encode_into(
+            &mut self,
+            data: Vec<u8>,
+            dst: &mut bytes::BytesMut,
+        ) -> Result<(), Infallible> {
+            dst.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            for chunk in data.chunks(100) {
+                dst.extend_from_slice(chunk);
+            }
+            Ok(())
+        }
+
+        fn size_hint(&self, data: &Vec<u8>) -> Option<usize> {
+            Some(4 + data.len())
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_tokio_codec_adapter_reserves_size_hint() {
+        use tokio_util::codec::Encoder as _;
+
+        let mut adapter = tokio_crate::MsgIoCodecAdapter::<_, Vec<u8>>::new(ExactHintEncoder);
+        let mut dst = bytes::BytesMut::new();
+        adapter.encode(vec![0x42; 10_000], &mut dst).unwrap();
+        assert_eq!(dst.len(), 10_004);
+        assert_eq!(dst.capacity(), 10_004);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_boxed_codecs() {
+        use codec::{BoxedDecoder, BoxedEncoder, LengthDelimited};
+        use tokio_util::compat::TokioAsyncReadCompatExt;
+
+        type BoxedIo<S> = r#async::AsyncMessageIo<S, BoxedEncoder<Vec<u8>>, BoxedDecoder<Vec<u8>>>;
+        let boxed = |stream: tokio::io::DuplexStream| -> BoxedIo<_> {
+            r#async::AsyncMessageIo::from_parts(
+                stream.compat(),
+                bytes::BytesMut::new(),
+                BoxedEncoder::new(LengthDelimited::u16_be()),
+                BoxedDecoder::new(LengthDelimited::u16_be()),
+            )
+        };
+        let (left, right) = tokio::io::duplex(1024);
+        let (mut left, mut right) = (boxed(left), boxed(right));
+
+        let written = left
+            .write_message(b"boxed".to_vec())
+            .await
+            .expect("Failed to write message");
+        assert_eq!(written, 7);
+        let message: Option<Vec<u8>> = right.read_message().await.unwrap();
+        assert_eq!(message.as_deref(), Some(&b"boxed"[..]));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_tokio_codec_adapter() {
+        use tokio_util::codec::LinesCodec;
+
+        let (a, b) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let codec = tokio_crate::TokioCodecAdapter::new(LinesCodec::new());
+        let mut client = tokio_crate::MessageTokio::new_rw(a, codec.clone());
+        let mut server = tokio_crate::MessageTokio::new_rw(b, codec);
+
+        client
+            .write_message("ping".to_string())
+            .await
+            .expect("Failed to write message");
+        let received = server
+            .read_message::<String>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received.as_deref(), Some("ping"));
+
+        server
+            .write_message("pong".to_string())
+            .await
+            .expect("Failed to write message");
+        let received = client
+            .read_message::<String>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received.as_deref(), Some("pong"));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_msg_io_codec_adapter() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_util::codec::Framed;
+
+        let (a, b) = tokio::io::duplex(64);
+        let codec = || {
+            tokio_crate::MsgIoCodecAdapter::<_, Vec<u8>>::new(codec::LengthDelimited::new(
+                codec::PrefixWidth::U16,
+                codec::Endian::Big,
+            ))
+        };
+        let mut client = Framed::new(a, codec());
+        let mut server = Framed::new(b, codec());
+
+        client
+            .send(b"framed".to_vec())
+            .await
+            .expect("Failed to send message");
+        let received = server
+            .next()
+            .await
+            .expect("Stream ended early")
+            .expect("Failed to read message");
+        assert_eq!(received, b"framed");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_tokio_codec_adapter_partial_frame() {
+        use decoder::Decoder;
+        use tokio_util::codec::LengthDelimitedCodec;
+
+        // LengthDelimitedCodec consumes the header before the body arrives.
+        let mut codec = tokio_crate::TokioCodecAdapter::new(LengthDelimitedCodec::new());
+        let frame = b"\x00\x00\x00\x05hello";
+        assert!(matches!(
+            codec.decode(&frame[..6]),
+            decoder::DecoderResult::Continue
+        ));
+        match codec.decode(frame) {
+            decoder::DecoderResult::Done(msg, used) => {
+                assert_eq!(&msg[..], b"hello");
+                assert_eq!(used, frame.len());
+            }
+            _ => panic!("Expected a decoded frame"),
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_write_messages() {
+        use encoder::Encoder;
+        use std::io::Read;
+
+        let (mut rx, tx) = std::io::pipe().expect("Failed to create pipe");
+        let mut writer = sync::MessageIo::new_writer(tx, Uint16FramedEncoder);
+
+        let messages = vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()];
+        writer
+            .write_messages(&messages)
+            .expect("Failed to write messages");
+
+        // All-or-nothing: the oversized message fails the whole batch.
+        let batch = vec![b"ok".to_vec(), vec![0u8; 70000]];
+        assert!(writer.write_messages(&batch).is_err());
+        drop(writer);
+
+        let mut expected = Vec::new();
+        for message in &messages {
+            expected.extend(Uint16FramedEncoder.encode(message).unwrap());
+        }
+        let mut received = Vec::new();
+        rx.read_to_end(&mut received).expect("Failed to read pipe");
+        assert_eq!(received, expected);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_messages() {
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, Uint16FramedEncoder);
+
+        let messages = vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()];
+        writer
+            .write_messages(&messages)
+            .await
+            .expect("Failed to write messages");
+        drop(writer);
+
+        for message in &messages {
+            let received = reader
+                .read_message::<Vec<u8>>()
+                .await
+                .expect("Failed to read message");
+            assert_eq!(received.as_ref(), Some(message));
+        }
+        assert!(matches!(reader.read_message::<Vec<u8>>().await, Ok(None)));
+    }
+
+    /// A u16 framed encoder that only supports `encode_into`, counting how often it is called.
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    #[derive(Default)]
+    struct DirectEncoder {
+        calls: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    impl encoder::Encoder<&[u8]> for DirectEncoder {
+        type Error = Infallible;
+
+        fn encode(&mut self, _data: &[u8]) -> Result<Vec<u8>, Infallible> {
+            panic!("Writers should call encode_into");
+        }
+
+        fn encode_into(
+            &mut self,
+            data: &[u8],
+            dst: &mut bytes::BytesMut,
+        ) -> Result<(), Infallible> {
+            self.calls
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            dst.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            dst.extend_from_slice(data);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encode_into_default() {
+        use encoder::Encoder;
+
+        // Encoders that only implement `encode` get `encode_into` for free.
+        let mut dst = bytes::BytesMut::from(&b"head"[..]);
+        Uint16FramedEncoder
+            .encode_into(&b"tail".to_vec(), &mut dst)
+            .expect("Failed to encode");
+        assert_eq!(&dst[..], b"head\x00\x04tail");
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_write_uses_encode_into() {
+        const MESSAGES: usize = 10_000;
+        let payload = [0x42u8; 64];
+
+        let encoder = DirectEncoder::default();
+        let calls = encoder.calls.clone();
+        let output = SharedWriter::default();
+        let mut writer = sync::MessageIo::new_writer(output.clone(), encoder);
+        for _ in 0..MESSAGES {
+            writer
+                .write_message(&payload[..])
+                .expect("Failed to write message");
+        }
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), MESSAGES);
+
+        let written = output.0.take();
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(written), Uint16FramedDecoder);
+        let mut count = 0;
+        while let Some(message) = reader.read_message().expect("Failed to read message") {
+            assert_eq!(message, payload);
+            count += 1;
+        }
+        assert_eq!(count, MESSAGES);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_uses_encode_into() {
+        const MESSAGES: usize = 10_000;
+        let payload = [0x42u8; 64];
+
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let encoder = DirectEncoder::default();
+        let calls = encoder.calls.clone();
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, encoder);
+        let writing = tokio::spawn(async move {
+            for _ in 0..MESSAGES {
+                writer
+                    .write_message(&payload[..])
+                    .await
+                    .expect("Failed to write message");
+            }
+        });
+
+        for _ in 0..MESSAGES {
+            let message = reader
+                .read_message::<Vec<u8>>()
+                .await
+                .expect("Failed to read message");
+            assert_eq!(message.as_deref(), Some(&payload[..]));
+        }
+        writing.await.expect("Writer task failed");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), MESSAGES);
+    }
+
+    /// A u16 framed encoder returning the prefix and the body as separate slices.
+    #[cfg(feature = "tokio")]
+    struct VectoredEncoder;
+    #[cfg(feature = "tokio")]
+    impl encoder::Encoder<bytes::Bytes> for VectoredEncoder {
+        type Error = Infallible;
+
+        fn encode(&mut self, _data: bytes::Bytes) -> Result<Vec<u8>, Infallible> {
+            panic!("Vectored writes should call encode_vectored");
+        }
+
+        fn encode_vectored(&mut self, data: bytes::Bytes) -> Result<Vec<bytes::Bytes>, Infallible> {
+            let prefix = bytes::Bytes::copy_from_slice(&(data.len() as u16).to_be_bytes());
+            Ok(vec![prefix, data])
+        }
+    }
+
+    /// An async writer accepting at most `limit` bytes per write, recording the slice counts of
+    /// vectored writes.
+    #[cfg(feature = "tokio")]
+    struct VectoredRecorder {
+        written: Vec<u8>,
+        slice_counts: Vec<usize>,
+        limit: usize,
+    }
+    #[cfg(feature = "tokio")]
+    impl futures_util::AsyncWrite for VectoredRecorder {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.poll_write_vectored(cx, &[std::io::IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            bufs: &[std::io::IoSlice<'_>],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let this = self.get_mut();
+            this.slice_counts.push(bufs.len());
+            let mut n = 0;
+            for buf in bufs {
+                let take = buf.len().min(this.limit - n);
+                this.written.extend_from_slice(&buf[..take]);
+                n += take;
+            }
+            std::task::Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_message_vectored() {
+        let recorder = VectoredRecorder {
+            written: Vec::new(),
+            slice_counts: Vec::new(),
+            limit: 3,
+        };
+        let mut writer = r#async::AsyncMessageIo::new_writer(recorder, VectoredEncoder);
+        let body = bytes::Bytes::from_static(b"hello");
+        writer
+            .write_message_vectored(body.clone())
+            .await
+            .expect("Failed to write message");
+        writer
+            .write_message_vectored(bytes::Bytes::from_static(b"world!"))
+            .await
+            .expect("Failed to write message");
+        assert_eq!(writer.bytes_written(), 15);
+        assert_eq!(writer.messages_written(), 2);
+
+        let recorder = writer.into_inner();
+        assert_eq!(recorder.written, b"\x00\x05hello\x00\x06world!");
+        // Header and body go out together, and partial writes resume mid-slice.
+        assert_eq!(recorder.slice_counts, [2, 1, 1, 2, 1, 1]);
+    }
+
+    /// A codec stamping every frame with a count of the frames it has encoded and decoded so
+    /// far. Decoded messages are the stamp followed by the payload.
+    #[cfg(feature = "sync")]
+    #[derive(Clone, Default)]
+    struct SequenceCodec {
+        count: u8,
+    }
+    #[cfg(feature = "sync")]
+    impl encoder::Encoder<Vec<u8>> for SequenceCodec {
+        type Error = Infallible;
+
+        fn encode(&mut self, data: Vec<u8>) -> Result<Vec<u8>, Infallible> {
+            let mut encoded = vec![self.count, data.len() as u8];
+            encoded.extend(data);
+            self.count += 1;
+            Ok(encoded)
+        }
+    }
+    #[cfg(feature = "sync")]
+    impl decoder::Decoder<Vec<u8>> for SequenceCodec {
+        type Error = Infallible;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<Vec<u8>, Infallible> {
+            let [stamp, len, ..] = *data else {
+                return decoder::DecoderResult::Continue;
+            };
+            let Some(payload) = data.get(2..2 + len as usize) else {
+                return decoder::DecoderResult::Continue;
+            };
+            self.count += 1;
+            let mut message = vec![stamp];
+            message.extend_from_slice(payload);
+            decoder::DecoderResult::Done(message, 2 + len as usize)
+        }
+    }
+
+    /// A stream reading back whatever was written to it.
+    #[cfg(feature = "sync")]
+    #[derive(Default)]
+    struct Loopback(std::collections::VecDeque<u8>);
+    #[cfg(feature = "sync")]
+    impl std::io::Read for Loopback {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+    #[cfg(feature = "sync")]
+    impl std::io::Write for Loopback {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_shared_codec() {
+        fn ping_pong<ED>(codec: ED) -> Vec<Vec<u8>>
+        where
+            ED: encoder::Encoder<Vec<u8>> + decoder::Decoder<Vec<u8>> + Clone,
+        {
+            let mut io = sync::MessageIo::new_rw(Loopback::default(), codec);
+            let mut received = Vec::new();
+            for payload in [b"ping", b"pong"] {
+                io.write_message(payload.to_vec())
+                    .expect("Failed to write message");
+                let message = io.read_message().expect("Failed to read message");
+                received.push(message.expect("Expected a message"));
+            }
+            received
+        }
+
+        // Cloned codecs count separately, the writer never sees the frame that was read.
+        assert_eq!(
+            ping_pong(SequenceCodec::default()),
+            [b"\x00ping".to_vec(), b"\x01pong".to_vec()]
+        );
+
+        // A shared codec counts both directions.
+        let codec = codec::Shared::new(SequenceCodec::default());
+        assert_eq!(
+            ping_pong(codec.clone()),
+            [b"\x00ping".to_vec(), b"\x02pong".to_vec()]
+        );
+        assert_eq!(codec.lock().count, 4);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_write_owned_message() {
+        /// Checks that the payload it is handed is the caller's buffer, moved rather than cloned.
+        struct ZeroCopyEncoder(*const u8);
+        impl encoder::Encoder<bytes::Bytes> for ZeroCopyEncoder {
+            type Error = Infallible;
+
+            fn encode(&mut self, _data: bytes::Bytes) -> Result<Vec<u8>, Infallible> {
+                panic!("Vectored writes should call encode_vectored");
+            }
+
+            fn encode_vectored(
+                &mut self,
+                data: bytes::Bytes,
+            ) -> Result<Vec<bytes::Bytes>, Infallible> {
+                assert!(data.is_unique(), "The payload was cloned on its way in");
+                assert_eq!(data.as_ptr(), self.0);
+                let prefix = bytes::Bytes::copy_from_slice(&(data.len() as u16).to_be_bytes());
+                Ok(vec![prefix, data])
+            }
+        }
+
+        let payload = bytes::Bytes::from(vec![0x5A; 32]);
+        let recorder = VectoredRecorder {
+            written: Vec::new(),
+            slice_counts: Vec::new(),
+            limit: usize::MAX,
+        };
+        let mut writer =
+            r#async::AsyncMessageIo::new_writer(recorder, ZeroCopyEncoder(payload.as_ptr()));
+        let written = writer
+            .write_message_vectored(payload)
+            .await
+            .expect("Failed to write message");
+        assert_eq!(written, 34);
+        assert_eq!(writer.into_inner().written[2..], [0x5A; 32]);
+    }
+
+    /// A writer appending to a shared buffer, so tests can observe what reached it.
+    #[cfg(feature = "sync")]
+    #[derive(Clone, Default)]
+    struct SharedWriter(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+    #[cfg(feature = "sync")]
+    impl std::io::Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_flush() {
+        let sink = SharedWriter::default();
+        let stream = std::io::BufWriter::new(sink.clone());
+        let mut writer = sync::MessageIo::new_writer(stream, Uint16FramedEncoder);
+
+        writer
+            .write_message(&b"buffered".to_vec())
+            .expect("Failed to write message");
+        assert!(sink.0.borrow().is_empty(), "Expected data to stay buffered");
+        writer.flush().expect("Failed to flush");
+        assert_eq!(sink.0.borrow().as_slice(), b"\x00\x08buffered");
+
+        writer
+            .write_message_flush(&b"flushed".to_vec())
+            .expect("Failed to write message");
+        assert_eq!(sink.0.borrow().len(), 10 + 9);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_flush() {
+        use tokio_util::compat::TokioAsyncWriteCompatExt;
+
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let stream = futures_util::io::BufWriter::new(tx.compat_write());
+        let mut writer = r#async::AsyncMessageIo::new_writer(stream, Uint16FramedEncoder);
+
+        let data = b"buffered".to_vec();
+        writer
+            .write_message(&data)
+            .await
+            .expect("Failed to write message");
+        let early = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            reader.read_message::<Vec<u8>>(),
+        )
+        .await;
+        assert!(early.is_err(), "Expected no data before flushing");
+
+        writer.flush().await.expect("Failed to flush");
+        let received = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(data));
+
+        let data = b"flushed".to_vec();
+        writer
+            .write_message_flush(&data)
+            .await
+            .expect("Failed to write message");
+        let received = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(data));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_clean_and_dirty_eof() {
+        // Clean EOF: the buffer is empty when the stream ends.
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(b"\x00\x02hi"), Uint16FramedDecoder);
+        assert_eq!(
+            reader.read_message::<Vec<u8>>().unwrap(),
+            Some(b"hi".to_vec())
+        );
+        assert!(matches!(reader.read_message::<Vec<u8>>(), Ok(None)));
+
+        // Dirty EOF: a partial frame is stranded in the buffer.
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(b"\x00\x02h"), Uint16FramedDecoder);
+        let err = reader
+            .read_message::<Vec<u8>>()
+            .expect_err("Expected an error for a truncated frame");
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_clean_eof() {
+        let (rx, tx) = tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let mut writer = tokio_crate::MessageTokio::new_writer(tx, Uint16FramedEncoder);
+
+        writer
+            .write_message(&b"bye".to_vec())
+            .await
+            .expect("Failed to write message");
+        drop(writer);
+
+        let received = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(b"bye".to_vec()));
+        assert!(matches!(reader.read_message::<Vec<u8>>().await, Ok(None)));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_into_parts() {
+        use encoder::Encoder;
+
+        let mut data = Uint16FramedEncoder.encode(&b"hello".to_vec()).unwrap();
+        data.extend_from_slice(b"RAW PROTOCOL");
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(data.clone()), Uint16FramedDecoder);
+        let received = reader
+            .read_message::<Vec<u8>>()
+            .expect("Failed to read message");
+        assert_eq!(received, Some(b"hello".to_vec()));
+
+        let (stream, leftover) = reader.into_parts();
+        assert_eq!(&leftover[..], b"RAW PROTOCOL");
+        assert_eq!(stream.position(), data.len() as u64);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_into_parts() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (rx, mut tx) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        tx.write_all(b"\x00\x02hiupgraded")
+            .await
+            .expect("Failed to write");
+        let received = reader
+            .read_message::<Vec<u8>>()
+            .await
+            .expect("Failed to read message");
+        assert_eq!(received, Some(b"hi".to_vec()));
+
+        let (stream, leftover) = reader.into_parts();
+        assert_eq!(&leftover[..], b"upgraded");
+        let mut stream = stream.into_inner();
+        tx.write_all(b" stream").await.expect("Failed to write");
+        drop(tx);
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.expect("Failed to read");
+        assert_eq!(rest, b" stream");
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_from_parts() {
+        use encoder::Encoder;
+
+        let mut data = Uint16FramedEncoder.encode(&b"hello".to_vec()).unwrap();
+        data.extend(codec::Cobs::new().encode(b"upgraded").unwrap());
+        let mut reader =
+            sync::MessageIo::new_reader(std::io::Cursor::new(data), Uint16FramedDecoder);
+        let received: Option<Vec<u8>> = reader.read_message().unwrap();
+        assert_eq!(received.as_deref(), Some(&b"hello"[..]));
+
+        let (stream, leftover) = reader.into_parts();
+        let mut reader = sync::MessageIo::from_parts(stream, leftover, (), codec::Cobs::new());
+        let received: Option<Vec<u8>> = reader.read_message().unwrap();
+        assert_eq!(received.as_deref(), Some(&b"upgraded"[..]));
+        assert!(matches!(reader.read_message::<Vec<u8>>(), Ok(None)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_from_parts() {
+        use encoder::Encoder;
+        use tokio::io::AsyncWriteExt;
+
+        let (rx, mut tx) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, Uint16FramedDecoder);
+        let mut data = Uint16FramedEncoder.encode(&b"hello".to_vec()).unwrap();
+        let upgraded = codec::Cobs::new().encode(b"upgraded").unwrap();
+        // The first Cobs frame arrives split, half of it with the last old frame.
+        data.extend_from_slice(&upgraded[..4]);
+        tx.write_all(&data).await.expect("Failed to write");
+        let received: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"hello"[..]));
+
+        let (stream, leftover) = reader.into_parts();
+        assert_eq!(&leftover[..], &upgraded[..4]);
+        let mut reader =
+            r#async::AsyncMessageIo::from_parts(stream, leftover, (), codec::Cobs::new());
+        tx.write_all(&upgraded[4..]).await.expect("Failed to write");
+        let received: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"upgraded"[..]));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_read_one_handshake() {
+        use encoder::Encoder;
+
+        let mut data = Uint16FramedEncoder.encode(&b"HELLO v2".to_vec()).unwrap();
+        data.extend_from_slice(b"first line\nsecond line\n");
+        let (handshake, stream, leftover) =
+            sync::read_one::<_, _, Vec<u8>>(std::io::Cursor::new(data), Uint16FramedDecoder)
+                .expect("Failed to read handshake");
+        assert_eq!(handshake.as_deref(), Some(&b"HELLO v2"[..]));
+        assert_eq!(&leftover[..], b"first line\nsecond line\n");
+
+        let mut reader =
+            sync::MessageIo::from_parts(stream, leftover, (), codec::LinesCodec::new());
+        let lines: Vec<String> = reader.iter().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(lines, ["first line", "second line"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_read_one_handshake() {
+        use encoder::Encoder;
+        use tokio::io::AsyncWriteExt;
+        use tokio_util::compat::TokioAsyncReadCompatExt;
+
+        let (rx, mut tx) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let mut data = Uint16FramedEncoder.encode(&b"HELLO v2".to_vec()).unwrap();
+        data.extend_from_slice(b"first ");
+        tx.write_all(&data).await.expect("Failed to write");
+        let (handshake, stream, leftover) =
+            r#async::read_one::<_, _, Vec<u8>>(rx.compat(), Uint16FramedDecoder)
+                .await
+                .expect("Failed to read handshake");
+        assert_eq!(handshake.as_deref(), Some(&b"HELLO v2"[..]));
+        assert_eq!(&leftover[..], b"first ");
+
+        let mut reader =
+            r#async::AsyncMessageIo::from_parts(stream, leftover, (), codec::LinesCodec::new());
+        tx.write_all(b"line\n").await.expect("Failed to write");
+        let line: Option<String> = reader.read_message().await.unwrap();
+        assert_eq!(line.as_deref(), Some("first line"));
+    }
+
+    /// A u32 framed decoder that copies body bytes into its own state as they arrive and hands
+    /// them back through `ContinueFrom`, counting every byte it is shown.
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    #[derive(Default)]
+    struct AccumulatingDecoder {
+        frame: Option<(usize, Vec<u8>)>,
+        shown: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    impl decoder::Decoder<Vec<u8>> for AccumulatingDecoder {
+        type Error = Infallible;
+
+        fn decode(&mut self, data: &[u8]) -> decoder::DecoderResult<Vec<u8>, Infallible> {
+            self.shown
+                .fetch_add(data.len(), std::sync::atomic::Ordering::Relaxed);
+            let (header, (len, body)) = match &mut self.frame {
+                Some(frame) => (0, frame),
+                None if data.len() >= 4 => {
+                    let len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                    (4, self.frame.insert((len, Vec::with_capacity(len))))
+                }
+                None => return decoder::DecoderResult::Continue,
+            };
+            let take = (*len - body.len()).min(data.len() - header);
+            body.extend_from_slice(&data[header..header + take]);
+            if body.len() < *len {
+                return decoder::DecoderResult::ContinueFrom(header + take);
+            }
+            let (_, body) = self.frame.take().expect("Frame in progress");
+            decoder::DecoderResult::Done(body, header + take)
+        }
+    }
+
+    /// A 1 MiB u32 framed message followed by a short one.
+    #[cfg(any(feature = "sync", feature = "tokio"))]
+    fn pipelined_frames() -> (Vec<u8>, Vec<u8>) {
+        let large: Vec<u8> = (0..1 << 20).map(|i| i as u8).collect();
+        let mut framed = Vec::new();
+        for message in [&large[..], b"after"] {
+            framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            framed.extend_from_slice(message);
+        }
+        (large, framed)
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_pipelined_decoding() {
+        let (large, framed) = pipelined_frames();
+        let decoder = AccumulatingDecoder::default();
+        let shown = decoder.shown.clone();
+        let stream = TrickleReader(std::io::Cursor::new(framed.clone()), 1024);
+        let mut reader = sync::MessageIo::new_reader(stream, decoder);
+
+        let first = reader.read_message().expect("Failed to read message");
+        assert_eq!(first, Some(large));
+        let second = reader.read_message().expect("Failed to read message");
+        assert_eq!(second, Some(b"after".to_vec()));
+        assert!(matches!(reader.read_message::<Vec<u8>>(), Ok(None)));
+
+        // No prefix is rescanned, only bytes following a completed frame are shown again.
+        assert!(shown.load(std::sync::atomic::Ordering::Relaxed) <= framed.len() + 1024);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_pipelined_dirty_eof() {
+        let (_, framed) = pipelined_frames();
+        let stream = TrickleReader(std::io::Cursor::new(framed[..4096].to_vec()), 1024);
+        let mut reader = sync::MessageIo::new_reader(stream, AccumulatingDecoder::default());
+
+        // The decoder has taken every buffered byte, the frame is still incomplete.
+        let err = reader
+            .read_message::<Vec<u8>>()
+            .expect_err("Expected the stream to end mid-frame");
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_pipelined_decoding() {
+        use tokio::io::AsyncWriteExt;
+
+        let (large, framed) = pipelined_frames();
+        let (rx, mut tx) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let decoder = AccumulatingDecoder::default();
+        let shown = decoder.shown.clone();
+        let mut reader = tokio_crate::MessageTokio::new_reader(rx, decoder);
+
+        let total = framed.len();
+        let writing = tokio::spawn(async move {
+            for chunk in framed.chunks(1024) {
+                tx.write_all(chunk).await.expect("Failed to write chunk");
+            }
+        });
+
+        let first = reader.read_message().await.expect("Failed to read message");
+        assert_eq!(first, Some(large));
+        let second = reader.read_message().await.expect("Failed to read message");
+        assert_eq!(second, Some(b"after".to_vec()));
+        writing.await.expect("Writer task failed");
+        assert!(matches!(reader.read_message::<Vec<u8>>().await, Ok(None)));
+
+        assert!(shown.load(std::sync::atomic::Ordering::Relaxed) <= total + 1024);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_split() {
+        const MESSAGES: u32 = 100;
+
+        async fn send<W>(
+            mut writer: r#async::AsyncMessageWriter<W, codec::LengthDelimited>,
+            tag: u8,
+        ) where
+            W: futures_util::AsyncWrite + Unpin,
+        {
+            for i in 0..MESSAGES {
+                let mut message = vec![tag];
+                message.extend_from_slice(&i.to_be_bytes());
+                writer
+                    .write_message(message)
+                    .await
+                    .expect("Failed to write message");
+            }
+            // Shuts down the write direction, the peer's read half sees the end of the stream.
+            writer.close_write().await.expect("Failed to close writer");
+        }
+
+        async fn receive<R>(
+            mut reader: r#async::AsyncMessageReader<R, codec::LengthDelimited>,
+            tag: u8,
+        ) where
+            R: futures_util::AsyncRead + Unpin,
+        {
+            for i in 0..MESSAGES {
+                let message = reader
+                    .read_message::<Vec<u8>>()
+                    .await
+                    .expect("Failed to read message")
+                    .expect("Stream ended early");
+                assert_eq!(message[0], tag);
+                assert_eq!(message[1..], i.to_be_bytes());
+            }
+            assert!(matches!(reader.read_message::<Vec<u8>>().await, Ok(None)));
+        }
+
+        let (left, right) =
+            tokio::net::UnixStream::pair().expect("Failed to create UnixStream pair");
+        let codec = codec::LengthDelimited::new(codec::PrefixWidth::U32, codec::Endian::Big);
+        let (left_rx, left_tx) =
+            tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(left, codec.clone()).split();
+        let (right_rx, right_tx) =
+            tokio_crate::MessageTokio::new_rw::<_, _, Vec<u8>>(right, codec).split();
+
+        // Both directions run at once, each half in its own task.
+        let tasks = [
+            tokio::spawn(send(left_tx, b'L')),
+            tokio::spawn(send(right_tx, b'R')),
+            tokio::spawn(receive(left_rx, b'R')),
+            tokio::spawn(receive(right_rx, b'L')),
+        ];
+        for task in tasks {
+            task.await.expect("Task failed");
+        }
+    }
 }
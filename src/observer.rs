@@ -0,0 +1,40 @@
+//! Callbacks observing the traffic of a message I/O handler.
+
+/// Observes the messages a handler reads and writes, e.g. to log traffic or feed metrics
+/// without the crate depending on a logging framework.
+///
+/// Set one with `with_observer`. Every method does nothing by default, implement the ones of
+/// interest. They are called inline on the I/O path, so keep them cheap.
+pub trait Observer: Send + Sync {
+    /// Called after a message is decoded and consumed from the read buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The length of the message's frame on the wire.
+    fn on_message_read(&self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// Called once all of a message's frame has been written to the stream, which may still
+    /// need a flush. Frames waiting in the write buffer, e.g. below a write high-water mark, are
+    /// reported when the buffer is written. A frame whose write fails is reported only if a
+    /// later write finishes it: the async handler keeps its unwritten rest queued and writes it
+    /// ahead of the next message, the sync handler drops it, so it is never reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The length of the message's frame on the wire.
+    fn on_message_written(&self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// Called for every error the decoder reports, including errors the reader resynchronizes
+    /// past.
+    ///
+    /// # Arguments
+    ///
+    /// * `error`: The decoder's error message.
+    fn on_decode_error(&self, error: &str) {
+        let _ = error;
+    }
+}
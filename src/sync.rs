@@ -1,22 +1,86 @@
 //! Synchronous Message I/O handler using `std::io` traits.
-use std::io::{self, Read, Write};
+use std::{
+    io::{self, Read, Write},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    net::{Shutdown, TcpStream},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use bytes::{Buf, BytesMut};
+use bytes::BytesMut;
 
 use crate::{
-    decoder::{Decoder, DecoderResult},
+    constants::INITIAL_BUFFER_SIZE,
+    decoder::{Decoder, DecoderResult, MessageGuard, RefDecoder, decode_range, reset_and_resync},
     encoder::Encoder,
+    error::MsgIoError,
+    framer::reader::{FrameReader, ReadError, reader_settings},
+    observer::Observer,
 };
 
-const INITIAL_BUFFER_SIZE: usize = 1024;
-const TEMP_BUFFER_SIZE: usize = 1024;
+/// Streams whose blocking reads can time out, used by `MessageIo::read_message_deadline`.
+pub trait SetReadTimeout {
+    /// Sets the timeout of subsequent reads, `None` blocks indefinitely.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Returns the current read timeout.
+    fn read_timeout(&self) -> io::Result<Option<Duration>>;
+}
+
+impl SetReadTimeout for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::read_timeout(self)
+    }
+}
+
+#[cfg(unix)]
+impl SetReadTimeout for std::os::unix::net::UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        std::os::unix::net::UnixStream::read_timeout(self)
+    }
+}
+
+/// Streams whose write side can be shut down on its own, used by `MessageIo::shutdown`.
+pub trait ShutdownWrite {
+    /// Shuts down the write side of the stream, the peer reads the end of the stream while
+    /// reads from it keep working.
+    fn shutdown_write(&self) -> io::Result<()>;
+}
+
+impl ShutdownWrite for TcpStream {
+    fn shutdown_write(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Write)
+    }
+}
+
+#[cfg(unix)]
+impl ShutdownWrite for std::os::unix::net::UnixStream {
+    fn shutdown_write(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Write)
+    }
+}
 
 /// Message I/O handler using `std::io` traits.
 pub struct MessageIo<S, E, D> {
     stream: S,
     encoder: E,
-    decoder: D,
-    buffer: BytesMut,
+    reader: FrameReader<D>,
+    observer: Option<Arc<dyn Observer>>,
+    write_buffer: BytesMut,
+    /// Lengths of the frames in the write buffer, kept for the observer only.
+    queued_lens: Vec<usize>,
+    write_high_water_mark: usize,
+    bytes_written: u64,
+    messages_written: u64,
 }
 
 impl<S, E, D> MessageIo<S, E, D> {
@@ -35,15 +99,165 @@ impl<S, E, D> MessageIo<S, E, D> {
         Self {
             stream,
             encoder,
-            decoder,
-            buffer: BytesMut::with_capacity(INITIAL_BUFFER_SIZE),
+            reader: FrameReader::new(decoder, INITIAL_BUFFER_SIZE),
+            observer: None,
+            write_buffer: BytesMut::new(),
+            queued_lens: Vec::new(),
+            write_high_water_mark: 0,
+            bytes_written: 0,
+            messages_written: 0,
         }
     }
+
+    reader_settings!();
+
+    /// Sets an observer called as messages are read and written and on decode errors.
+    ///
+    /// By default there is none and nothing is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `observer`: The observer, see `Observer`.
+    ///
+    /// # Returns
+    ///
+    /// The instance with the observer set.
+    pub fn with_observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Sets how many encoded bytes may pile up before they are written to the stream.
+    ///
+    /// Messages are encoded into the write buffer, which is only written out once it holds at
+    /// least this many bytes, or on `flush`. Coalescing many small messages this way saves a
+    /// write call per message. Bytes still buffered when the handler is dropped are lost, so
+    /// flush before dropping it. Defaults to 0, every message is written straight away.
+    ///
+    /// # Arguments
+    ///
+    /// * `high_water_mark`: The buffered byte count that triggers a write.
+    ///
+    /// # Returns
+    ///
+    /// The instance with the threshold applied.
+    pub fn with_write_high_water_mark(mut self, high_water_mark: usize) -> Self {
+        self.write_high_water_mark = high_water_mark;
+        self
+    }
+
+    /// Number of bytes written to the stream so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Number of messages encoded for writing so far.
+    pub fn messages_written(&self) -> u64 {
+        self.messages_written
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    ///
+    /// Reading or writing the stream directly bypasses the handler's buffers. Bytes already
+    /// read ahead stay in the read buffer and encoded bytes may still be queued for writing, so
+    /// raw I/O in the middle of a frame, or before a flush, corrupts the framing.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns a mutable reference to the encoder, e.g. to change its settings after a
+    /// handshake.
+    pub fn encoder_mut(&mut self) -> &mut E {
+        &mut self.encoder
+    }
+
+    /// Returns a mutable reference to the decoder, e.g. to change its settings after a
+    /// handshake.
+    ///
+    /// Bytes read ahead stay buffered and are decoded by the reconfigured decoder, so only
+    /// change it between frames. A decoder holding a partial frame through `ContinueFrom`
+    /// must not be changed at all.
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.reader.decoder
+    }
+
+    /// Replaces the encoder, messages encoded afterwards use the new one.
+    ///
+    /// Bytes already encoded and still buffered for writing are sent unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: The new encoder.
+    pub fn set_encoder(&mut self, encoder: E) {
+        self.encoder = encoder;
+    }
+
+    /// Replaces the decoder, keeping the bytes read ahead for it to decode.
+    ///
+    /// Only swap decoders between frames, see `decoder_mut`.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder`: The new decoder.
+    pub fn set_decoder(&mut self, decoder: D) {
+        self.reader.set_decoder(decoder);
+    }
+
+    /// Consumes the handler, returning the stream and the bytes read ahead but not yet decoded.
+    ///
+    /// Use this to hand the stream to another protocol without losing prefetched data,
+    /// `from_parts` builds the next handler from them. Bytes the decoder already took through
+    /// `ContinueFrom` are part of its state and not returned.
+    ///
+    /// # Returns
+    ///
+    /// The stream and the unconsumed read buffer.
+    pub fn into_parts(self) -> (S, BytesMut) {
+        (self.stream, self.reader.buffer)
+    }
+
+    /// Creates a handler over a stream some bytes were already read from, e.g. the parts
+    /// returned by `into_parts` after a protocol upgrade.
+    ///
+    /// `buffer` is decoded before anything more is read from the stream, so handing a stream
+    /// from one handler to the next loses no data. Other settings start at their defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: The stream to keep reading from and writing to.
+    /// * `buffer`: The bytes read from the stream but not yet decoded.
+    /// * `encoder`: An encoder that implements the `Encoder` trait, `()` for a reader.
+    /// * `decoder`: A decoder that implements the `Decoder` trait, `()` for a writer.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MessageIo` seeded with `buffer`.
+    pub fn from_parts(stream: S, buffer: BytesMut, encoder: E, decoder: D) -> Self {
+        let mut io = Self::new(stream, encoder, decoder);
+        io.reader.buffer = buffer;
+        io
+    }
+
+    /// Consumes the handler, returning the stream.
+    ///
+    /// Buffered bytes are discarded, use `into_parts` to keep data read ahead, and flush
+    /// before calling this to send bytes still queued for writing.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
 }
 
 impl<S, ED> MessageIo<S, ED, ED> {
     /// Creates a new MessageIo instance for reading and writing with the given stream.
     ///
+    /// The reader and the writer each get their own clone of `enc_dec`, wrap it in
+    /// `codec::Shared` for both directions to share one codec state.
+    ///
     /// # Type Parameters
     ///
     /// * `EDT`: The type of the input/out data to be encoded/decoded.
@@ -63,6 +277,26 @@ impl<S, ED> MessageIo<S, ED, ED> {
     {
         Self::new(stream, enc_dec.clone(), enc_dec)
     }
+
+    /// Shuts down the write direction while keeping the read direction open, so the
+    /// connection ends gracefully on both sides.
+    ///
+    /// The steps happen in this order: frames still queued in the write buffer are written,
+    /// the stream is flushed, then its write side is shut down and the peer reads the end of
+    /// the stream once it has read every frame sent before. Reads are not affected, keep
+    /// calling `read_message` to drain what the peer still sends until it returns `Ok(None)`.
+    /// Nothing may be written afterwards.
+    ///
+    /// # Returns
+    ///
+    /// The result of the shutdown operation.
+    pub fn shutdown(&mut self) -> io::Result<()>
+    where
+        S: Write + ShutdownWrite,
+    {
+        self.flush()?;
+        self.stream.shutdown_write()
+    }
 }
 
 impl<S, D> MessageIo<S, (), D> {
@@ -85,79 +319,576 @@ impl<S, D> MessageIo<S, (), D> {
         S: Read,
         D: Decoder<DT>,
     {
-        Self {
-            stream,
-            encoder: (),
-            decoder,
-            buffer: BytesMut::with_capacity(INITIAL_BUFFER_SIZE),
-        }
+        Self::new(stream, (), decoder)
+    }
+}
+
+impl<S, E> MessageIo<S, E, ()> {
+    /// Creates a new MessageIo instance for writing with the given stream.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `ET`: The type of the input data to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: An synchronous stream that implements `Write`.
+    /// * `encoder`: An encoder that implements the `Encoder` trait.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MessageIo` for writing.
+    pub fn new_writer<ET>(stream: S, encoder: E) -> Self
+    where
+        S: Write,
+        E: Encoder<ET>,
+    {
+        Self::new(stream, encoder, ())
     }
+}
 
+impl<S, E, D> MessageIo<S, E, D> {
     /// Reads a message from the stream using the specified decoder.
     ///
+    /// Data already buffered from a previous read is decoded first, the stream is only read
+    /// when the decoder needs more data.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// A result containing an optional message of type `M`. `Ok(None)` indicates a clean end of
+    /// the stream, if the stream ends while a partial frame is buffered the error is
+    /// `MsgIoError::UnexpectedEof`.
+    pub fn read_message<M>(&mut self) -> Result<Option<M>, MsgIoError>
+    where
+        D: Decoder<M>,
+        S: Read,
+    {
+        Ok(self.next_message(true, |_| Ok(()))?)
+    }
+
+    /// Reads a message from a non-blocking stream without waiting for one to arrive.
+    ///
+    /// Meant for streams driven by `poll` or `epoll` loops. A read that would block ends the
+    /// call with `Ok(None)`, meaning no complete message is available yet: bytes received so
+    /// far stay buffered and the call can be repeated once the stream is readable again.
+    ///
+    /// Unlike `read_message`, `Ok(None)` never means the end of the stream. A clean end of the
+    /// stream is reported as an error of kind `io::ErrorKind::UnexpectedEof`, like an end in the
+    /// middle of a frame. Once the limit set with `with_message_limit` is reached, the error is
+    /// of kind `io::ErrorKind::QuotaExceeded` instead, so a limit stop can be told apart from a
+    /// dropped peer.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// The result of the read operation, which is either:
+    /// - `Ok(Some(M))`: A successfully decoded message.
+    /// - `Ok(None)`: No complete message yet, the stream would block.
+    /// - `Err(io::Error)`: The stream ended, the message limit was reached, or an error occurred
+    ///   during reading or decoding.
+    pub fn try_read_message<M>(&mut self) -> io::Result<Option<M>>
+    where
+        D: Decoder<M>,
+        S: Read,
+    {
+        match self.next_message(true, |_| Ok(())) {
+            Ok(Some(msg)) => Ok(Some(msg)),
+            Ok(None) if self.reader.message_limit_reached() => Err(io::Error::new(
+                io::ErrorKind::QuotaExceeded,
+                "Message limit reached",
+            )),
+            Ok(None) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Stream ended")),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads a message from the stream, giving up once `deadline` has passed.
+    ///
+    /// Every read from the stream is given the time left until the deadline as its timeout,
+    /// the stream's own read timeout is restored afterwards. Bytes received before the deadline
+    /// stay buffered, so a later read picks up the frame where this one left off.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type to be decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline`: When to stop waiting for a complete message.
+    ///
+    /// # Returns
+    ///
+    /// The result of `read_message`, or an error of kind `io::ErrorKind::TimedOut` if no
+    /// message was decoded in time.
+    pub fn read_message_deadline<M>(&mut self, deadline: Instant) -> io::Result<Option<M>>
+    where
+        D: Decoder<M>,
+        S: Read + SetReadTimeout,
+    {
+        let previous = self.stream.read_timeout()?;
+        let result = self.next_message(true, |stream| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            stream.set_read_timeout(Some(remaining))
+        });
+        self.stream.set_read_timeout(previous)?;
+        result.map_err(|e| match e.kind() {
+            // Unix reports an expired read timeout as `WouldBlock`.
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Deadline passed waiting for a message",
+            ),
+            _ => e,
+        })
+    }
+
+    /// Reads every message that is ready, appending them to `out`.
+    ///
+    /// Waits for the first message exactly like `read_message`, then keeps decoding frames that
+    /// are already buffered without reading from the stream again, so a burst of small frames
+    /// arriving in one read is handed over in one call. Messages decoded before an error are
+    /// left in `out`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the messages to be decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `out`: The vector to append the messages to.
+    ///
+    /// # Returns
+    ///
+    /// The number of messages appended, `0` indicating a clean end of the stream.
+    pub fn read_messages<M>(&mut self, out: &mut Vec<M>) -> io::Result<usize>
+    where
+        D: Decoder<M>,
+        S: Read,
+    {
+        let Some(first) = self.read_message()? else {
+            return Ok(0);
+        };
+        out.push(first);
+        let mut count = 1;
+        while let Some(msg) = self.reader.decode(true, self.observer.as_deref())? {
+            out.push(msg);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Decodes the next message without consuming it, the following `read_message` or
+    /// `peek_message` returns the same message again.
+    ///
+    /// If no complete frame is buffered yet the stream is read until one is, exactly like
+    /// `read_message`. The decoder runs again on the same bytes when the message is read, so
+    /// decoders that take bytes with `ContinueFrom` or keep state past `Done` can't be peeked.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// The same as `read_message`.
+    pub fn peek_message<M>(&mut self) -> io::Result<Option<M>>
+    where
+        D: Decoder<M>,
+        S: Read,
+    {
+        self.next_message(false, |_| Ok(()))
+    }
+
+    /// Whether a complete message is already buffered, so the next `read_message` returns
+    /// without reading from the stream.
+    ///
+    /// The decoder runs on the buffered bytes without consuming them, like `peek_message`
+    /// without the read. A `poll` loop calls it to decide whether to wait for the stream to
+    /// become readable first. Decode errors are returned like from `read_message`, and with
+    /// resync enabled the bytes before the next frame boundary are dropped. As with
+    /// `peek_message`, decoders that take bytes with `ContinueFrom` or keep state past `Done`
+    /// can't be checked.
+    ///
     /// # Type Parameters
     ///
     /// * `M`: The message type to be decoded.
     ///
     /// # Returns
     ///
-    /// A result containing an optional message of type `M`.
-    pub fn read_message<M>(&mut self) -> io::Result<Option<M>>
+    /// `true` if a message is buffered, `false` if the decoder needs more data.
+    pub fn has_buffered_message<M>(&mut self) -> io::Result<bool>
+    where
+        D: Decoder<M>,
+    {
+        Ok(self
+            .reader
+            .decode::<M>(false, self.observer.as_deref())?
+            .is_some())
+    }
+
+    /// Drops the next frame without keeping its message, e.g. one of a type `peek_message`
+    /// showed isn't wanted.
+    ///
+    /// The frame is decoded to find where it ends and the message is dropped straight away. If
+    /// no complete frame is buffered yet the stream is read until one is, exactly like
+    /// `read_message`, and the frame counts toward `messages_read` like a read one.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type of the decoder.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes the frame took up on the wire, or `None` at a clean end of the
+    /// stream like `read_message`.
+    pub fn skip_message<M>(&mut self) -> io::Result<Option<usize>>
     where
         D: Decoder<M>,
         S: Read,
+    {
+        self.next_message_by(
+            |reader, observer| {
+                // Bytes taken with `ContinueFrom` before the frame is done count toward it.
+                let consumed = reader.consumed();
+                let used = reader.decode_by(
+                    true,
+                    observer,
+                    D::min_frame_hint,
+                    |decoder, buf| match decoder.decode(buf) {
+                        DecoderResult::Done(_, used) => DecoderResult::Done(used, used),
+                        result => result.map(|_| 0),
+                    },
+                    reset_and_resync::<M, D>,
+                )?;
+                Ok(used.map(|used| consumed + used))
+            },
+            |_| Ok(()),
+        )
+    }
+
+    /// Reads a frame and hands its payload to `f` straight from the read buffer, without
+    /// copying it into an owned message.
+    ///
+    /// The frame's bytes are dropped from the buffer once `f` returns, so `f` can parse the
+    /// payload in place and return whatever it extracted.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `R`: The type returned by `f`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: Called with the payload of the next frame.
+    ///
+    /// # Returns
+    ///
+    /// The value returned by `f`, or `None` at a clean end of the stream like `read_message`.
+    pub fn read_message_with<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> io::Result<Option<R>>
+    where
+        D: RefDecoder,
+        S: Read,
+    {
+        let mut f = Some(f);
+        self.next_message_by(
+            |reader, observer| {
+                reader.decode_by(
+                    true,
+                    observer,
+                    |_| 0,
+                    |decoder, data| {
+                        decoder.decode_ref(data).map(|frame| {
+                            let f = f.take().expect("Frame handed over more than once");
+                            f(&frame)
+                        })
+                    },
+                    D::resync,
+                )
+            },
+            |_| Ok(()),
+        )
+    }
+
+    /// Reads a frame and returns its payload still in the read buffer, behind a guard that
+    /// drops the frame's bytes from the buffer once it is dropped.
+    ///
+    /// Like `read_message_with` without the closure: the payload can be parsed in place and
+    /// kept borrowed across other work, e.g. by a parser keeping its arena between frames. The
+    /// guard borrows the reader, so the next read waits until the payload is let go.
+    ///
+    /// # Returns
+    ///
+    /// The guard of the frame's payload, or `None` at a clean end of the stream like
+    /// `read_message`.
+    pub fn read_message_arena(&mut self) -> io::Result<Option<MessageGuard<'_>>>
+    where
+        D: RefDecoder,
+        S: Read,
+    {
+        let frame = self.next_message_by(
+            |reader, observer| {
+                reader.decode_by(
+                    false,
+                    observer,
+                    |_| 0,
+                    |d, buf| decode_range(d, buf),
+                    D::resync,
+                )
+            },
+            |_| Ok(()),
+        )?;
+        match frame {
+            Some((payload, used)) => Ok(Some(self.reader.message_guard(
+                payload,
+                used,
+                self.observer.as_deref(),
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a message's bytes into `out`, replacing its contents, so one buffer can be reused
+    /// for every message instead of allocating a `Vec` per frame.
+    ///
+    /// Frames are decoded with `Decoder::decode_into`, which copies the payload into `out` for
+    /// the byte framings of `codec`. Once `out` has grown to the largest payload, reading
+    /// further messages doesn't allocate for them.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The message type of the decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `out`: The buffer receiving the message's bytes.
+    ///
+    /// # Returns
+    ///
+    /// `true` once a message was read into `out`, `false` at a clean end of the stream like
+    /// `read_message` returning `None`.
+    pub fn read_message_into<M>(&mut self, out: &mut Vec<u8>) -> io::Result<bool>
+    where
+        D: Decoder<M>,
+        M: Into<Vec<u8>>,
+        S: Read,
+    {
+        let message = self.next_message_by(
+            |reader, observer| {
+                reader.decode_by(
+                    true,
+                    observer,
+                    D::min_frame_hint,
+                    |decoder, buf| decoder.decode_into(buf, out),
+                    reset_and_resync::<M, D>,
+                )
+            },
+            |_| Ok(()),
+        )?;
+        Ok(message.is_some())
+    }
+
+    /// Returns an iterator reading messages until the end of the stream.
+    ///
+    /// Each item is the result of a `read_message` call, frames already buffered are yielded
+    /// before the stream is read again. Iteration ends at a clean end of the stream, errors are
+    /// yielded as items and the caller decides whether to keep going, e.g. with resync enabled.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the messages to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// A `MessageIter` yielding `io::Result<M>` items.
+    pub fn iter<M>(&mut self) -> MessageIter<'_, S, E, D, M>
+    where
+        D: Decoder<M>,
+        S: Read,
+    {
+        MessageIter {
+            io: self,
+            _message: PhantomData,
+        }
+    }
+
+    /// Decodes the next message, dropping its bytes from the buffer if `consume` is set.
+    /// `before_read` runs ahead of every read from the stream.
+    fn next_message<M>(
+        &mut self,
+        consume: bool,
+        before_read: impl FnMut(&mut S) -> io::Result<()>,
+    ) -> io::Result<Option<M>>
+    where
+        D: Decoder<M>,
+        S: Read,
+    {
+        self.next_message_by(
+            |reader, observer| reader.decode(consume, observer),
+            before_read,
+        )
+    }
+
+    /// Reads from the stream until `decode` returns a message from the buffered data.
+    fn next_message_by<M>(
+        &mut self,
+        mut decode: impl FnMut(
+            &mut FrameReader<D>,
+            Option<&dyn Observer>,
+        ) -> Result<Option<M>, ReadError>,
+        mut before_read: impl FnMut(&mut S) -> io::Result<()>,
+    ) -> io::Result<Option<M>>
+    where
+        S: Read,
     {
         loop {
-            let mut temp = [0u8; TEMP_BUFFER_SIZE];
-            match self.stream.read(&mut temp)? {
-                0 => return Ok(None),
-                n => {
-                    self.buffer.extend_from_slice(&temp[..n]);
-                    match self.decoder.decode(&self.buffer) {
-                        DecoderResult::Continue => continue,
-                        DecoderResult::Done(msg, used) => {
-                            self.buffer.advance(used);
-                            return Ok(Some(msg));
-                        }
-                        DecoderResult::Error(e) => {
-                            return Err(io::Error::new(io::ErrorKind::InvalidData, e));
-                        }
-                    }
+            if self.reader.message_limit_reached() {
+                return Ok(None);
+            }
+            if let Some(msg) = decode(&mut self.reader, self.observer.as_deref())? {
+                return Ok(Some(msg));
+            }
+            before_read(&mut self.stream)?;
+            match self.read_chunk()? {
+                0 if self.reader.at_frame_boundary() => return Ok(None),
+                0 => {
+                    return Err(MsgIoError::UnexpectedEof.into());
                 }
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads up to `read_chunk_size` bytes from the stream straight into the buffer's spare
+    /// capacity, retrying reads that were interrupted.
+    fn read_chunk(&mut self) -> io::Result<usize>
+    where
+        S: Read,
+    {
+        let spare = self.reader.read_spare();
+        let len = spare.len();
+        spare.fill(MaybeUninit::new(0));
+        // SAFETY: every byte of `spare` was initialized just above.
+        let dst = unsafe { &mut *(spare as *mut [MaybeUninit<u8>] as *mut [u8]) };
+        // Interrupted reads are retried, like `Read::read_exact` does.
+        let n = loop {
+            match self.stream.read(dst) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                result => break result?,
             }
+        };
+        if n > len {
+            return Err(io::Error::other(
+                "Stream reported reading more bytes than requested",
+            ));
         }
+        // SAFETY: the first `n <= len` bytes past the end were initialized and filled above.
+        unsafe { self.reader.advance_read(n) };
+        Ok(n)
     }
 }
 
-impl<S, E> MessageIo<S, E, ()> {
-    /// Creates a new MessageIo instance for writing with the given stream.
+impl<S, E, D> MessageIo<S, E, D> {
+    /// Writes a message to the stream using the specified encoder.
+    ///
+    /// The stream is not flushed, use `flush` or `write_message_flush` when writing to a
+    /// buffered stream. With a write high-water mark set, the message may also wait in the
+    /// write buffer until `flush`.
     ///
     /// # Type Parameters
     ///
-    /// * `ET`: The type of the input data to be encoded.
+    /// * `M`: The type of the message to be encoded.
+    ///
+    /// # Returns
+    ///
+    /// The result of the write operation, which is either:
+    /// - `Ok(usize)`: The message was successfully written, taking up this many bytes on the
+    ///   wire including its framing.
+    /// - `Err(MsgIoError)`: An error occurred during encoding or writing, `MsgIoError::Encode`
+    ///   if the encoder rejected the message.
+    pub fn write_message<M>(&mut self, msg: M) -> Result<usize, MsgIoError>
+    where
+        E: Encoder<M>,
+        S: Write,
+    {
+        let len = self.encode_into_buffer(msg)?;
+        self.messages_written += 1;
+        self.write_buffer_if_full()?;
+        Ok(len)
+    }
+
+    /// Encodes a message with the writer's encoder and returns the frame, without writing it.
+    ///
+    /// For tracing, tests, or sending through a transport other than the stream. The encoder is
+    /// the one `write_message` uses, so a stateful encoder, e.g. one numbering its frames,
+    /// advances as if the message had been written. The stream, the write buffer and the
+    /// written counters are left untouched.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be encoded.
     ///
     /// # Arguments
     ///
-    /// * `stream`: An synchronous stream that implements `Write`.
-    /// * `encoder`: An encoder that implements the `Encoder` trait.
+    /// * `msg`: The message to encode.
     ///
     /// # Returns
     ///
-    /// A new instance of `MessageIo` for writing.
-    pub fn new_writer<ET>(stream: S, encoder: E) -> Self
+    /// The encoded frame, including its framing.
+    pub fn encode_message<M>(&mut self, msg: M) -> io::Result<Vec<u8>>
+    where
+        E: Encoder<M>,
+    {
+        self.encoder
+            .encode(msg)
+            .map_err(|e| MsgIoError::Encode(e.into()).into())
+    }
+
+    /// Writes several messages to the stream with a single `write_all`.
+    ///
+    /// Every message is encoded before anything is written, so if any message fails to
+    /// encode nothing reaches the stream.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the messages to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `msgs`: The messages to write, in order.
+    ///
+    /// # Returns
+    ///
+    /// The result of the write operation, which is either:
+    /// - `Ok(())`: All messages were successfully written.
+    /// - `Err(io::Error)`: An error occurred during encoding or writing.
+    pub fn write_messages<'m, M>(&mut self, msgs: &'m [M]) -> io::Result<()>
     where
+        E: Encoder<&'m M>,
         S: Write,
-        E: Encoder<ET>,
     {
-        Self {
-            stream,
-            encoder,
-            decoder: (),
-            buffer: BytesMut::with_capacity(INITIAL_BUFFER_SIZE),
+        let start = self.write_buffer.len();
+        let queued = self.queued_lens.len();
+        for msg in msgs {
+            if let Err(e) = self.encode_into_buffer(msg) {
+                self.write_buffer.truncate(start);
+                self.queued_lens.truncate(queued);
+                return Err(e);
+            }
         }
+        self.messages_written += msgs.len() as u64;
+        self.write_buffer_if_full()
     }
 
-    /// Writes a message to the stream using the specified encoder.
+    /// Writes a message to the stream and then flushes it.
     ///
     /// # Type Parameters
     ///
@@ -166,15 +897,167 @@ impl<S, E> MessageIo<S, E, ()> {
     /// # Returns
     ///
     /// The result of the write operation, which is either:
-    /// - `Ok(())`: The message was successfully written.
-    /// - `Err(io::Error)`: An error occurred during encoding or writing.
-    pub fn write_message<M>(&mut self, msg: M) -> io::Result<()>
+    /// - `Ok(usize)`: The message was successfully written and flushed, taking up this many
+    ///   bytes on the wire including its framing.
+    /// - `Err(io::Error)`: An error occurred during encoding, writing, or flushing.
+    pub fn write_message_flush<M>(&mut self, msg: M) -> io::Result<usize>
+    where
+        E: Encoder<M>,
+        S: Write,
+    {
+        let len = self.write_message(msg)?;
+        self.flush()?;
+        Ok(len)
+    }
+
+    /// Writes out any buffered bytes and flushes the underlying stream.
+    ///
+    /// # Returns
+    ///
+    /// The result of the flush operation.
+    pub fn flush(&mut self) -> io::Result<()>
+    where
+        S: Write,
+    {
+        self.write_buffer_all()?;
+        self.stream.flush()
+    }
+
+    /// Encodes a message onto the end of the write buffer, leaving the buffer untouched if the
+    /// encoder fails. Returns the encoded length, which is queued for the observer, if any.
+    fn encode_into_buffer<M>(&mut self, msg: M) -> io::Result<usize>
     where
         E: Encoder<M>,
+    {
+        if let Some(len) = self.encoder.size_hint(&msg) {
+            self.write_buffer.reserve(len);
+        }
+        let start = self.write_buffer.len();
+        self.encoder
+            .encode_into(msg, &mut self.write_buffer)
+            .map_err(|e| {
+                self.write_buffer.truncate(start);
+                MsgIoError::Encode(e.into())
+            })?;
+        let len = self.write_buffer.len() - start;
+        if self.observer.is_some() {
+            self.queued_lens.push(len);
+        }
+        Ok(len)
+    }
+
+    /// Writes the write buffer to the stream once it reaches the high-water mark.
+    fn write_buffer_if_full(&mut self) -> io::Result<()>
+    where
         S: Write,
     {
-        let encoded = self.encoder.encode(msg).map_err(io::Error::other)?;
-        self.stream.write_all(&encoded)?;
+        if self.write_buffer.len() >= self.write_high_water_mark {
+            self.write_buffer_all()?;
+        }
         Ok(())
     }
+
+    /// Writes the whole write buffer to the stream, then reports its frames to the observer, if
+    /// any. The buffer is emptied either way, after an error it is unknown how much of it
+    /// reached the stream and no frame is reported.
+    fn write_buffer_all(&mut self) -> io::Result<()>
+    where
+        S: Write,
+    {
+        let result = self.stream.write_all(&self.write_buffer);
+        if result.is_ok() {
+            self.bytes_written += self.write_buffer.len() as u64;
+            if let Some(observer) = &self.observer {
+                self.queued_lens
+                    .iter()
+                    .for_each(|&len| observer.on_message_written(len));
+            }
+        }
+        self.write_buffer.clear();
+        self.queued_lens.clear();
+        result
+    }
+}
+
+/// An `Iterator` of decoded messages, created by `MessageIo::iter`.
+pub struct MessageIter<'a, S, E, D, M> {
+    io: &'a mut MessageIo<S, E, D>,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<S, E, D, M> Iterator for MessageIter<'_, S, E, D, M>
+where
+    S: Read,
+    D: Decoder<M>,
+{
+    type Item = io::Result<M>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.io.read_message().map_err(Into::into).transpose()
+    }
+}
+
+/// Reads a single message from `stream`, then hands the stream back, e.g. for a handshake frame
+/// sent before the protocol switches modes.
+///
+/// The temporary reader may read past the end of the message, so the bytes read ahead are
+/// returned too. Pass them to `MessageIo::from_parts` along with the stream to carry on with
+/// the next stage without losing data.
+///
+/// # Type Parameters
+///
+/// * `M`: The type of the message to be decoded.
+///
+/// # Arguments
+///
+/// * `stream`: A synchronous stream that implements `Read`.
+/// * `decoder`: A decoder that implements the `Decoder` trait.
+///
+/// # Returns
+///
+/// The result of the read operation, which is either:
+/// - `Ok((Option<M>, S, BytesMut))`: The message, or `None` at the end of the stream, along
+///   with the stream and the bytes read past the message.
+/// - `Err(io::Error)`: An error occurred during reading or decoding.
+pub fn read_one<S, D, M>(stream: S, decoder: D) -> io::Result<(Option<M>, S, BytesMut)>
+where
+    S: Read,
+    D: Decoder<M>,
+{
+    let mut reader = MessageIo::new_reader(stream, decoder);
+    let message = reader.read_message()?;
+    let (stream, buffer) = reader.into_parts();
+    Ok((message, stream, buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::{Endian, LengthDelimited, PrefixWidth},
+        constants::BUFFER_SHRINK_FACTOR,
+    };
+
+    #[test]
+    fn test_buffer_shrinks_after_large_frame() {
+        let mut codec = LengthDelimited::new(PrefixWidth::U32, Endian::Big);
+        let mut framed = codec.encode(vec![0xA5; 10 * 1024 * 1024]).unwrap();
+        for _ in 0..1000 {
+            framed.extend(codec.encode(b"tiny").unwrap());
+        }
+        let mut reader = MessageIo::new_reader(io::Cursor::new(framed), codec);
+
+        let large = reader
+            .read_message::<Vec<u8>>()
+            .expect("Failed to read message")
+            .expect("No message received");
+        assert_eq!(large.len(), 10 * 1024 * 1024);
+        let mut tiny = 0;
+        while let Some(message) = reader.read_message::<Vec<u8>>().expect("Failed to read") {
+            assert_eq!(message, b"tiny");
+            tiny += 1;
+        }
+        assert_eq!(tiny, 1000);
+        assert!(reader.reader.buffer.capacity() <= BUFFER_SHRINK_FACTOR * INITIAL_BUFFER_SIZE);
+    }
 }
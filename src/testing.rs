@@ -0,0 +1,237 @@
+//! In-memory streams and helpers for testing codecs without touching the OS.
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+use bytes::{Buf, BytesMut};
+use futures_util::{AsyncRead, AsyncWrite};
+
+use crate::{r#async::AsyncMessageIo, decoder::Decoder, encoder::Encoder};
+
+/// One direction of a duplex, the bytes written by one end and not yet read by the other.
+#[derive(Default)]
+struct Pipe {
+    buffer: BytesMut,
+    closed: bool,
+    read_waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// One end of an in-memory duplex stream, created by `duplex`.
+///
+/// Bytes written to one end are read from the other. Closing or dropping an end signals end of
+/// stream to the other end once it has read everything written before, and makes writes from
+/// the other end fail with `BrokenPipe`. Writes never block, the pipe grows as needed.
+pub struct DuplexStream {
+    read: Arc<Mutex<Pipe>>,
+    write: Arc<Mutex<Pipe>>,
+}
+
+/// Creates two connected in-memory duplex endpoints.
+///
+/// # Returns
+///
+/// A pair of `DuplexStream`, each reading what the other writes.
+pub fn duplex() -> (DuplexStream, DuplexStream) {
+    let a_to_b = Arc::new(Mutex::new(Pipe::default()));
+    let b_to_a = Arc::new(Mutex::new(Pipe::default()));
+    let a = DuplexStream {
+        read: b_to_a.clone(),
+        write: a_to_b.clone(),
+    };
+    let b = DuplexStream {
+        read: a_to_b,
+        write: b_to_a,
+    };
+    (a, b)
+}
+
+fn lock(pipe: &Mutex<Pipe>) -> MutexGuard<'_, Pipe> {
+    pipe.lock().expect("Duplex pipe lock poisoned")
+}
+
+impl AsyncRead for DuplexStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = lock(&self.read);
+        if !pipe.buffer.is_empty() {
+            let len = buf.len().min(pipe.buffer.len());
+            buf[..len].copy_from_slice(&pipe.buffer[..len]);
+            pipe.buffer.advance(len);
+            return Poll::Ready(Ok(len));
+        }
+        if pipe.closed || buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        pipe.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for DuplexStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = lock(&self.write);
+        if pipe.closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        pipe.buffer.extend_from_slice(buf);
+        if let Some(waker) = pipe.read_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        lock(&self.write).close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        lock(&self.read).close();
+        lock(&self.write).close();
+    }
+}
+
+/// Creates a writer and a reader connected through an in-memory duplex.
+///
+/// # Type Parameters
+///
+/// * `E`: The type of the encoder.
+/// * `D`: The type of the decoder.
+/// * `T`: The type of the messages to be encoded and decoded.
+///
+/// # Arguments
+///
+/// * `encoder`: The encoder of the writer.
+/// * `decoder`: The decoder of the reader.
+///
+/// # Returns
+///
+/// The writer and the reader, messages written to the first are read from the second.
+pub fn message_pair<E, D, T>(
+    encoder: E,
+    decoder: D,
+) -> (
+    AsyncMessageIo<DuplexStream, E, ()>,
+    AsyncMessageIo<DuplexStream, (), D>,
+)
+where
+    E: Encoder<T>,
+    D: Decoder<T>,
+{
+    let (a, b) = duplex();
+    (
+        AsyncMessageIo::new_writer(a, encoder),
+        AsyncMessageIo::new_reader(b, decoder),
+    )
+}
+
+/// Creates two read and write handlers connected through an in-memory duplex.
+///
+/// # Type Parameters
+///
+/// * `ED`: The type of the encoder/decoder.
+/// * `EDT`: The type of the messages to be encoded and decoded.
+///
+/// # Arguments
+///
+/// * `enc_dec`: The encoder/decoder, cloned for each direction of both handlers.
+///
+/// # Returns
+///
+/// Two handlers, each reading the messages the other writes.
+pub fn rw_pair<ED, EDT>(
+    enc_dec: ED,
+) -> (
+    AsyncMessageIo<DuplexStream, ED, ED>,
+    AsyncMessageIo<DuplexStream, ED, ED>,
+)
+where
+    ED: Encoder<EDT> + Decoder<EDT> + Clone,
+{
+    let (a, b) = duplex();
+    (
+        AsyncMessageIo::new_rw(a, enc_dec.clone()),
+        AsyncMessageIo::new_rw(b, enc_dec),
+    )
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::codec::{Cobs, Endian, LengthDelimited, PrefixWidth};
+    use futures_util::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_duplex_bytes() {
+        let (mut a, mut b) = duplex();
+        a.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        b.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        b.write_all(b"pong").await.unwrap();
+        drop(b);
+        let mut rest = Vec::new();
+        a.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"pong");
+        let err = a.write_all(b"gone").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[tokio::test]
+    async fn test_message_pair_round_trip() {
+        let (mut writer, mut reader) = message_pair(Cobs::new(), Cobs::new());
+        let message = b"hello over memory".to_vec();
+        writer.write_message(&message).await.unwrap();
+        let received: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(received, Some(message));
+
+        drop(writer);
+        let received: Option<Vec<u8>> = reader.read_message().await.unwrap();
+        assert_eq!(received, None);
+    }
+
+    #[tokio::test]
+    async fn test_rw_pair_concurrent() {
+        let codec = LengthDelimited::new(PrefixWidth::U16, Endian::Big);
+        let (mut a, mut b) = rw_pair::<_, Vec<u8>>(codec);
+        let echo = async {
+            while let Some(message) = b.read_message::<Vec<u8>>().await.unwrap() {
+                b.write_message(message.to_ascii_uppercase()).await.unwrap();
+            }
+        };
+        let client = async {
+            a.write_message(b"hello").await.unwrap();
+            let reply: Option<Vec<u8>> = a.read_message().await.unwrap();
+            a.close_write().await.unwrap();
+            reply
+        };
+        let (_, reply) = futures_util::join!(echo, client);
+        assert_eq!(reply.as_deref(), Some(&b"HELLO"[..]));
+    }
+}
@@ -1,8 +1,37 @@
 //! Asynchronous Message I/O handler using `tokio` traits.
-use tokio::io::{AsyncRead as TokioAsyncRead, AsyncWrite as TokioAsyncWrite};
-use tokio_util::compat::{Compat, TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+use std::{
+    error::Error,
+    io::{self, Read, Write},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
 
-use crate::{r#async::AsyncMessageIo, decoder::Decoder, encoder::Encoder};
+use bytes::BytesMut;
+use futures_util::{AsyncRead, AsyncWrite};
+use tokio::{
+    io::{AsyncRead as TokioAsyncRead, AsyncWrite as TokioAsyncWrite, ReadBuf},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Mutex, mpsc, oneshot},
+    task::JoinHandle,
+    time,
+};
+use tokio_util::{
+    codec as tokio_codec,
+    compat::{Compat, TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt},
+};
+
+use crate::{
+    r#async::AsyncMessageIo,
+    constants::MAX_READ_AHEAD,
+    decoder::{Decoder, DecoderResult},
+    encoder::Encoder,
+    error::DecodeError,
+    framer::decode_step,
+};
 
 // A wrapper around the asynchronous MessageIo to work with Tokio streams.
 pub struct MessageTokio;
@@ -10,6 +39,9 @@ pub struct MessageTokio;
 impl MessageTokio {
     /// Creates a new MessageIo instance (Read & Write) with the given Tokio stream.
     ///
+    /// Messages are read through the stream's tokio `AsyncRead`, straight into the read buffer,
+    /// without the zeroing `Compat`'s `AsyncRead` needs.
+    ///
     /// # Type Parameters
     ///
     /// * `S`: The type of the Tokio stream.
@@ -29,11 +61,16 @@ impl MessageTokio {
         S: TokioAsyncRead + TokioAsyncWrite + Unpin,
         ED: Encoder<EDT> + Decoder<EDT> + Clone,
     {
-        AsyncMessageIo::new_rw(stream.compat_write(), enc_dec)
+        let mut io = AsyncMessageIo::new_rw(stream.compat_write(), enc_dec);
+        io.set_poll_read(poll_read_native::<S>);
+        io
     }
 
     /// Creates a new MessageIo instance for reading with the given Tokio stream.
     ///
+    /// Messages are read through the stream's tokio `AsyncRead`, straight into the read buffer,
+    /// without the zeroing `Compat`'s `AsyncRead` needs.
+    ///
     /// # Type Parameters
     ///
     /// * `S`: The type of the Tokio stream.
@@ -53,7 +90,9 @@ impl MessageTokio {
         S: TokioAsyncRead + Unpin,
         D: Decoder<DT>,
     {
-        AsyncMessageIo::new_reader(stream.compat(), decoder)
+        let mut io = AsyncMessageIo::new_reader(stream.compat(), decoder);
+        io.set_poll_read(poll_read_native::<S>);
+        io
     }
 
     /// Creates a new MessageIo instance for writing with the given Tokio stream.
@@ -79,4 +118,782 @@ impl MessageTokio {
     {
         AsyncMessageIo::new_writer(stream.compat_write(), encoder)
     }
+
+    /// Connects to `addr` over TCP and frames the connection with the given codec.
+    ///
+    /// `TCP_NODELAY` is set on the socket, messages are usually small and written whole, so
+    /// Nagle's algorithm would only delay them.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `A`: The type of the address to connect to.
+    /// * `ED`: The type of the encoder/decoder.
+    /// * `EDT`: The type of the input/out data to be encoded/decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr`: The address to connect to.
+    /// * `enc_dec`: A struct that implements both `Encoder` and `Decoder` traits. Needs to be clone as well.
+    ///
+    /// # Returns
+    ///
+    /// A new async instance of `MessageIo` for reading and writing over the connection.
+    pub async fn connect<A, ED, EDT>(
+        addr: A,
+        enc_dec: ED,
+    ) -> io::Result<AsyncMessageIo<Compat<TcpStream>, ED, ED>>
+    where
+        A: ToSocketAddrs,
+        ED: Encoder<EDT> + Decoder<EDT> + Clone,
+    {
+        let stream = TcpStream::connect(addr).await?;
+        Self::accept(stream, enc_dec)
+    }
+
+    /// Frames an accepted TCP connection with the given codec, setting `TCP_NODELAY` like
+    /// `connect`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `ED`: The type of the encoder/decoder.
+    /// * `EDT`: The type of the input/out data to be encoded/decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: A connection returned by `TcpListener::accept`.
+    /// * `enc_dec`: A struct that implements both `Encoder` and `Decoder` traits. Needs to be clone as well.
+    ///
+    /// # Returns
+    ///
+    /// A new async instance of `MessageIo` for reading and writing over the connection.
+    pub fn accept<ED, EDT>(
+        stream: TcpStream,
+        enc_dec: ED,
+    ) -> io::Result<AsyncMessageIo<Compat<TcpStream>, ED, ED>>
+    where
+        ED: Encoder<EDT> + Decoder<EDT> + Clone,
+    {
+        stream.set_nodelay(true)?;
+        Ok(Self::new_rw(stream, enc_dec))
+    }
+
+    /// Frames a TLS connection established with `tokio-rustls` with the given codec.
+    ///
+    /// This is `new_rw` for a `tokio_rustls::TlsStream`, the stream of either side of a
+    /// connection, and needs no more than the `TlsStream::Client` or `TlsStream::Server` from
+    /// `TlsConnector::connect` or `TlsAcceptor::accept` converted with `into()`. The
+    /// `client::TlsStream` and `server::TlsStream` can also be passed to `new_rw` directly, like
+    /// any stream implementing tokio's `AsyncRead` and `AsyncWrite`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `IO`: The type of the underlying transport, e.g. `TcpStream`.
+    /// * `ED`: The type of the encoder/decoder.
+    /// * `EDT`: The type of the input/out data to be encoded/decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: A TLS connection that completed its handshake.
+    /// * `enc_dec`: A struct that implements both `Encoder` and `Decoder` traits. Needs to be clone as well.
+    ///
+    /// # Returns
+    ///
+    /// A new async instance of `MessageIo` for reading and writing over the connection.
+    #[cfg(feature = "tls")]
+    pub fn with_tls<IO, ED, EDT>(
+        stream: tokio_rustls::TlsStream<IO>,
+        enc_dec: ED,
+    ) -> AsyncMessageIo<Compat<tokio_rustls::TlsStream<IO>>, ED, ED>
+    where
+        IO: TokioAsyncRead + TokioAsyncWrite + Unpin,
+        ED: Encoder<EDT> + Decoder<EDT> + Clone,
+    {
+        Self::new_rw(stream, enc_dec)
+    }
+
+    /// Frames a blocking `std::io` stream, e.g. a serial port or a pipe without async support,
+    /// for use from async code.
+    ///
+    /// Every read, write and flush runs as a `tokio::task::spawn_blocking` task, so each one
+    /// occupies a thread of tokio's blocking pool for as long as the stream blocks, and written
+    /// bytes are copied to hand them to that thread. The operations run one at a time: a read
+    /// waiting for data holds the stream until it completes, delaying writes queued behind it.
+    /// Prefer a native async stream where one exists.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `S`: The type of the blocking stream.
+    /// * `ED`: The type of the encoder/decoder.
+    /// * `EDT`: The type of the input/out data to be encoded/decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: A blocking stream that implements both `Read` and `Write`.
+    /// * `enc_dec`: A struct that implements both `Encoder` and `Decoder` traits. Needs to be clone as well.
+    ///
+    /// # Returns
+    ///
+    /// A new async instance of `MessageIo`. Using it outside a tokio runtime panics.
+    pub fn from_blocking<S, ED, EDT>(
+        stream: S,
+        enc_dec: ED,
+    ) -> AsyncMessageIo<Compat<BlockingStream<S>>, ED, ED>
+    where
+        S: Read + Write + Send + 'static,
+        ED: Encoder<EDT> + Decoder<EDT> + Clone,
+    {
+        Self::new_rw(BlockingStream::new(stream), enc_dec)
+    }
+}
+
+/// Tokio stream running the reads and writes of a blocking stream on the blocking thread pool,
+/// created by `MessageTokio::from_blocking`.
+///
+/// Writes complete as soon as their bytes are handed to the pool, an error writing them is
+/// returned by the next write or flush.
+pub struct BlockingStream<S> {
+    /// The stream, unless an operation on it is in flight.
+    stream: Option<S>,
+    task: Option<JoinHandle<(S, BlockingOp)>>,
+    /// Bytes read beyond what the caller's buffer could take, or the error of the last read.
+    read_result: Option<io::Result<BytesMut>>,
+    write_error: Option<io::Error>,
+    flushed: bool,
+}
+
+enum BlockingOp {
+    Read(io::Result<Vec<u8>>),
+    Write(io::Result<()>),
+    Flush(io::Result<()>),
+}
+
+// The stream is only ever moved, never pinned.
+impl<S> Unpin for BlockingStream<S> {}
+
+impl<S> BlockingStream<S> {
+    /// Wraps a blocking stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream`: A blocking stream that implements `Read` and/or `Write`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `BlockingStream`.
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream: Some(stream),
+            task: None,
+            read_result: None,
+            write_error: None,
+            flushed: false,
+        }
+    }
+
+    /// Waits for the operation in flight, if any, and stores its outcome.
+    fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(task) = &mut self.task {
+            let result = ready!(Pin::new(task).poll(cx));
+            self.task = None;
+            let (stream, op) = result.map_err(io::Error::other)?;
+            self.stream = Some(stream);
+            match op {
+                BlockingOp::Read(result) => {
+                    self.read_result = Some(result.map(|data| data.as_slice().into()));
+                }
+                BlockingOp::Write(result) => self.write_error = result.err(),
+                BlockingOp::Flush(result) => match result {
+                    Ok(()) => self.flushed = true,
+                    Err(e) => self.write_error = Some(e),
+                },
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Runs `op` on the stream in a blocking task. The previous operation must be complete.
+    fn spawn(&mut self, op: impl FnOnce(&mut S) -> BlockingOp + Send + 'static) -> io::Result<()>
+    where
+        S: Send + 'static,
+    {
+        let mut stream = self
+            .stream
+            .take()
+            .ok_or_else(|| io::Error::other("Blocking stream was lost to a failed operation"))?;
+        self.task = Some(tokio::task::spawn_blocking(move || {
+            let op = op(&mut stream);
+            (stream, op)
+        }));
+        Ok(())
+    }
+}
+
+impl<S: Read + Send + 'static> TokioAsyncRead for BlockingStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            if let Some(result) = this.read_result.take() {
+                let mut data = result?;
+                let len = data.len().min(buf.remaining());
+                buf.put_slice(&data.split_to(len));
+                if !data.is_empty() {
+                    this.read_result = Some(Ok(data));
+                }
+                return Poll::Ready(Ok(()));
+            }
+            ready!(this.poll_complete(cx))?;
+            if this.read_result.is_none() {
+                let len = buf.remaining();
+                this.spawn(move |stream| {
+                    let mut data = vec![0; len];
+                    let result = loop {
+                        match stream.read(&mut data) {
+                            Ok(n) => {
+                                data.truncate(n);
+                                break Ok(data);
+                            }
+                            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                            Err(e) => break Err(e),
+                        }
+                    };
+                    BlockingOp::Read(result)
+                })?;
+            }
+        }
+    }
+}
+
+impl<S: Write + Send + 'static> TokioAsyncWrite for BlockingStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_complete(cx))?;
+        if let Some(e) = this.write_error.take() {
+            return Poll::Ready(Err(e));
+        }
+        let data = buf.to_vec();
+        this.spawn(move |stream| BlockingOp::Write(stream.write_all(&data)))?;
+        this.flushed = false;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.poll_complete(cx))?;
+            if let Some(e) = this.write_error.take() {
+                return Poll::Ready(Err(e));
+            }
+            if this.flushed {
+                return Poll::Ready(Ok(()));
+            }
+            this.spawn(|stream| BlockingOp::Flush(stream.flush()))?;
+        }
+    }
+
+    /// Flushes the stream. A blocking stream has no shutdown of its own, it is closed when
+    /// dropped.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Reads from the tokio stream inside `stream` into `spare`, left uninitialized, instead of
+/// zeroing it for `Compat`'s `AsyncRead` first.
+fn poll_read_native<S: TokioAsyncRead + Unpin>(
+    stream: Pin<&mut Compat<S>>,
+    cx: &mut Context<'_>,
+    spare: &mut [MaybeUninit<u8>],
+) -> Poll<io::Result<usize>> {
+    let mut buf = ReadBuf::uninit(spare);
+    ready!(Pin::new(stream.get_mut().get_mut()).poll_read(cx, &mut buf))?;
+    Poll::Ready(Ok(buf.filled().len()))
+}
+
+impl<S, E, D> AsyncMessageIo<S, E, D> {
+    /// Reads a message from the stream, giving up once `duration` has passed.
+    ///
+    /// The timeout covers the whole read, however many reads from the stream decoding takes.
+    /// Bytes received before it expires stay buffered, so a later read picks up the frame
+    /// where this one left off.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration`: How long to wait for a complete message.
+    ///
+    /// # Returns
+    ///
+    /// The result of `read_message`, or an error of kind `io::ErrorKind::TimedOut` if no
+    /// message was decoded in time.
+    pub async fn read_message_timeout<M>(&mut self, duration: Duration) -> io::Result<Option<M>>
+    where
+        D: Decoder<M>,
+        S: AsyncRead + Unpin,
+    {
+        match tokio::time::timeout(duration, self.read_message()).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out waiting for a message",
+            )),
+        }
+    }
+
+    /// Writes a message to the stream, giving up once `duration` has passed.
+    ///
+    /// Part of the frame may already be written when the timeout expires. The rest stays
+    /// queued and the next `write_message` call finishes it before writing its own message,
+    /// see `AsyncMessageIo::write_message`. Still, a peer that stopped reading for that long
+    /// may never take it, and dropping the handler leaves a truncated frame on the wire. Close
+    /// the stream on timeout rather than keep using it.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the message to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `message`: The message to write.
+    /// * `duration`: How long to wait for the write to complete.
+    ///
+    /// # Returns
+    ///
+    /// The result of `write_message`, or an error of kind `io::ErrorKind::TimedOut` if the
+    /// message wasn't written in time.
+    pub async fn write_message_timeout<M>(
+        &mut self,
+        message: M,
+        duration: Duration,
+    ) -> io::Result<usize>
+    where
+        E: Encoder<M>,
+        S: AsyncWrite + Unpin,
+    {
+        match tokio::time::timeout(duration, self.write_message(message)).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out writing a message",
+            )),
+        }
+    }
+
+    /// Spawns a task reading messages and sending each one to `tx`.
+    ///
+    /// The task ends with `Ok(())` at the end of the stream or once every receiver is dropped,
+    /// and with the error of the first failed read otherwise.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the messages to be decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx`: The channel to send the messages to.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the spawned task.
+    pub fn spawn_reader<M>(mut self, tx: mpsc::Sender<M>) -> JoinHandle<io::Result<()>>
+    where
+        M: Send + 'static,
+        S: AsyncRead + Unpin + Send + 'static,
+        E: Send + 'static,
+        D: Decoder<M> + Send + 'static,
+    {
+        tokio::spawn(async move {
+            while let Some(message) = self.read_message().await? {
+                if tx.send(message).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Spawns a task writing every message received from `rx`.
+    ///
+    /// Messages are flushed whenever the channel runs empty, so a burst of messages is written
+    /// together. Once every sender is dropped the task closes the write side of the stream and
+    /// ends with `Ok(())`, a failed write ends it with the error.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the messages to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `rx`: The channel to receive the messages from.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the spawned task.
+    pub fn spawn_writer<M>(mut self, mut rx: mpsc::Receiver<M>) -> JoinHandle<io::Result<()>>
+    where
+        M: Send + 'static,
+        S: AsyncWrite + Unpin + Send + 'static,
+        E: Encoder<M> + Send + 'static,
+        D: Send + 'static,
+    {
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                self.write_message(message).await?;
+                if rx.is_empty() {
+                    self.flush().await?;
+                }
+            }
+            self.close_write().await
+        })
+    }
+
+    /// Spawns a task owning the writer, returning a cloneable handle for queueing messages.
+    ///
+    /// Built on `spawn_writer` for several producers sharing one connection: each message is
+    /// written whole, in the order the sends completed, so frames of different producers never
+    /// interleave. The task ends like the one of `spawn_writer` once every handle is dropped.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `M`: The type of the messages to be encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity`: The number of messages the queue holds before `send` waits.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the queue and the handle of the spawned task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn spawn_write_queue<M>(
+        self,
+        capacity: usize,
+    ) -> (WriterHandle<M>, JoinHandle<io::Result<()>>)
+    where
+        M: Send + 'static,
+        S: AsyncWrite + Unpin + Send + 'static,
+        E: Encoder<M> + Send + 'static,
+        D: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(capacity);
+        (WriterHandle { tx }, self.spawn_writer(rx))
+    }
+}
+
+/// Cloneable handle queueing messages for the writer task started by `spawn_write_queue`.
+#[derive(Debug)]
+pub struct WriterHandle<M> {
+    tx: mpsc::Sender<M>,
+}
+
+impl<M> WriterHandle<M> {
+    /// Queues a message for writing, waiting while the queue is full.
+    ///
+    /// Returning doesn't mean the message was written, a failed write ends the task and its
+    /// error is returned by the task's `JoinHandle`.
+    ///
+    /// # Arguments
+    ///
+    /// * `message`: The message to write.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once queued, or an error of kind `io::ErrorKind::BrokenPipe` if the task has
+    /// ended.
+    pub async fn send(&self, message: M) -> io::Result<()> {
+        self.tx
+            .send(message)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Writer task has ended"))
+    }
+}
+
+impl<M> Clone for WriterHandle<M> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+/// Handle of a keepalive task started by `spawn_keepalive`.
+///
+/// Dropping the handle stops the task as well, so keep it for as long as keepalives should be
+/// sent.
+#[derive(Debug)]
+pub struct KeepaliveHandle {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<io::Result<()>>,
+}
+
+impl KeepaliveHandle {
+    /// Stops sending keepalives, letting a keepalive that is being written finish first.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or the error of the failed write that ended the task early.
+    pub async fn stop(self) -> io::Result<()> {
+        let Self { stop, task } = self;
+        drop(stop);
+        task.await.map_err(io::Error::other)?
+    }
+}
+
+/// Spawns a task writing a keepalive message to `writer` every `interval`, e.g. to keep an
+/// idle connection from timing out.
+///
+/// The writer is shared behind a mutex so other tasks can keep writing to it, each keepalive
+/// is written and flushed while holding the lock and never lands in the middle of another
+/// frame. The task only keeps a weak reference and ends once every other reference to the
+/// writer is dropped, once the returned handle is stopped or dropped, or with the error of the
+/// first failed write.
+///
+/// # Type Parameters
+///
+/// * `M`: The type of the keepalive messages.
+///
+/// # Arguments
+///
+/// * `writer`: The writer to send keepalives through.
+/// * `interval`: How long to wait between keepalives, the first one is sent after `interval`.
+/// * `make_msg`: Creates each keepalive message.
+///
+/// # Returns
+///
+/// The handle of the keepalive task.
+pub fn spawn_keepalive<S, E, D, M>(
+    writer: &Arc<Mutex<AsyncMessageIo<S, E, D>>>,
+    interval: Duration,
+    make_msg: impl Fn() -> M + Send + 'static,
+) -> KeepaliveHandle
+where
+    M: Send + 'static,
+    S: AsyncWrite + Unpin + Send + 'static,
+    E: Encoder<M> + Send + 'static,
+    D: Send + 'static,
+{
+    let writer = Arc::downgrade(writer);
+    let (stop, mut stopped) = oneshot::channel();
+    let task = tokio::spawn(async move {
+        let mut ticks = time::interval_at(time::Instant::now() + interval, interval);
+        ticks.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = &mut stopped => return Ok(()),
+                _ = ticks.tick() => {}
+            }
+            let Some(writer) = writer.upgrade() else {
+                return Ok(());
+            };
+            let mut writer = writer.lock().await;
+            writer.write_message(make_msg()).await?;
+            writer.flush().await?;
+        }
+    });
+    KeepaliveHandle { stop, task }
+}
+
+/// Adapts a `tokio_util` codec (e.g. `LinesCodec`) to this crate's `Encoder` and `Decoder`.
+#[derive(Debug, Clone)]
+pub struct TokioCodecAdapter<C> {
+    codec: C,
+    consumed: usize,
+}
+
+impl<C> TokioCodecAdapter<C> {
+    /// Wraps a `tokio_util` codec.
+    ///
+    /// # Arguments
+    ///
+    /// * `codec`: A codec implementing `tokio_util::codec::Encoder` and/or `Decoder`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `TokioCodecAdapter`.
+    pub fn new(codec: C) -> Self {
+        Self { codec, consumed: 0 }
+    }
+
+    /// Consumes the adapter, returning the wrapped codec.
+    pub fn into_inner(self) -> C {
+        self.codec
+    }
+}
+
+impl<C, T> Encoder<T> for TokioCodecAdapter<C>
+where
+    C: tokio_codec::Encoder<T>,
+    C::Error: Error + Send + Sync + 'static,
+{
+    type Error = C::Error;
+
+    fn encode(&mut self, data: T) -> Result<Vec<u8>, C::Error> {
+        let mut dst = BytesMut::new();
+        self.codec.encode(data, &mut dst)?;
+        Ok(dst.to_vec())
+    }
+
+    fn encode_into(&mut self, data: T, dst: &mut BytesMut) -> Result<(), C::Error> {
+        self.codec.encode(data, dst)
+    }
+}
+
+impl<C> Decoder<C::Item> for TokioCodecAdapter<C>
+where
+    C: tokio_codec::Decoder,
+    C::Error: Error + Send + Sync + 'static,
+{
+    type Error = C::Error;
+
+    fn decode(&mut self, data: &[u8]) -> DecoderResult<C::Item, C::Error> {
+        // tokio codecs may consume a header from `src` before the body is complete and keep
+        // it in their own state, so those bytes must not be handed to them again.
+        let Some(pending) = data.get(self.consumed..) else {
+            self.consumed = 0;
+            return DecoderResult::Error(
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Buffered data shrank below consumed bytes",
+                )
+                .into(),
+            );
+        };
+        let mut src = BytesMut::from(pending);
+        let result = self.codec.decode(&mut src);
+        let consumed = self.consumed + pending.len() - src.len();
+        match result {
+            Ok(Some(item)) => {
+                self.consumed = 0;
+                DecoderResult::Done(item, consumed)
+            }
+            Ok(None) => {
+                self.consumed = consumed;
+                DecoderResult::Continue
+            }
+            Err(e) => {
+                self.consumed = 0;
+                DecoderResult::Error(e)
+            }
+        }
+    }
+}
+
+/// Adapts this crate's `Encoder`/`Decoder` to the `tokio_util` codec traits, so it can be used
+/// with `tokio_util::codec::Framed` and friends.
+///
+/// # Type Parameters
+///
+/// * `C`: The wrapped codec.
+/// * `M`: The message type produced when decoding.
+pub struct MsgIoCodecAdapter<C, M> {
+    codec: C,
+    in_frame: bool,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<C, M> MsgIoCodecAdapter<C, M> {
+    /// Wraps a codec implementing this crate's `Encoder` and/or `Decoder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `codec`: The codec to wrap.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MsgIoCodecAdapter`.
+    pub fn new(codec: C) -> Self {
+        Self {
+            codec,
+            in_frame: false,
+            _message: PhantomData,
+        }
+    }
+
+    /// Consumes the adapter, returning the wrapped codec.
+    pub fn into_inner(self) -> C {
+        self.codec
+    }
+}
+
+impl<C, M, T> tokio_codec::Encoder<T> for MsgIoCodecAdapter<C, M>
+where
+    C: Encoder<T>,
+{
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> io::Result<()> {
+        if let Some(len) = self.codec.size_hint(&item) {
+            dst.reserve(len);
+        }
+        let start = dst.len();
+        self.codec.encode_into(item, dst).map_err(|e| {
+            dst.truncate(start);
+            io::Error::new(io::ErrorKind::InvalidData, e)
+        })
+    }
+}
+
+impl<C, M> tokio_codec::Decoder for MsgIoCodecAdapter<C, M>
+where
+    C: Decoder<M>,
+{
+    type Item = M;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<M>> {
+        if src.is_empty() || src.len() < self.codec.min_frame_hint() {
+            return Ok(None);
+        }
+        let result = decode_step(&mut self.codec, src, true, C::decode_bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        match result {
+            DecoderResult::Continue => Ok(None),
+            DecoderResult::NeedMore(len) => {
+                src.reserve(len.min(MAX_READ_AHEAD));
+                Ok(None)
+            }
+            DecoderResult::ContinueFrom(_) => {
+                self.in_frame = true;
+                Ok(None)
+            }
+            DecoderResult::Done(msg, _) => {
+                self.in_frame = false;
+                Ok(Some(msg))
+            }
+            DecoderResult::Error(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            DecoderResult::ErrorAt(e, offset) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                DecodeError::new(offset, e),
+            )),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<M>> {
+        match self.decode(src)? {
+            Some(msg) => Ok(Some(msg)),
+            // Bytes handed off through `ContinueFrom` are gone from `src`, so an empty buffer
+            // alone doesn't mean the stream ended cleanly.
+            None if src.is_empty() && !self.in_frame => Ok(None),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Stream ended in the middle of a frame",
+            )),
+        }
+    }
 }